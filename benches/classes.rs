@@ -0,0 +1,54 @@
+//! Compares lexing with the built-in character classes, which test ASCII
+//! bytes without decoding them, with the same classes as [`Rule::Chars`]
+//! predicates, which decode every character, on about 1 MB of identifiers
+//! and numbers.
+//!
+//! Run with `cargo bench --bench classes`.
+
+use std::{hint::black_box, time::Instant};
+
+use mile::{Lexer, Rule};
+
+const ASCII: Rule<'static, usize> = Rule::Any(&[
+    Rule::Ignore(&Rule::Whitespace),
+    Rule::Value(&Rule::Alphabetic, &str::len),
+    Rule::Value(&Rule::Numeric, &str::len),
+]);
+
+const UNICODE: Rule<'static, usize> = Rule::Any(&[
+    Rule::Ignore(&Rule::Chars(char::is_whitespace)),
+    Rule::Value(&Rule::Chars(char::is_alphabetic), &str::len),
+    Rule::Value(&Rule::Chars(char::is_numeric), &str::len),
+]);
+
+fn main() {
+    let words = ["identifier", "x", "0123456789", "local", "42"];
+    let mut input = String::new();
+
+    while input.len() < 1024 * 1024 {
+        let word = words[input.len() % words.len()];
+        input.push_str(&word.repeat(1 + input.len() % 64));
+        input.push_str(if input.len().is_multiple_of(3) {
+            "\n"
+        } else {
+            " "
+        });
+    }
+
+    let input: &'static str = input.leak();
+    let mut counts = Vec::new();
+
+    for (name, rule) in [("classes", ASCII), ("Chars", UNICODE)] {
+        let started = Instant::now();
+        let count = black_box(Lexer::with_buffer(rule, black_box(input)).count());
+
+        println!(
+            "{name:>8}: {count} tokens from {} bytes in {:?}",
+            input.len(),
+            started.elapsed()
+        );
+        counts.push(count);
+    }
+
+    assert_eq!(counts[0], counts[1]);
+}
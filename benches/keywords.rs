@@ -0,0 +1,124 @@
+//! Compares a sorted [`Rule::Keywords`] table with an `Any` of one literal
+//! rule per keyword, on about 1 MB of synthetic Lua.
+//!
+//! Run with `cargo bench --bench keywords`.
+
+use std::{hint::black_box, time::Instant};
+
+use mile::{Lexer, Rule};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Keyword(usize),
+    Identifier,
+    Number,
+    Punctuation,
+}
+
+const CHUNK: &str = "
+local function fib(n)
+    if n < 2 then
+        return n
+    elseif n == 2 then
+        return 1
+    else
+        return fib(n - 1) + fib(n - 2)
+    end
+end
+
+for index = 1, 10 do
+    while not done and index ~= nil do
+        repeat count = count + 1 until count > index or false
+    end
+end
+";
+
+const PUNCTUATION: Rule<'static, Token> = Rule::Value(
+    &Rule::Literals(&["(", ")", ",", "+", "-", "<", ">", "=", "==", "~="]),
+    &|_| Token::Punctuation,
+);
+
+const TABLE: Rule<'static, Token> = Rule::Any(&[
+    Rule::Ignore(&Rule::Whitespace),
+    Rule::Keywords(&[
+        ("and", || Token::Keyword(0)),
+        ("break", || Token::Keyword(1)),
+        ("do", || Token::Keyword(2)),
+        ("else", || Token::Keyword(3)),
+        ("elseif", || Token::Keyword(4)),
+        ("end", || Token::Keyword(5)),
+        ("false", || Token::Keyword(6)),
+        ("for", || Token::Keyword(7)),
+        ("function", || Token::Keyword(8)),
+        ("if", || Token::Keyword(9)),
+        ("in", || Token::Keyword(10)),
+        ("local", || Token::Keyword(11)),
+        ("nil", || Token::Keyword(12)),
+        ("not", || Token::Keyword(13)),
+        ("or", || Token::Keyword(14)),
+        ("repeat", || Token::Keyword(15)),
+        ("return", || Token::Keyword(16)),
+        ("then", || Token::Keyword(17)),
+        ("true", || Token::Keyword(18)),
+        ("until", || Token::Keyword(19)),
+        ("while", || Token::Keyword(20)),
+    ]),
+    Rule::Value(&Rule::Alphabetic, &|_| Token::Identifier),
+    Rule::Value(&Rule::Numeric, &|_| Token::Number),
+    PUNCTUATION,
+]);
+
+const LITERALS: Rule<'static, Token> = Rule::Any(&[
+    Rule::Ignore(&Rule::Whitespace),
+    Rule::Value(&Rule::Literal("and"), &|_| Token::Keyword(0)),
+    Rule::Value(&Rule::Literal("break"), &|_| Token::Keyword(1)),
+    Rule::Value(&Rule::Literal("do"), &|_| Token::Keyword(2)),
+    Rule::Value(&Rule::Literal("else"), &|_| Token::Keyword(3)),
+    Rule::Value(&Rule::Literal("elseif"), &|_| Token::Keyword(4)),
+    Rule::Value(&Rule::Literal("end"), &|_| Token::Keyword(5)),
+    Rule::Value(&Rule::Literal("false"), &|_| Token::Keyword(6)),
+    Rule::Value(&Rule::Literal("for"), &|_| Token::Keyword(7)),
+    Rule::Value(&Rule::Literal("function"), &|_| Token::Keyword(8)),
+    Rule::Value(&Rule::Literal("if"), &|_| Token::Keyword(9)),
+    Rule::Value(&Rule::Literal("in"), &|_| Token::Keyword(10)),
+    Rule::Value(&Rule::Literal("local"), &|_| Token::Keyword(11)),
+    Rule::Value(&Rule::Literal("nil"), &|_| Token::Keyword(12)),
+    Rule::Value(&Rule::Literal("not"), &|_| Token::Keyword(13)),
+    Rule::Value(&Rule::Literal("or"), &|_| Token::Keyword(14)),
+    Rule::Value(&Rule::Literal("repeat"), &|_| Token::Keyword(15)),
+    Rule::Value(&Rule::Literal("return"), &|_| Token::Keyword(16)),
+    Rule::Value(&Rule::Literal("then"), &|_| Token::Keyword(17)),
+    Rule::Value(&Rule::Literal("true"), &|_| Token::Keyword(18)),
+    Rule::Value(&Rule::Literal("until"), &|_| Token::Keyword(19)),
+    Rule::Value(&Rule::Literal("while"), &|_| Token::Keyword(20)),
+    Rule::Value(&Rule::Alphabetic, &|_| Token::Identifier),
+    Rule::Value(&Rule::Numeric, &|_| Token::Number),
+    PUNCTUATION,
+]);
+
+fn lex(rule: Rule<'static, Token>, input: &'static str) -> Vec<Token> {
+    Lexer::with_buffer(rule, input)
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn main() {
+    let input: &'static str = CHUNK.repeat((1 << 20) / CHUNK.len()).leak();
+
+    let mut results = Vec::new();
+
+    for (name, rule) in [("Any of literals", LITERALS), ("Rule::Keywords", TABLE)] {
+        let start = Instant::now();
+        let tokens = black_box(lex(rule, black_box(input)));
+        let elapsed = start.elapsed();
+
+        println!(
+            "{name:>16}: {} tokens from {} bytes in {elapsed:?}",
+            tokens.len(),
+            input.len()
+        );
+        results.push(tokens);
+    }
+
+    assert_eq!(results[0], results[1]);
+}
@@ -0,0 +1,85 @@
+//! Compares finding the longest match by evaluating every prefix with
+//! [`Rule::matches_in`] with growing a single [`Matcher`](mile::Matcher), on about 100 KB of
+//! long identifiers.
+//!
+//! Run with `cargo bench --bench matcher`.
+
+use std::{hint::black_box, time::Instant};
+
+use mile::{Context, MatchResult, Rule};
+
+const RULE: Rule<'static, usize> = Rule::Any(&[
+    Rule::Ignore(&Rule::Whitespace),
+    Rule::Keywords(&[("end", || 0), ("function", || 1), ("local", || 2)]),
+    Rule::Value(&Rule::Alphabetic, &str::len),
+]);
+
+/// Returns the end of the longest match, given the results of the growing
+/// slices starting at a token.
+fn longest(results: impl Iterator<Item = (usize, MatchResult<usize>)>) -> usize {
+    let mut last = 0;
+
+    for (end, result) in results {
+        match result {
+            MatchResult::Match(_) => last = end,
+            MatchResult::PartialMatch => {}
+            _ => break,
+        }
+    }
+
+    last
+}
+
+fn tokens(input: &str, mut next: impl FnMut(usize) -> usize) -> usize {
+    let mut start = 0;
+    let mut count = 0;
+
+    while start < input.len() {
+        start = next(start);
+        count += 1;
+    }
+
+    count
+}
+
+fn main() {
+    let words = ["local", "identifier", "function", "end", "x"];
+    let mut input = String::new();
+
+    while input.len() < 100 * 1024 {
+        let word = words[input.len() % words.len()];
+        input.push_str(&word.repeat(1 + input.len() % 40));
+        input.push(' ');
+    }
+
+    let input: &'static str = input.leak();
+
+    let rescan = |start| {
+        let ends = input[start..].char_indices().map(|(offset, c)| {
+            let end = start + offset + c.len_utf8();
+            (end, RULE.matches_in(Context::new(input, start..end)))
+        });
+
+        longest(ends)
+    };
+    let incremental = |start| longest(RULE.matcher(input, start));
+
+    let mut counts = Vec::new();
+
+    for (name, next) in [
+        ("matches_in", &rescan as &dyn Fn(usize) -> usize),
+        ("Matcher", &incremental),
+    ] {
+        let started = Instant::now();
+        let count = black_box(tokens(black_box(input), next));
+
+        println!(
+            "{name:>10}: {count} tokens from {} bytes in {:?}",
+            input.len(),
+            started.elapsed()
+        );
+        counts.push(count);
+    }
+
+    assert_eq!(counts[0], counts[1]);
+}
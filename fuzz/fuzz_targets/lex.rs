@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mile::{fuzz_lex, FuzzOutcome, RuleDesc};
+
+fuzz_target!(|data: &[u8]| {
+    let mut data = data;
+    let desc = RuleDesc::decode(&mut data);
+
+    if let Ok(input) = core::str::from_utf8(data) {
+        assert!(!matches!(fuzz_lex(&desc, input), FuzzOutcome::Exhausted));
+    }
+});
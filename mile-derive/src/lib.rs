@@ -0,0 +1,230 @@
+//! Derive macros for `mile`, generating the rule of a token enum from
+//! attributes on its variants, and the names of its tokens. Use them through
+//! `mile::Lexable` and `mile::TokenName` with the `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error, Fields, Ident,
+    LitStr, Result, Variant,
+};
+
+/// The built-in classes usable in `#[rule(..)]` and `#[skip(..)]`, by the
+/// name they are written with and the `Rule` variant they stand for.
+const CLASSES: &[(&str, &str)] = &[
+    ("alphabetic", "Alphabetic"),
+    ("float", "Float"),
+    ("hex_integer", "HexInteger"),
+    ("integer", "Integer"),
+    ("number", "Number"),
+    ("numeric", "Numeric"),
+    ("whitespace", "Whitespace"),
+];
+
+/// Generates `rule()`, returning a `Rule::Any` with an alternative for each
+/// `#[token("..")]` and `#[rule(class)]` attribute in declaration order,
+/// after the `#[skip(class)]` alternatives of the enum, which are ignored,
+/// and `lexer(input)`, returning a `Lexer` over `input` with that rule.
+/// Skipped classes are named `skip` rather than `ignore`, which would clash
+/// with the built-in test attribute.
+///
+/// Unit variants are produced as is, while variants with a single field
+/// receive the matched slice. Variants without attributes aren't lexed.
+#[proc_macro_derive(Lexable, attributes(token, rule, skip))]
+pub fn derive_lexable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new(
+            input.ident.span(),
+            "`Lexable` can only be derived for enums",
+        ));
+    };
+
+    let mut rules = Vec::new();
+
+    for attr in attrs(&input.attrs, "skip") {
+        let class = class(attr)?;
+        rules.push(quote!(::mile::Rule::Ignore(&#class)));
+    }
+
+    for variant in &data.variants {
+        if let Some(attr) = attrs(&variant.attrs, "skip").next() {
+            return Err(Error::new_spanned(
+                attr,
+                "`#[skip]` belongs on the enum, not on a variant",
+            ));
+        }
+
+        let tokens = attrs(&variant.attrs, "token").collect::<Vec<_>>();
+        let classes = attrs(&variant.attrs, "rule").collect::<Vec<_>>();
+
+        match (tokens.first(), classes.first()) {
+            (Some(_), Some(attr)) => {
+                return Err(Error::new_spanned(
+                    attr,
+                    "a variant can't have both `#[token]` and `#[rule]`",
+                ));
+            }
+            (None, None) => continue,
+            _ => {}
+        }
+
+        let make = make(&input.ident, variant)?;
+
+        for attr in tokens {
+            let literal = attr.parse_args::<LitStr>().map_err(|error| {
+                Error::new(
+                    error.span(),
+                    "expected a string literal, as in `#[token(\"and\")]`",
+                )
+            })?;
+
+            rules.push(quote!(::mile::Rule::Value(&::mile::Rule::Literal(#literal), &#make)));
+        }
+
+        for attr in classes {
+            let class = class(attr)?;
+            rules.push(quote!(::mile::Rule::Value(&#class, &#make)));
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let (rule, lexer) = match input.generics.lifetimes().next() {
+        Some(lifetime) => {
+            let lifetime = &lifetime.lifetime;
+
+            (
+                quote!(fn rule() -> ::mile::Rule<#lifetime, Self>),
+                quote!(fn lexer(input: &#lifetime str) -> ::mile::Lexer<#lifetime, Self>),
+            )
+        }
+        None => (
+            quote!(fn rule<'a>() -> ::mile::Rule<'a, Self>),
+            quote!(fn lexer(input: &str) -> ::mile::Lexer<'_, Self>),
+        ),
+    };
+
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Returns the rule derived from the `#[token]`, `#[rule]` and
+            /// `#[skip]` attributes, trying alternatives in declaration
+            /// order.
+            pub #rule {
+                ::mile::Rule::Any(&[#(#rules),*])
+            }
+
+            /// Returns a lexer over `input` with the derived rule.
+            pub #lexer {
+                ::mile::Lexer::with_buffer(Self::rule(), input)
+            }
+        }
+    })
+}
+
+/// Generates `TokenName`, naming each variant by its `#[name("..")]`
+/// attribute, or after the variant itself if it has none.
+#[proc_macro_derive(TokenName, attributes(name))]
+pub fn derive_token_name(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_name(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_name(input: &DeriveInput) -> Result<TokenStream2> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new(
+            input.ident.span(),
+            "`TokenName` can only be derived for enums",
+        ));
+    };
+
+    let mut arms = Vec::new();
+
+    for variant in &data.variants {
+        let ident = &variant.ident;
+        let name = match attrs(&variant.attrs, "name").next() {
+            Some(attr) => attr.parse_args::<LitStr>().map_err(|error| {
+                Error::new(
+                    error.span(),
+                    "expected a string literal, as in `#[name(\"identifier\")]`",
+                )
+            })?,
+            None => LitStr::new(&ident.to_string(), ident.span()),
+        };
+
+        arms.push(quote!(Self::#ident { .. } => #name));
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::mile::TokenName for #name #ty_generics #where_clause {
+            fn name(&self) -> &'static str {
+                match self {
+                    #(#arms,)*
+                }
+            }
+        }
+    })
+}
+
+fn attrs<'a>(attrs: &'a [Attribute], name: &'a str) -> impl Iterator<Item = &'a Attribute> {
+    attrs.iter().filter(move |attr| attr.path().is_ident(name))
+}
+
+/// Parses the built-in class named by `attr`, such as `alphabetic` in
+/// `#[rule(alphabetic)]`, into the `Rule` it stands for.
+fn class(attr: &Attribute) -> Result<TokenStream2> {
+    let name = attr.parse_args::<Ident>().map_err(|error| {
+        Error::new(
+            error.span(),
+            "expected a class name, as in `#[rule(alphabetic)]`",
+        )
+    })?;
+
+    let Some((_, variant)) = CLASSES.iter().find(|(class, _)| name == class) else {
+        let names = CLASSES
+            .iter()
+            .map(|(class, _)| *class)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        return Err(Error::new(
+            name.span(),
+            format!("unknown class `{name}`, expected one of {names}"),
+        ));
+    };
+
+    let variant = Ident::new(variant, Span::call_site());
+
+    Ok(quote!(::mile::Rule::#variant))
+}
+
+/// Returns the callback making `variant` out of the matched slice.
+fn make(name: &Ident, variant: &Variant) -> Result<TokenStream2> {
+    let ident = &variant.ident;
+
+    match &variant.fields {
+        Fields::Unit => Ok(quote!(|_| #name::#ident)),
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+            Ok(quote!(|value| #name::#ident(value)))
+        }
+        fields => Err(Error::new(
+            fields.span(),
+            "lexed variants must be unit variants or have a single field for the slice",
+        )),
+    }
+}
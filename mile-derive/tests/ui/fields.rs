@@ -0,0 +1,11 @@
+use mile::Lexable;
+
+#[derive(Lexable)]
+enum Token<'a> {
+    #[rule(numeric)]
+    Number { text: &'a str },
+    #[rule(alphabetic)]
+    Pair(&'a str, usize),
+}
+
+fn main() {}
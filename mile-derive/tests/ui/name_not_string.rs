@@ -0,0 +1,9 @@
+use mile::TokenName;
+
+#[derive(TokenName)]
+enum Token {
+    #[name(identifier)]
+    Identifier,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use mile::Lexable;
+
+#[derive(Lexable)]
+struct Token;
+
+fn main() {}
@@ -0,0 +1,9 @@
+use mile::Lexable;
+
+#[derive(Lexable)]
+enum Token {
+    #[skip(whitespace)]
+    Space,
+}
+
+fn main() {}
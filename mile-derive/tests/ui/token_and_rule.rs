@@ -0,0 +1,10 @@
+use mile::Lexable;
+
+#[derive(Lexable)]
+enum Token<'a> {
+    #[token("if")]
+    #[rule(alphabetic)]
+    Word(&'a str),
+}
+
+fn main() {}
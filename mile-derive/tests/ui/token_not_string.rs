@@ -0,0 +1,9 @@
+use mile::Lexable;
+
+#[derive(Lexable)]
+enum Token {
+    #[token(42)]
+    Answer,
+}
+
+fn main() {}
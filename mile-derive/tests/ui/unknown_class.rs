@@ -0,0 +1,10 @@
+use mile::Lexable;
+
+#[derive(Lexable)]
+#[skip(blank)]
+enum Token {
+    #[rule(alphabetic)]
+    Word,
+}
+
+fn main() {}
@@ -0,0 +1,136 @@
+use core::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use std::{fmt, io};
+
+use futures_core::Stream;
+use tokio::io::AsyncBufRead;
+
+use crate::{
+    stream::{Chunked, Grammar, Next},
+    LexErrorOwned,
+};
+
+/// Lexes input from an [`AsyncBufRead`] as it arrives, as a [`Stream`] of
+/// tokens.
+///
+/// Tokens are produced as soon as the lexer commits them and the last one is
+/// flushed at EOF, giving exactly the tokens a [`Lexer`](crate::Lexer) over
+/// the whole input would. Reader failures, input that isn't valid UTF-8 and
+/// lexing errors are reported as an [`AsyncLexError`], after which the
+/// stream ends. Dropping the stream at any point is safe.
+pub struct AsyncLexer<G: Grammar, R> {
+    chunked: Chunked<G>,
+    reader: R,
+    done: bool,
+}
+
+/// An error ending an [`AsyncLexer`] stream.
+#[derive(Debug)]
+pub enum AsyncLexError {
+    /// The reader failed, or the input isn't valid UTF-8, reported with
+    /// [`io::ErrorKind::InvalidData`].
+    Io(io::Error),
+    /// The lexer raised an error, with offsets into the whole input.
+    Lex(LexErrorOwned),
+}
+
+impl fmt::Display for AsyncLexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => error.fmt(f),
+            Self::Lex(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AsyncLexError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Lex(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for AsyncLexError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<LexErrorOwned> for AsyncLexError {
+    fn from(error: LexErrorOwned) -> Self {
+        Self::Lex(error)
+    }
+}
+
+impl<G: Grammar, R: AsyncBufRead> AsyncLexer<G, R> {
+    pub const fn new(grammar: G, reader: R) -> Self {
+        Self {
+            chunked: Chunked::new(grammar),
+            reader,
+            done: false,
+        }
+    }
+
+    /// Returns the number of bytes committed to tokens or skipped so far,
+    /// like [`ChunkedLexer::consumed_bytes`](crate::ChunkedLexer::consumed_bytes).
+    pub const fn consumed_bytes(&self) -> usize {
+        self.chunked.consumed_bytes()
+    }
+
+    /// Returns the number of bytes read from the reader so far. The
+    /// difference with [`AsyncLexer::consumed_bytes`] is the input held
+    /// until more arrives or the input ends.
+    pub fn fed_bytes(&self) -> usize {
+        self.chunked.fed_bytes()
+    }
+}
+
+/// The lexer's progress isn't pinned, as tokens are only ever moved out.
+impl<G: Grammar + Unpin, R: Unpin> Unpin for AsyncLexer<G, R> {}
+
+impl<G: Grammar + Unpin, R: AsyncBufRead + Unpin> Stream for AsyncLexer<G, R> {
+    type Item = Result<G::Token, AsyncLexError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        while !this.done {
+            match this.chunked.next_token() {
+                Next::Token(token) => return Poll::Ready(Some(Ok(token))),
+                Next::Error(error) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(error.into())));
+                }
+                Next::Done => this.done = true,
+                Next::NeedInput => {
+                    let result = match ready!(Pin::new(&mut this.reader).poll_fill_buf(cx)) {
+                        Ok([]) => this.chunked.finish(),
+                        Ok(bytes) => {
+                            let len = bytes.len();
+                            let result = this.chunked.push(bytes);
+                            Pin::new(&mut this.reader).consume(len);
+                            result
+                        }
+                        Err(error) => {
+                            this.done = true;
+                            return Poll::Ready(Some(Err(error.into())));
+                        }
+                    };
+
+                    if let Err(error) = result {
+                        this.done = true;
+                        let error = io::Error::new(io::ErrorKind::InvalidData, error);
+
+                        return Poll::Ready(Some(Err(error.into())));
+                    }
+                }
+            }
+        }
+
+        Poll::Ready(None)
+    }
+}
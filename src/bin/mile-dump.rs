@@ -0,0 +1,193 @@
+//! Lexes a file with a grammar spec and prints its tokens, to try out a
+//! grammar without writing any Rust.
+//!
+//! ```text
+//! mile-dump [--json] <spec.toml> <input>
+//! ```
+//!
+//! The spec is a [`GrammarSpec`] in TOML. Each token is printed on a line of
+//! its own with its position, span, name and text, or as JSON with `--json`.
+//! Lexing stops at the first error, which is reported after the tokens
+//! before it, and the exit code is 1. Mixed indentation and redundant
+//! leading zeros are reported as warnings, which don't change the exit code.
+
+use std::{env, fs, ops::Range, process::ExitCode};
+
+use mile::{leading_zero, mixed_indentation, Error, GrammarSpec, Lexer, Named, Rule, TokenName};
+use serde_json::{json, Value};
+
+const USAGE: &str = "usage: mile-dump [--json] <spec.toml> <input>";
+
+/// A token of a grammar loaded at runtime: the name given by its rule and the
+/// text it was lexed from.
+struct Token {
+    name: &'static str,
+    text: &'static str,
+}
+
+impl TokenName for Token {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+fn token(name: &'static str, text: &'static str) -> Token {
+    Token { name, text }
+}
+
+fn main() -> ExitCode {
+    let mut json = false;
+    let mut paths = Vec::new();
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    let [spec_path, input_path] = &paths[..] else {
+        eprintln!("{USAGE}");
+        return ExitCode::from(2);
+    };
+
+    let read = |path: &str| {
+        fs::read_to_string(path).map_err(|error| eprintln!("error: cannot read {path}: {error}"))
+    };
+    let (Ok(spec), Ok(input)) = (read(spec_path), read(input_path)) else {
+        return ExitCode::from(2);
+    };
+
+    // The input, the spec and its rule live until the process exits, which
+    // gives the tokens `'static` names.
+    let input = input.leak();
+    let spec: &'static GrammarSpec = match toml::from_str(&spec) {
+        Ok(spec) => Box::leak(Box::new(spec)),
+        Err(error) => {
+            eprintln!("error: invalid spec {spec_path}: {error}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let rule = Box::leak(Box::new(Rule::from_spec(spec, token)));
+    let mut lexer =
+        Lexer::with_buffer(rule.as_rule(), input).with_checks(&[mixed_indentation, leading_zero]);
+    let mut tokens = Vec::new();
+    let mut error = None;
+
+    while let Some(result) = lexer.next() {
+        match result {
+            Ok(token) => tokens.push((token, lexer.span())),
+            Err(e) => error = Some(e),
+        }
+    }
+
+    let failed = error.is_some();
+
+    if json {
+        let tokens = tokens
+            .iter()
+            .map(|(token, span)| {
+                let position = lexer.position(span.start);
+
+                json!({
+                    "token": token.name(),
+                    "text": token.text,
+                    "span": [span.start, span.end],
+                    "line": position.line,
+                    "column": position.column,
+                })
+            })
+            .collect::<Vec<_>>();
+        let warnings = lexer
+            .warnings()
+            .iter()
+            .map(|warning| {
+                let position = lexer.position(warning.span.start);
+
+                json!({
+                    "kind": format!("{:?}", warning.kind),
+                    "message": warning.message,
+                    "span": [warning.span.start, warning.span.end],
+                    "line": position.line,
+                    "column": position.column,
+                })
+            })
+            .collect::<Vec<_>>();
+        let error = error.map_or(Value::Null, |error| {
+            let error = lexer.map_err_span(error);
+            let position = lexer.position(error.span.start);
+
+            json!({
+                "kind": format!("{:?}", error.kind),
+                "message": error.message,
+                "span": [error.span.start, error.span.end],
+                "line": position.line,
+                "column": position.column,
+            })
+        });
+
+        println!(
+            "{:#}",
+            json!({ "tokens": tokens, "warnings": warnings, "error": error })
+        );
+    } else {
+        for (token, span) in &tokens {
+            let position = lexer.position(span.start);
+
+            println!(
+                "{}:{} {}..{} {} {:?}",
+                position.line,
+                position.column,
+                span.start,
+                span.end,
+                Named(token),
+                token.text
+            );
+        }
+
+        for warning in lexer.warnings() {
+            report(
+                &lexer,
+                &warning.to_string(),
+                warning.span.clone(),
+                input_path,
+            );
+        }
+
+        if let Some(error) = &error {
+            let header = format!("error: {}", error.display(Error::TEXT_LIMIT));
+            report(&lexer, &header, lexer.error_span(error), input_path);
+        }
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Prints `header` to stderr, followed by the line of `path` that `span`
+/// points at.
+fn report<T>(lexer: &Lexer<'_, T>, header: &str, span: Range<usize>, path: &str) {
+    let position = lexer.position(span.start);
+    let (line, range) = lexer.line_containing(span.start);
+    let gutter = " ".repeat(position.line.to_string().len());
+    let start = span.start.min(range.end);
+    let text = &line[start - range.start..span.end.clamp(start, range.end) - range.start];
+
+    eprintln!("{header}");
+    eprintln!("{gutter}--> {path}:{}:{}", position.line, position.column);
+    eprintln!("{gutter} |");
+    eprintln!("{} | {line}", position.line);
+    eprintln!(
+        "{gutter} | {}{}",
+        " ".repeat(position.column - 1),
+        "^".repeat(text.chars().count().max(1))
+    );
+}
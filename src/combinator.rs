@@ -0,0 +1,148 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::ops::{Bound, RangeBounds};
+
+use crate::{Extract, OwnedRule, Rule};
+
+/// Method-style combinators building an [`OwnedRule`], so that nested rules
+/// read left to right rather than inside out, such as
+/// `Rule::literal("function").or(Rule::literal("func")).map(&|_| Token::Function)`.
+///
+/// Implemented for [`Rule`], [`OwnedRule`] and references to rules, which
+/// are embedded as they are.
+pub trait RuleExt<'a, T: 'a>: Into<OwnedRule<'a, T>> {
+    /// Matches like this rule or `other`, the first one listed deciding the
+    /// token, like [`Rule::Any`]. Chained alternatives are collected into a
+    /// single `Any`.
+    fn or(self, other: impl Into<OwnedRule<'a, T>>) -> OwnedRule<'a, T> {
+        let mut rules = Vec::new();
+
+        for rule in [self.into(), other.into()] {
+            match rule {
+                OwnedRule::Any(alternatives) => rules.extend(alternatives),
+                rule => rules.push(rule),
+            }
+        }
+
+        OwnedRule::Any(rules)
+    }
+
+    /// Matches this rule followed by `other`, like [`Rule::Seq`]. Chained
+    /// parts are collected into a single `Seq`.
+    fn then(self, other: impl Into<OwnedRule<'a, T>>) -> OwnedRule<'a, T> {
+        let mut rules = Vec::new();
+
+        for rule in [self.into(), other.into()] {
+            match rule {
+                OwnedRule::Seq(parts) => rules.extend(parts),
+                rule => rules.push(rule),
+            }
+        }
+
+        OwnedRule::Seq(rules)
+    }
+
+    /// Makes the token of a match through `out`, like [`Rule::Value`].
+    fn map(self, out: &'a dyn Extract<'a, T>) -> OwnedRule<'a, T> {
+        OwnedRule::Value(Box::new(self.into()), out)
+    }
+
+    /// Skips matches, like [`Rule::Ignore`].
+    fn ignored(self) -> OwnedRule<'a, T> {
+        OwnedRule::Ignore(Box::new(self.into()))
+    }
+
+    /// Matches the rule repeated a number of times within `counts`, like
+    /// [`Rule::Repeat`], such as `1..` for one or more.
+    fn repeated(self, counts: impl RangeBounds<usize>) -> OwnedRule<'a, T> {
+        let min = match counts.start_bound() {
+            Bound::Included(min) => *min,
+            Bound::Excluded(min) => min + 1,
+            Bound::Unbounded => 0,
+        };
+        let max = match counts.end_bound() {
+            Bound::Included(max) => Some(*max),
+            Bound::Excluded(max) => Some(max.saturating_sub(1)),
+            Bound::Unbounded => None,
+        };
+
+        OwnedRule::Repeat {
+            rule: Box::new(self.into()),
+            min,
+            max,
+        }
+    }
+}
+
+impl<'a, T> RuleExt<'a, T> for Rule<'a, T> {}
+
+impl<'a, T> RuleExt<'a, T> for &'a Rule<'a, T> {}
+
+impl<'a, T> RuleExt<'a, T> for OwnedRule<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lexer, MatchResult};
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        Hex(&'a str),
+        Word(&'a str),
+    }
+
+    #[test]
+    fn or_flattens() {
+        let rule = Rule::<()>::literal("a")
+            .or(Rule::literal("b"))
+            .or(Rule::literal("c").or(Rule::literal("d")));
+
+        assert_eq!(
+            rule,
+            OwnedRule::Any(Vec::from(
+                ["a", "b", "c", "d"].map(|literal| OwnedRule::Inline(Rule::Literal(literal)))
+            ))
+        );
+    }
+
+    #[test]
+    fn then_repeated() {
+        let digits = Rule::<()>::literal("0x").then(Rule::char_range('0'..='9').repeated(1..));
+
+        assert!(digits.matches("0x").is_partial_match());
+        assert!(digits.matches("0x12").is_match());
+        assert!(digits.matches("0xa").is_none());
+
+        let pair = Rule::<()>::literal("ab").repeated(..=2);
+
+        assert!(pair.matches("abab").is_match());
+        assert!(pair.matches("ababa").is_none());
+        assert_eq!(
+            Rule::<()>::Numeric.repeated(2..4),
+            OwnedRule::Repeat {
+                rule: Box::new(OwnedRule::Inline(Rule::Numeric)),
+                min: 2,
+                max: Some(3),
+            }
+        );
+    }
+
+    #[test]
+    fn lexes() {
+        let rule = Rule::Whitespace
+            .ignored()
+            .or(Rule::literal("0x")
+                .then(Rule::char_range('0'..='9').repeated(1..))
+                .map(&Token::Hex))
+            .or(Rule::Alphabetic.map(&Token::Word));
+
+        let tokens = Lexer::with_buffer(rule.as_rule(), "ff 0x10 x\n")
+            .flatten()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [Token::Word("ff"), Token::Hex("0x10"), Token::Word("x")]
+        );
+        assert!(matches!(rule.matches(" "), MatchResult::Match(None)));
+    }
+}
@@ -0,0 +1,434 @@
+use crate::{MatchResult, Rule};
+
+impl<'a, T> Rule<'a, T> {
+    /// Matches like [`Rule::matches`], but in const contexts. As callbacks
+    /// can't be called there, a full match reports whether it would produce
+    /// a token, as `Match(Some(()))`, instead of the token itself.
+    ///
+    /// Only literals, keywords, character classes other than [`Rule::Chars`],
+    /// `Value`, `All`, `Ignore`, `Not`, `Only`, `Both`, `Either`, `Any`,
+    /// `Priority` and the mode actions are supported. Returns `None` when
+    /// evaluation reaches any other rule, or [`Rule::Numeric`] or
+    /// [`Rule::Alphabetic`] sees a non-ASCII character, as the Unicode tables
+    /// behind them aren't available in const contexts.
+    pub const fn const_matches(&self, value: &str) -> Option<MatchResult<()>> {
+        match self.const_eval(value.as_bytes()) {
+            Some(MatchResult::Match(_)) if value.is_empty() => Some(MatchResult::PartialMatch),
+            result => result,
+        }
+    }
+
+    const fn const_eval(&self, value: &[u8]) -> Option<MatchResult<()>> {
+        let result = match self {
+            Self::Literal(literal) => literal_result(literal.as_bytes(), value),
+            Self::Literals(literals) => {
+                let mut partial = false;
+                let mut index = 0;
+
+                while index < literals.len() {
+                    match literal_result(literals[index].as_bytes(), value) {
+                        MatchResult::Match(_) => return Some(MatchResult::Match(None)),
+                        MatchResult::PartialMatch => partial = true,
+                        _ => {}
+                    }
+
+                    index += 1;
+                }
+
+                partial_or_none(partial)
+            }
+            Self::Keywords(keywords) => {
+                let mut partial = false;
+                let mut index = 0;
+
+                while index < keywords.len() {
+                    match literal_result(keywords[index].0.as_bytes(), value) {
+                        MatchResult::Match(_) => return Some(MatchResult::Match(Some(()))),
+                        MatchResult::PartialMatch => partial = true,
+                        _ => {}
+                    }
+
+                    index += 1;
+                }
+
+                partial_or_none(partial)
+            }
+            Self::Numeric => return class(value, Class::Numeric),
+            Self::Alphabetic => return class(value, Class::Alphabetic),
+            Self::Whitespace => return class(value, Class::Whitespace),
+            Self::WhitespaceOf(whitespace) => {
+                return class(value, Class::Of(whitespace.as_bytes()))
+            }
+            Self::CharRange(range) => {
+                return class(value, Class::Range(*range.start(), *range.end()))
+            }
+            Self::EndsWith(literal) => match find(value, literal.as_bytes()) {
+                _ if literal.is_empty() => MatchResult::Match(None),
+                Some(index) if index + literal.len() == value.len() => MatchResult::Match(None),
+                Some(_) => MatchResult::None,
+                None => MatchResult::PartialMatch,
+            },
+            Self::StartsWith(literal) => match starts_with(value, literal.as_bytes()) {
+                true => MatchResult::Match(None),
+                false => literal_result(literal.as_bytes(), value),
+            },
+            Self::Value(rule, _) => match rule.const_eval(value) {
+                Some(MatchResult::Match(_)) => MatchResult::Match(Some(())),
+                result => return result,
+            },
+            Self::All(rules, _) => {
+                let mut index = 0;
+
+                while index < rules.len() {
+                    match rules[index].const_eval(value) {
+                        Some(MatchResult::Match(_)) => {}
+                        result => return result,
+                    }
+
+                    index += 1;
+                }
+
+                MatchResult::Match(Some(()))
+            }
+            Self::Ignore(rule) => match rule.const_eval(value) {
+                Some(MatchResult::Match(_)) => MatchResult::Match(None),
+                result => return result,
+            },
+            Self::Not(rule) => match rule.const_eval(value) {
+                Some(MatchResult::None | MatchResult::PartialMatch) => MatchResult::Match(None),
+                Some(MatchResult::Match(_)) => MatchResult::PartialMatch,
+                Some(MatchResult::Cut) => MatchResult::Cut,
+                None => return None,
+            },
+            Self::Only(rule)
+            | Self::Priority(_, rule)
+            | Self::PushMode(rule, _)
+            | Self::PopMode(rule) => return rule.const_eval(value),
+            Self::Both(a, b) => {
+                let partial = match a.const_eval(value) {
+                    Some(MatchResult::Match(_)) => false,
+                    Some(MatchResult::PartialMatch) => true,
+                    result => return result,
+                };
+
+                match b.const_eval(value) {
+                    Some(MatchResult::Match(_)) if !partial => MatchResult::Match(None),
+                    Some(MatchResult::Match(_) | MatchResult::PartialMatch) => {
+                        MatchResult::PartialMatch
+                    }
+                    result => return result,
+                }
+            }
+            Self::Either(a, b) => {
+                let (partial, result) = match a.const_eval(value) {
+                    Some(MatchResult::None) => (false, b.const_eval(value)),
+                    Some(MatchResult::PartialMatch) => (true, b.const_eval(value)),
+                    result => (false, result),
+                };
+
+                match result {
+                    Some(MatchResult::Match(_)) => MatchResult::Match(None),
+                    Some(MatchResult::Cut) => MatchResult::Cut,
+                    Some(MatchResult::PartialMatch) => MatchResult::PartialMatch,
+                    Some(MatchResult::None) => partial_or_none(partial),
+                    None => return None,
+                }
+            }
+            Self::Any(rules) => {
+                let mut best = None;
+                let mut partial = false;
+                let mut index = 0;
+
+                while index < rules.len() {
+                    let Some(priority) = rules[index].const_priority() else {
+                        return None;
+                    };
+
+                    let ranked = match best {
+                        Some((best, _)) => priority > best,
+                        None => true,
+                    };
+
+                    if ranked {
+                        match rules[index].const_eval(value) {
+                            Some(MatchResult::None) => {}
+                            Some(MatchResult::PartialMatch) => partial = true,
+                            Some(MatchResult::Match(token)) => {
+                                best = Some((priority, MatchResult::Match(token)))
+                            }
+                            result => return result,
+                        }
+                    }
+
+                    index += 1;
+                }
+
+                match best {
+                    Some((_, result)) => result,
+                    None => partial_or_none(partial),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(result)
+    }
+
+    /// Returns the priority of the rule like [`Rule::priority`], or `None`
+    /// if it depends on an [`OwnedRule`](crate::OwnedRule).
+    const fn const_priority(&self) -> Option<u8> {
+        match self {
+            Self::Priority(priority, _) => Some(*priority),
+            Self::Value(rule, _)
+            | Self::ValueFilter(rule, _)
+            | Self::ValueMany(rule, _)
+            | Self::Ignore(rule)
+            | Self::Only(rule) => rule.const_priority(),
+            #[cfg(feature = "alloc")]
+            Self::ValueCow(rule, _) => rule.const_priority(),
+            #[cfg(feature = "alloc")]
+            Self::Owned(_) => None,
+            _ => Some(0),
+        }
+    }
+}
+
+/// Counts the tokens `rule` lexes `input` into, in const contexts, following
+/// the lexer's longest match with [`Rule::const_matches`]. Returns `None` if
+/// some text doesn't match, or the rule can't be evaluated in const
+/// contexts.
+pub const fn lex_count<T>(rule: &Rule<'_, T>, input: &str) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let mut count = 0;
+    let mut start = 0;
+
+    while start < bytes.len() {
+        let mut end = start;
+        let mut matched = None;
+
+        while end < bytes.len() {
+            end += decode(bytes, end).1;
+
+            let (candidate, _) = input.split_at(end);
+            let (_, candidate) = candidate.split_at(start);
+
+            match rule.const_matches(candidate) {
+                Some(MatchResult::Match(token)) => matched = Some((end, token.is_some())),
+                Some(MatchResult::PartialMatch) => {}
+                Some(_) => break,
+                None => return None,
+            }
+        }
+
+        match matched {
+            Some((end, token)) => {
+                count += token as usize;
+                start = end;
+            }
+            None => return None,
+        }
+    }
+
+    Some(count)
+}
+
+enum Class<'a> {
+    Numeric,
+    Alphabetic,
+    Whitespace,
+    Of(&'a [u8]),
+    Range(char, char),
+}
+
+/// Matches if every character of `value` is in `class`.
+const fn class(value: &[u8], class: Class<'_>) -> Option<MatchResult<()>> {
+    let mut index = 0;
+
+    while index < value.len() {
+        let (c, len) = decode(value, index);
+
+        let member = match class {
+            Class::Numeric | Class::Alphabetic if !c.is_ascii() => return None,
+            Class::Numeric => c.is_ascii_digit(),
+            Class::Alphabetic => c.is_ascii_alphabetic(),
+            Class::Whitespace => c.is_whitespace(),
+            Class::Of(set) => contains(set, c),
+            Class::Range(start, end) => start <= c && c <= end,
+        };
+
+        if !member {
+            return Some(MatchResult::None);
+        }
+
+        index += len;
+    }
+
+    Some(MatchResult::Match(None))
+}
+
+const fn literal_result(literal: &[u8], value: &[u8]) -> MatchResult<()> {
+    if value.len() <= literal.len() && starts_with(literal, value) {
+        match value.len() == literal.len() {
+            true => MatchResult::Match(None),
+            false => MatchResult::PartialMatch,
+        }
+    } else {
+        MatchResult::None
+    }
+}
+
+const fn partial_or_none(partial: bool) -> MatchResult<()> {
+    match partial {
+        true => MatchResult::PartialMatch,
+        false => MatchResult::None,
+    }
+}
+
+const fn starts_with(bytes: &[u8], prefix: &[u8]) -> bool {
+    if prefix.len() > bytes.len() {
+        return false;
+    }
+
+    let mut index = 0;
+
+    while index < prefix.len() {
+        if bytes[index] != prefix[index] {
+            return false;
+        }
+
+        index += 1;
+    }
+
+    true
+}
+
+/// Returns the offset of the first occurrence of `needle` in `bytes`.
+const fn find(bytes: &[u8], needle: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+
+    while offset + needle.len() <= bytes.len() {
+        let (_, rest) = bytes.split_at(offset);
+
+        if starts_with(rest, needle) {
+            return Some(offset);
+        }
+
+        offset += 1;
+    }
+
+    None
+}
+
+const fn contains(set: &[u8], c: char) -> bool {
+    let mut index = 0;
+
+    while index < set.len() {
+        let (member, len) = decode(set, index);
+
+        if member == c {
+            return true;
+        }
+
+        index += len;
+    }
+
+    false
+}
+
+/// Decodes the character starting at `index` of valid UTF-8, returning it
+/// along with its length.
+const fn decode(bytes: &[u8], index: usize) -> (char, usize) {
+    let first = bytes[index] as u32;
+
+    let (len, mut code) = match first {
+        0x00..=0x7f => (1, first),
+        0xc0..=0xdf => (2, first & 0x1f),
+        0xe0..=0xef => (3, first & 0x0f),
+        _ => (4, first & 0x07),
+    };
+
+    let mut offset = 1;
+
+    while offset < len {
+        code = (code << 6) | (bytes[index + offset] as u32 & 0x3f);
+        offset += 1;
+    }
+
+    match char::from_u32(code) {
+        Some(c) => (c, len),
+        None => panic!("invalid UTF-8"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lexer;
+
+    #[derive(Debug)]
+    enum Token {
+        Keyword,
+        Identifier,
+        Number,
+        Operator,
+    }
+
+    const RULE: Rule<'static, Token> = Rule::Any(&[
+        Rule::Ignore(&Rule::WhitespaceOf(" \t\n")),
+        Rule::Value(&Rule::Literals(&["let", "in"]), &|_| Token::Keyword),
+        Rule::Value(&Rule::Alphabetic, &|_| Token::Identifier),
+        Rule::Value(&Rule::Numeric, &|_| Token::Number),
+        Rule::Value(
+            &Rule::Either(&Rule::Literal("="), &Rule::Literal("+")),
+            &|_| Token::Operator,
+        ),
+        Rule::Ignore(&Rule::Both(
+            &Rule::Not(&Rule::Numeric),
+            &Rule::EndsWith("#"),
+        )),
+    ]);
+
+    const COUNT: Option<usize> = lex_count(&RULE, "let x = 10 + y in x\n");
+
+    fn agrees(rule: &Rule<'static, Token>, value: &'static str) -> bool {
+        let Some(result) = rule.const_matches(value) else {
+            return false;
+        };
+
+        match (rule.matches(value), result) {
+            (MatchResult::Match(a), MatchResult::Match(b)) => a.is_some() == b.is_some(),
+            (MatchResult::None, MatchResult::None)
+            | (MatchResult::PartialMatch, MatchResult::PartialMatch)
+            | (MatchResult::Cut, MatchResult::Cut) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn agrees_with_runtime() {
+        let samples = [
+            "", "l", "le", "let", "lets", "i", "in", "x", "10", "1a", "=", "+", "==", " ", " \t",
+            "\n", "#", "a#", "1#",
+        ];
+
+        for value in samples {
+            assert!(agrees(&RULE, value), "{value:?}");
+        }
+
+        let hex = Rule::char_range('a'..='f');
+        assert!(agrees(&hex, "cafe") && agrees(&hex, "cafe!"));
+
+        assert!(RULE.const_matches("١").is_none());
+        assert!(Rule::<Token>::Seq(&[]).const_matches("a").is_none());
+    }
+
+    #[test]
+    fn count() {
+        let input = "let x = 10 + y in x\n";
+        let lexed = Lexer::with_buffer(RULE, input).flatten().count();
+
+        assert_eq!(COUNT, Some(8));
+        assert_eq!(lex_count(&RULE, input), Some(lexed));
+        assert_eq!(lex_count(&RULE, "let x = ?\n"), None);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{Error, Lexer, Result};
+
+/// A vector of at most `N` values stored inline, for collecting tokens
+/// without an allocator.
+#[derive(Debug, Clone)]
+pub struct ArrayVec<T, const N: usize> {
+    items: [Option<T>; N],
+    len: usize,
+}
+
+impl<T, const N: usize> ArrayVec<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            items: [const { None }; N],
+            len: 0,
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Appends `value`, handing it back if the vector is full.
+    pub fn push(&mut self, value: T) -> core::result::Result<(), T> {
+        match self.items.get_mut(self.len) {
+            Some(slot) => {
+                *slot = Some(value);
+                self.len += 1;
+                Ok(())
+            }
+            None => Err(value),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.items.get(index)?.as_ref()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.items[..self.len].iter().flatten()
+    }
+
+    pub fn clear(&mut self) {
+        self.items = [const { None }; N];
+        self.len = 0;
+    }
+}
+
+impl<T, const N: usize> Default for ArrayVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> IntoIterator for ArrayVec<T, N> {
+    type Item = T;
+    type IntoIter = core::iter::Flatten<core::array::IntoIter<Option<T>, N>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter().flatten()
+    }
+}
+
+impl<'a, T> Lexer<'a, T> {
+    /// Appends the remaining tokens to `tokens`, returning how many were
+    /// added once the input is exhausted.
+    ///
+    /// If `tokens` fills up first, the token that didn't fit is pushed back
+    /// and [`Error::Overflow`] reports how far the lexer got, so the input
+    /// can be processed in batches by emptying `tokens` and calling again.
+    /// Any other error is returned as is, with the tokens lexed before it
+    /// left in `tokens`.
+    pub fn tokenize_fixed<const N: usize>(
+        &mut self,
+        tokens: &mut ArrayVec<T, N>,
+    ) -> Result<'a, usize> {
+        let mut produced = 0;
+
+        loop {
+            match self.step() {
+                Ok(Some(token)) => match tokens.push(token) {
+                    Ok(()) => produced += 1,
+                    Err(token) => {
+                        let span = self.span();
+                        let offset = span.start;
+                        self.push_back(token, span);
+
+                        return Err(Error::Overflow { produced, offset });
+                    }
+                },
+                Ok(None) => {}
+                Err(Error::Eof) => return Ok(produced),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rule;
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        Word(&'a str),
+    }
+
+    #[test]
+    fn batches() {
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Alphabetic, &Token::Word),
+            ]),
+            "a bc d ef g\n",
+        );
+        let mut tokens = ArrayVec::<_, 2>::new();
+
+        assert!(matches!(
+            lexer.tokenize_fixed(&mut tokens),
+            Err(Error::Overflow {
+                produced: 2,
+                offset: 5,
+            })
+        ));
+        assert!(tokens.iter().eq(&[Token::Word("a"), Token::Word("bc")]));
+
+        tokens.clear();
+
+        assert!(matches!(
+            lexer.tokenize_fixed(&mut tokens),
+            Err(Error::Overflow {
+                produced: 2,
+                offset: 10,
+            })
+        ));
+        assert!(tokens.iter().eq(&[Token::Word("d"), Token::Word("ef")]));
+
+        tokens.clear();
+
+        assert!(matches!(lexer.tokenize_fixed(&mut tokens), Ok(1)));
+        assert_eq!(tokens.get(0), Some(&Token::Word("g")));
+        assert_eq!(tokens.len(), 1);
+    }
+}
@@ -0,0 +1,191 @@
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{Error, Lexer, OwnedRule, Rule};
+
+/// A constrained description of a rule tree, for generating grammars from
+/// fuzzer input. Compiles into an [`OwnedRule`] whose tokens are the lengths
+/// of the matched slices.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleDesc {
+    Literal(String),
+    Literals(Vec<String>),
+    Numeric,
+    Alphabetic,
+    Whitespace,
+    Value(Box<RuleDesc>),
+    Ignore(Box<RuleDesc>),
+    Not(Box<RuleDesc>),
+    Only(Box<RuleDesc>),
+    Both(Box<RuleDesc>, Box<RuleDesc>),
+    Either(Box<RuleDesc>, Box<RuleDesc>),
+    All(Vec<RuleDesc>),
+    Any(Vec<RuleDesc>),
+}
+
+/// The result of [`fuzz_lex`].
+#[derive(Debug)]
+pub enum FuzzOutcome {
+    /// Lexing reached the end of the input, producing this many tokens.
+    Tokens(usize),
+    /// Lexing stopped with an error other than the end of the input, kept
+    /// as its message since the error borrows the compiled rule.
+    Error(String),
+    /// The rule was rejected by [`Rule::validate`].
+    Invalid,
+    /// Lexing took more steps than the input could possibly need.
+    Exhausted,
+}
+
+impl RuleDesc {
+    /// The maximum nesting of decoded descriptions.
+    pub const MAX_DEPTH: usize = 6;
+
+    /// Decodes a description from the start of `data`, advancing it past
+    /// the bytes used. Never fails: running out of bytes yields leaves.
+    pub fn decode(data: &mut &[u8]) -> Self {
+        Self::decode_at(data, 0)
+    }
+
+    fn decode_at(data: &mut &[u8], depth: usize) -> Self {
+        let boxed = |data: &mut &[u8]| Box::new(Self::decode_at(data, depth + 1));
+        let list = |data: &mut &[u8]| {
+            (0..byte(data) % 4)
+                .map(|_| Self::decode_at(data, depth + 1))
+                .collect()
+        };
+
+        let kinds = if depth >= Self::MAX_DEPTH { 5 } else { 13 };
+
+        match byte(data) % kinds {
+            0 => Self::Literal(text(data)),
+            1 => Self::Literals((0..byte(data) % 4).map(|_| text(data)).collect()),
+            2 => Self::Numeric,
+            3 => Self::Alphabetic,
+            4 => Self::Whitespace,
+            5 => Self::Value(boxed(data)),
+            6 => Self::Ignore(boxed(data)),
+            7 => Self::Not(boxed(data)),
+            8 => Self::Only(boxed(data)),
+            9 => Self::Both(boxed(data), boxed(data)),
+            10 => Self::Either(boxed(data), boxed(data)),
+            11 => Self::All(list(data)),
+            _ => Self::Any(list(data)),
+        }
+    }
+
+    /// Compiles the description into an owned rule.
+    pub fn compile(&self) -> OwnedRule<'_, usize> {
+        match self {
+            Self::Literal(literal) => OwnedRule::Literals(Vec::from([literal.as_str()])),
+            Self::Literals(literals) => {
+                OwnedRule::Literals(literals.iter().map(String::as_str).collect())
+            }
+            Self::Numeric => OwnedRule::Rule(&Rule::Numeric),
+            Self::Alphabetic => OwnedRule::Rule(&Rule::Alphabetic),
+            Self::Whitespace => OwnedRule::Rule(&Rule::Whitespace),
+            Self::Value(rule) => OwnedRule::Value(rule.boxed(), &str::len),
+            Self::Ignore(rule) => OwnedRule::Ignore(rule.boxed()),
+            Self::Not(rule) => OwnedRule::Not(rule.boxed()),
+            Self::Only(rule) => OwnedRule::Only(rule.boxed()),
+            Self::Both(a, b) => OwnedRule::Both(a.boxed(), b.boxed()),
+            Self::Either(a, b) => OwnedRule::Either(a.boxed(), b.boxed()),
+            Self::All(rules) => {
+                OwnedRule::All(rules.iter().map(Self::compile).collect(), &str::len)
+            }
+            Self::Any(rules) => OwnedRule::Any(rules.iter().map(Self::compile).collect()),
+        }
+    }
+
+    fn boxed(&self) -> Box<OwnedRule<'_, usize>> {
+        Box::new(self.compile())
+    }
+}
+
+fn byte(data: &mut &[u8]) -> u8 {
+    match data.split_first() {
+        Some((&byte, rest)) => {
+            *data = rest;
+            byte
+        }
+        None => 0,
+    }
+}
+
+/// Decodes a short string over a small alphabet, so that literals are
+/// likely to occur in the input.
+fn text(data: &mut &[u8]) -> String {
+    const ALPHABET: &[char] = &['a', 'b', '1', ' ', '\n', 'é', '👩'];
+
+    (0..byte(data) % 4)
+        .map(|_| ALPHABET[usize::from(byte(data)) % ALPHABET.len()])
+        .collect()
+}
+
+/// Lexes `input` with the rule described by `rule_desc` until the end of the
+/// input or the first error, giving up if it takes more steps than lexing
+/// could need.
+pub fn fuzz_lex(rule_desc: &RuleDesc, input: &str) -> FuzzOutcome {
+    let rule = rule_desc.compile();
+    let Ok(mut lexer) = Lexer::try_new(rule.as_rule()) else {
+        return FuzzOutcome::Invalid;
+    };
+    lexer.reset(input);
+    let mut tokens = 0;
+
+    // Every commit rescans at most the rest of the input.
+    let budget = (input.len() + 2) * (input.len() + 2);
+
+    for _ in 0..budget {
+        match lexer.step() {
+            Ok(Some(_)) => tokens += 1,
+            Ok(None) => {}
+            Err(Error::Eof) => return FuzzOutcome::Tokens(tokens),
+            Err(error) => return FuzzOutcome::Error(error.to_string()),
+        }
+    }
+
+    FuzzOutcome::Exhausted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_and_lex() {
+        let mut seed = 0x9e37_79b9_u32;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed
+        };
+
+        for _ in 0..500 {
+            let bytes = (0..64).map(|_| next() as u8).collect::<Vec<_>>();
+            let mut data = bytes.as_slice();
+
+            let desc = RuleDesc::decode(&mut data);
+            let input = String::from_utf8_lossy(data);
+
+            assert!(!matches!(fuzz_lex(&desc, &input), FuzzOutcome::Exhausted));
+        }
+    }
+
+    #[test]
+    fn non_ascii_input() {
+        let desc = RuleDesc::Any(Vec::from([
+            RuleDesc::Ignore(Box::new(RuleDesc::Whitespace)),
+            RuleDesc::Value(Box::new(RuleDesc::Alphabetic)),
+        ]));
+
+        assert!(matches!(
+            fuzz_lex(&desc, "πé ñ x\n"),
+            FuzzOutcome::Tokens(3)
+        ));
+    }
+}
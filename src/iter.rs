@@ -0,0 +1,321 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::{Lexer, Result, Spanned};
+
+/// Iterator pairing each token with the text skipped immediately before it.
+///
+/// Created by [`Lexer::with_gaps`].
+pub struct Gaps<'l, 'a, T> {
+    lexer: &'l mut Lexer<'a, T>,
+    start: usize,
+    done: bool,
+}
+
+impl<'l, 'a, T> Gaps<'l, 'a, T> {
+    pub(crate) fn new(lexer: &'l mut Lexer<'a, T>) -> Self {
+        let start = lexer.index.0;
+
+        Self {
+            lexer,
+            start,
+            done: false,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Gaps<'_, 'a, T> {
+    /// The skipped text (`None` if the token directly follows the previous
+    /// one) and the token. The token is `None` only for the final item, which
+    /// carries the text left over after the last token.
+    type Item = (Option<&'a str>, Option<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            let committed = self.lexer.index.0;
+
+            match self.lexer.step() {
+                Ok(Some(token)) => {
+                    let gap = &self.lexer.buffer[self.start..committed];
+                    self.start = self.lexer.index.0;
+
+                    return Some(((!gap.is_empty()).then_some(gap), Some(token)));
+                }
+                Ok(None) => {}
+                Err(_) => {
+                    self.done = true;
+
+                    let gap = &self.lexer.buffer[self.start..];
+
+                    return (!gap.is_empty()).then_some((Some(gap), None));
+                }
+            }
+        }
+    }
+}
+
+/// Iterator over the tokens of a lexer along with their spans, stopping at
+/// the first error.
+///
+/// Created by [`Lexer::spanned`].
+pub struct SpannedTokens<'l, 'a, T> {
+    lexer: &'l mut Lexer<'a, T>,
+}
+
+impl<'l, 'a, T> SpannedTokens<'l, 'a, T> {
+    pub(crate) fn new(lexer: &'l mut Lexer<'a, T>) -> Self {
+        Self { lexer }
+    }
+}
+
+impl<T> Iterator for SpannedTokens<'_, '_, T> {
+    type Item = Spanned<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(token) = self.lexer.step().ok()? {
+                return Some(Spanned::new(token, self.lexer.span()));
+            }
+        }
+    }
+}
+
+/// Iterator over the tokens of a lexer that a predicate accepts, optionally
+/// keeping the others aside.
+///
+/// Created by [`Lexer::filtered`].
+pub struct FilteredLexer<'a, T> {
+    lexer: Lexer<'a, T>,
+    significant: fn(&T) -> bool,
+    #[cfg(feature = "alloc")]
+    skipped: Option<Vec<Spanned<T>>>,
+}
+
+impl<'a, T> FilteredLexer<'a, T> {
+    pub(crate) fn new(lexer: Lexer<'a, T>, significant: fn(&T) -> bool) -> Self {
+        Self {
+            lexer,
+            significant,
+            #[cfg(feature = "alloc")]
+            skipped: None,
+        }
+    }
+
+    /// Keeps the dropped tokens along with their spans, in order, for
+    /// [`FilteredLexer::skipped`].
+    #[cfg(feature = "alloc")]
+    pub fn keep_skipped(mut self) -> Self {
+        self.skipped = Some(Vec::new());
+        self
+    }
+
+    /// Returns the tokens dropped so far, if kept with
+    /// [`FilteredLexer::keep_skipped`].
+    #[cfg(feature = "alloc")]
+    pub fn skipped(&self) -> &[Spanned<T>] {
+        self.skipped.as_deref().unwrap_or_default()
+    }
+
+    /// Removes and returns the tokens dropped so far.
+    #[cfg(feature = "alloc")]
+    pub fn take_skipped(&mut self) -> Vec<Spanned<T>> {
+        self.skipped
+            .as_mut()
+            .map(core::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Returns the lexer, whose [`Lexer::span`] is the span of the token
+    /// most recently returned.
+    pub fn lexer(&self) -> &Lexer<'a, T> {
+        &self.lexer
+    }
+
+    pub fn into_inner(self) -> Lexer<'a, T> {
+        self.lexer
+    }
+}
+
+impl<'a, T> Iterator for FilteredLexer<'a, T> {
+    type Item = Result<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.lexer.next()? {
+                Ok(token) if !(self.significant)(&token) => {
+                    #[cfg(feature = "alloc")]
+                    if let Some(skipped) = &mut self.skipped {
+                        skipped.push(Spanned::new(token, self.lexer.span()));
+                    }
+                }
+                item => return Some(item),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[derive(Debug)]
+    struct Word<'a>(&'a str);
+
+    #[test]
+    fn gaps_reconstruct_input() {
+        const INPUT: &str = "  foo -- note\n\t\tbar  \n";
+
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Alphabetic, &Word),
+                Rule::Ignore(&Rule::Seq(&[Rule::Literal("--"), Rule::EndsWith("\n")])),
+            ]),
+            INPUT,
+        );
+
+        let mut output = String::new();
+        let mut items = lexer.with_gaps().peekable();
+
+        assert!(matches!(
+            items.peek(),
+            Some((Some("  "), Some(Word("foo"))))
+        ));
+
+        for (gap, token) in items {
+            if let Some(gap) = gap {
+                output.push_str(gap);
+            }
+
+            if let Some(Word(word)) = token {
+                output.push_str(word);
+            }
+        }
+
+        assert_eq!(output, INPUT);
+    }
+
+    #[test]
+    fn trailing_gap() {
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Literal("ab"), &Word),
+            ]),
+            "abab   ",
+        );
+
+        let items = lexer.with_gaps().collect::<Vec<_>>();
+
+        assert!(matches!(
+            items.as_slice(),
+            [
+                (None, Some(Word("ab"))),
+                (None, Some(Word("ab"))),
+                (Some("   "), None),
+            ]
+        ));
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        Space,
+        Comment(&'a str),
+        Word(&'a str),
+    }
+
+    const TRIVIA: Rule<'static, Token<'static>> = Rule::Any(&[
+        Rule::Value(&Rule::Whitespace, &|_| Token::Space),
+        Rule::Value(
+            &Rule::Seq(&[Rule::Literal("--"), Rule::EndsWith("\n")]),
+            &Token::Comment,
+        ),
+        Rule::Value(&Rule::Alphabetic, &Token::Word),
+    ]);
+
+    fn significant(token: &Token) -> bool {
+        matches!(token, Token::Word(_))
+    }
+
+    #[test]
+    fn filtered() {
+        let mut tokens = Lexer::with_buffer(TRIVIA, "let -- note\n x\n").filtered(significant);
+        let mut words = Vec::new();
+
+        while let Some(token) = tokens.next() {
+            words.push(Spanned::new(token.unwrap(), tokens.lexer().span()));
+        }
+
+        assert!(words[0].eq_with_span(&Spanned::new(Token::Word("let"), 0..3)));
+        assert!(words[1].eq_with_span(&Spanned::new(Token::Word("x"), 13..14)));
+        assert_eq!(words.len(), 2);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn keep_skipped() {
+        let mut tokens = Lexer::with_buffer(TRIVIA, "let -- note\n x\n")
+            .filtered(significant)
+            .keep_skipped();
+
+        assert!(tokens
+            .by_ref()
+            .flatten()
+            .eq([Token::Word("let"), Token::Word("x")]));
+
+        let skipped = tokens.take_skipped();
+        let spans = skipped.iter().map(|token| token.span.clone());
+
+        assert_eq!(
+            skipped,
+            [
+                Spanned::new(Token::Space, 0..0),
+                Spanned::new(Token::Comment("-- note\n"), 0..0),
+                Spanned::new(Token::Space, 0..0),
+                Spanned::new(Token::Space, 0..0),
+            ]
+        );
+        assert!(spans.eq([3..4, 4..12, 12..13, 14..15]));
+        assert!(tokens.skipped().is_empty());
+    }
+
+    #[test]
+    fn filtered_errors() {
+        let tokens = Lexer::with_buffer(TRIVIA, "a @ b\n")
+            .filtered(significant)
+            .collect::<Vec<_>>();
+
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                Ok(Token::Word("a")),
+                Err(Error::UnknownToken {
+                    text: "@",
+                    at: 2,
+                    ..
+                })
+            ]
+        ));
+    }
+
+    #[test]
+    fn next_significant() {
+        let mut lexer = Lexer::with_buffer(TRIVIA, " -- x\n  y z\n");
+
+        assert!(matches!(
+            lexer.next_significant(significant),
+            Some(Ok(Token::Word("y")))
+        ));
+        assert_eq!(lexer.span(), 8..9);
+        assert!(matches!(lexer.next(), Some(Ok(Token::Space))));
+        assert!(matches!(
+            lexer.next_significant(significant),
+            Some(Ok(Token::Word("z")))
+        ));
+        assert!(lexer.next_significant(significant).is_none());
+    }
+}
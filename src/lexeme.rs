@@ -0,0 +1,161 @@
+use alloc::{borrow::Cow, string::String};
+use core::{fmt, str::Chars};
+
+/// Token text that borrows from the input when it can, and owns normalized
+/// text, such as decoded escapes, when it has to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lexeme<'a>(pub Cow<'a, str>);
+
+impl<'a> Lexeme<'a> {
+    /// Decodes the escape sequences in `value`, borrowing it unchanged if it
+    /// contains no `escape` character.
+    ///
+    /// `n`, `r`, `t`, `b`, `f` and `0` after `escape` decode to the matching
+    /// control character, and `u` followed by four hex digits to that UTF-16
+    /// code unit, combining surrogate pairs as in JSON; a lone surrogate
+    /// decodes to U+FFFD. Any other character decodes to itself.
+    pub fn unescape(value: &'a str, escape: char) -> Self {
+        if !value.contains(escape) {
+            return Self(Cow::Borrowed(value));
+        }
+
+        let mut decoded = String::with_capacity(value.len());
+        let mut chars = value.chars();
+
+        while let Some(c) = chars.next() {
+            if c != escape {
+                decoded.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('n') => decoded.push('\n'),
+                Some('r') => decoded.push('\r'),
+                Some('t') => decoded.push('\t'),
+                Some('0') => decoded.push('\0'),
+                Some('b') => decoded.push('\u{8}'),
+                Some('f') => decoded.push('\u{c}'),
+                Some('u') => match code_unit(&mut chars) {
+                    Some(high @ 0xd800..=0xdbff) => {
+                        let mut next = chars.clone();
+                        let low = (next.next() == Some(escape) && next.next() == Some('u'))
+                            .then(|| code_unit(&mut next))
+                            .flatten()
+                            .filter(|low| (0xdc00..=0xdfff).contains(low));
+
+                        match low {
+                            Some(low) => {
+                                chars = next;
+                                let c = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                                decoded.push(char::from_u32(c).unwrap_or('\u{fffd}'));
+                            }
+                            None => decoded.push('\u{fffd}'),
+                        }
+                    }
+                    Some(unit) => decoded.push(char::from_u32(unit).unwrap_or('\u{fffd}')),
+                    None => decoded.push('u'),
+                },
+                Some(c) => decoded.push(c),
+                None => decoded.push(escape),
+            }
+        }
+
+        Self(Cow::Owned(decoded))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns whether the text is still borrowed from the input.
+    pub const fn is_borrowed(&self) -> bool {
+        matches!(self.0, Cow::Borrowed(_))
+    }
+
+    pub fn into_inner(self) -> Cow<'a, str> {
+        self.0
+    }
+}
+
+/// Reads four hex digits, leaving `chars` untouched if they aren't there.
+fn code_unit(chars: &mut Chars<'_>) -> Option<u32> {
+    let mut next = chars.clone();
+    let mut unit = 0;
+
+    for _ in 0..4 {
+        unit = unit * 16 + next.next()?.to_digit(16)?;
+    }
+
+    *chars = next;
+    Some(unit)
+}
+
+impl<'a> From<&'a str> for Lexeme<'a> {
+    fn from(value: &'a str) -> Self {
+        Self(Cow::Borrowed(value))
+    }
+}
+
+impl From<String> for Lexeme<'_> {
+    fn from(value: String) -> Self {
+        Self(Cow::Owned(value))
+    }
+}
+
+impl fmt::Display for Lexeme<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{Lexer, Rule};
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        String(Lexeme<'a>),
+    }
+
+    fn strings(input: &str) -> Vec<Lexeme<'_>> {
+        Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::ValueCow(
+                    &Rule::Quoted {
+                        quote: '"',
+                        escape: '\\',
+                    },
+                    Token::String,
+                ),
+            ]),
+            input,
+        )
+        .flatten()
+        .map(|Token::String(lexeme)| lexeme)
+        .collect()
+    }
+
+    #[test]
+    fn unescape() {
+        assert!(Lexeme::unescape("plain", '\\').is_borrowed());
+        assert_eq!(Lexeme::unescape(r#"a\tb\\c\""#, '\\').as_str(), "a\tb\\c\"");
+        assert_eq!(Lexeme::unescape(r"trailing\", '\\').as_str(), r"trailing\");
+        assert_eq!(
+            Lexeme::unescape(r"\u00e9\ud83d\ude00\ud800!\u12", '\\').as_str(),
+            "é😀\u{fffd}!u12"
+        );
+    }
+
+    #[test]
+    fn borrowed_when_possible() {
+        let strings = strings(r#""plain" "esc\"aped\n" "#);
+
+        assert_eq!(strings, ["plain", "esc\"aped\n"].map(Lexeme::from));
+        assert!(strings[0].is_borrowed());
+        assert!(!strings[1].is_borrowed());
+    }
+}
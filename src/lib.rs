@@ -1,156 +1,3899 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![forbid(unsafe_code)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "tokio")]
+pub mod async_lexer;
+#[cfg(feature = "alloc")]
+pub mod combinator;
+pub mod const_eval;
+pub mod fixed;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod iter;
+#[cfg(feature = "alloc")]
+pub mod lexeme;
+#[cfg(feature = "alloc")]
+pub mod matcher;
+pub mod mode;
+pub mod name;
+#[cfg(feature = "unicode-normalization")]
+pub mod normalize;
+mod number;
+#[cfg(feature = "alloc")]
+pub mod optimize;
+#[cfg(feature = "alloc")]
+pub mod owned;
+#[cfg(feature = "alloc")]
+pub mod owned_error;
+pub mod peek;
+pub mod position;
+#[cfg(feature = "presets")]
+pub mod presets;
+pub mod recovery;
 pub mod rule;
+pub mod spanned;
+#[cfg(all(feature = "serde", feature = "alloc"))]
+pub mod spec;
+pub mod state;
+#[cfg(feature = "alloc")]
+pub mod stats;
+#[cfg(feature = "alloc")]
+pub mod stream;
+#[cfg(feature = "alloc")]
+pub mod template;
+#[cfg(feature = "alloc")]
+pub mod token_stream;
+#[cfg(feature = "alloc")]
+pub mod validate;
+#[cfg(feature = "alloc")]
+pub mod verify;
+#[cfg(feature = "alloc")]
+pub mod warning;
 
+#[cfg(feature = "tokio")]
+pub use async_lexer::*;
+#[cfg(feature = "alloc")]
+pub use combinator::*;
+pub use const_eval::*;
+pub use fixed::*;
+#[cfg(feature = "fuzz")]
+pub use fuzz::*;
+pub use iter::*;
+#[cfg(feature = "alloc")]
+pub use lexeme::*;
+#[cfg(feature = "alloc")]
+pub use matcher::*;
+#[cfg(feature = "derive")]
+pub use mile_derive::{Lexable, TokenName};
+pub use mode::*;
+pub use name::*;
+#[cfg(feature = "unicode-normalization")]
+pub use normalize::*;
+#[cfg(feature = "alloc")]
+pub use owned::*;
+#[cfg(feature = "alloc")]
+pub use owned_error::*;
+pub use peek::*;
+pub use position::*;
+pub use recovery::*;
 pub use rule::*;
+pub use spanned::*;
+#[cfg(all(feature = "serde", feature = "alloc"))]
+pub use spec::*;
+pub use state::*;
+#[cfg(feature = "alloc")]
+pub use stats::*;
+#[cfg(feature = "alloc")]
+pub use stream::{ChunkedLexer, Grammar};
+#[cfg(feature = "alloc")]
+pub use template::*;
+#[cfg(feature = "alloc")]
+pub use token_stream::*;
+#[cfg(feature = "alloc")]
+pub use validate::*;
+#[cfg(feature = "alloc")]
+pub use verify::*;
+#[cfg(feature = "alloc")]
+pub use warning::*;
+
+use core::{fmt, ops::Range};
 
-#[derive(Debug)]
+/// Errors raised by the [`Lexer`], borrowing offending text from its buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error<'a> {
-    None,
+    /// Raised once the lexer has consumed the whole buffer, which ends
+    /// iteration rather than reporting a problem.
     Eof,
-    UnknownToken(&'a str),
+    /// Raised when the rule rejects the candidate slice before any prefix of
+    /// it fully matched, so no token can start at the committed cursor. The
+    /// slice, `text`, starts at byte `at`, which `line` and `column` locate
+    /// like [`Lexer::position`]. The slice is discarded;
+    /// [`Lexer::skip_char`] skips past its first character to resume.
+    UnknownToken {
+        text: &'a str,
+        at: usize,
+        line: usize,
+        column: usize,
+    },
+    /// Raised in strict mode when more than one alternative of the top-level
+    /// `Rule::Any` with the highest priority fully matches the committed
+    /// slice starting at `at`. `rules` holds the indices of the first two of
+    /// them.
+    Ambiguous { at: usize, rules: [usize; 2] },
+    /// Raised when a `Rule::Commit` matched its prefix at `at` but the rest
+    /// of the slice failed to match. `rule` is the index of the top-level
+    /// `Rule::Any` alternative that committed, or 0 for any other rule, and
+    /// `tag` the tag of the `Rule::Commit`, unless it cut inside a
+    /// `Rule::Seq` or `Rule::Repeat`.
+    Committed {
+        at: usize,
+        rule: usize,
+        tag: Option<&'a str>,
+    },
+    /// Raised at the end of the input while a delimited rule, such as
+    /// `Rule::Quoted`, `Rule::BlockComment` or `Rule::Delimited`, is still
+    /// waiting for its closing delimiter. `open` is the span of the opening
+    /// delimiter and `eof` the offset where the input ended.
+    Unterminated { open: Range<usize>, eof: usize },
+    /// Raised by [`Lexer::expect_next`] when the next token isn't the
+    /// expected one. `found` is the text of the token, or `None` at the end
+    /// of the input, and `line` and `column` locate the start of `span`.
+    Unexpected {
+        expected: &'static str,
+        found: Option<&'a str>,
+        span: Range<usize>,
+        line: usize,
+        column: usize,
+    },
+    /// Raised when a `Rule::PushMode` matches the slice starting at `at`
+    /// while [`MODE_DEPTH_LIMIT`] modes are already pushed. The slice is not
+    /// committed.
+    ModeOverflow { at: usize },
+    /// Raised when a [`Rule::ValueMany`] splits the slice starting at `at`
+    /// into more than [`PUSH_BACK_LIMIT`] tokens. The slice is not
+    /// committed.
+    TooManyTokens { at: usize },
+    /// Raised by [`Lexer::tokenize_fixed`] when its vector fills up before
+    /// the end of the input. `produced` tokens were added, and the lexer
+    /// resumes with the token at `offset`.
+    Overflow { produced: usize, offset: usize },
+}
+
+impl<'a> Error<'a> {
+    /// The number of characters of offending text shown by `Display`.
+    pub const TEXT_LIMIT: usize = 64;
+
+    /// Returns a `Display` implementation that shows at most `limit`
+    /// characters of offending text, followed by an ellipsis and the full
+    /// length in bytes if the text is longer.
+    pub const fn display(&self, limit: usize) -> ErrorDisplay<'_, 'a> {
+        ErrorDisplay { error: self, limit }
+    }
+}
+
+impl fmt::Display for Error<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display(Self::TEXT_LIMIT).fmt(f)
+    }
+}
+
+impl core::error::Error for Error<'_> {}
+
+/// Displays an [`Error`] with its offending text truncated, as returned by
+/// [`Error::display`].
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorDisplay<'e, 'a> {
+    error: &'e Error<'a>,
+    limit: usize,
+}
+
+impl fmt::Display for ErrorDisplay<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.error {
+            Error::Eof => f.write_str("end of input"),
+            Error::UnknownToken {
+                text, line, column, ..
+            } => {
+                f.write_str("unknown token ")?;
+                self.text(f, text)?;
+                write!(f, " at {line}:{column}")
+            }
+            Error::Ambiguous { at, rules: [a, b] } => write!(
+                f,
+                "ambiguous token at byte {at}: alternatives {a} and {b} both match"
+            ),
+            Error::Committed {
+                at,
+                rule,
+                tag: Some(tag),
+            } => write!(
+                f,
+                "invalid {tag} at byte {at}: alternative {rule} failed after committing"
+            ),
+            Error::Committed { at, rule, .. } => {
+                write!(f, "alternative {rule} failed after committing at byte {at}")
+            }
+            Error::Unterminated { open, eof } => write!(
+                f,
+                "unterminated delimiter at bytes {}..{}, input ends at byte {eof}",
+                open.start, open.end
+            ),
+            Error::Unexpected {
+                expected,
+                found,
+                line,
+                column,
+                ..
+            } => {
+                write!(f, "expected {expected}, found ")?;
+
+                match found {
+                    Some(text) => self.text(f, text)?,
+                    None => f.write_str("end of input")?,
+                }
+
+                write!(f, " at {line}:{column}")
+            }
+            Error::ModeOverflow { at } => {
+                write!(f, "more than {MODE_DEPTH_LIMIT} nested modes at byte {at}")
+            }
+            Error::TooManyTokens { at } => write!(
+                f,
+                "token at byte {at} split into more than {PUSH_BACK_LIMIT} tokens"
+            ),
+            Error::Overflow { produced, offset } => write!(
+                f,
+                "token buffer full after {produced} tokens, next token at byte {offset}"
+            ),
+        }
+    }
+}
+
+impl ErrorDisplay<'_, '_> {
+    /// Returns the full length in bytes of the offending text if it is
+    /// longer than the limit, so that callers can report it without
+    /// rendering the error.
+    pub fn truncated(&self) -> Option<usize> {
+        let text = match self.error {
+            Error::UnknownToken { text, .. } => text,
+            Error::Unexpected {
+                found: Some(text), ..
+            } => text,
+            _ => return None,
+        };
+
+        text.chars().nth(self.limit).map(|_| text.len())
+    }
+
+    /// Writes offending text in backticks, truncated to the limit.
+    fn text(&self, f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+        match text.char_indices().nth(self.limit) {
+            Some((end, _)) => write!(f, "`{}…` ({} bytes)", &text[..end], text.len()),
+            None => write!(f, "`{text}`"),
+        }
+    }
 }
 
 pub type Result<'a, T> = core::result::Result<T, Error<'a>>;
 
-pub struct Lexer<'a, T: core::fmt::Debug> {
+/// Decides whether a token ends a statement, and makes the terminator token.
+type Terminator<T> = (fn(&T) -> bool, fn() -> T);
+
+/// Post-processes an emitted token and its byte range. The hook must be
+/// `Send + Sync` so that the lexer can be moved to and shared with other
+/// threads.
+type TokenMap<'a, T> = &'a mut (dyn FnMut(T, Range<usize>) -> Option<T> + Send + Sync);
+
+/// Receives skipped text and its byte range, for [`Lexer::with_trivia`].
+type TriviaHandler<'a> = &'a mut (dyn FnMut(&str, Range<usize>) + Send + Sync);
+
+/// Receives each candidate slice and its byte range, for
+/// [`Lexer::with_trace`].
+type Trace = fn(&str, Range<usize>);
+
+/// The maximum number of tokens that can be pushed back at once.
+pub const PUSH_BACK_LIMIT: usize = 4;
+
+/// Parses a position directive into the line number of the line that follows
+/// it and, optionally, the name of the source it refers to.
+type DirectiveParser<'a> = fn(&'a str) -> Option<(u32, Option<&'a str>)>;
+
+/// A token pushed back with [`Lexer::push_back`], along with its span.
+pub(crate) type PushedBack<T> = Option<Spanned<T>>;
+
+pub struct Lexer<'a, T> {
     data: &'a str,
     buffer: &'a str,
     rule: Rule<'a, T>,
     index: (usize, usize),
+    matched: Option<usize>,
+    strict: bool,
+    terminator: Option<Terminator<T>>,
+    terminate: bool,
+    gate: Option<fn(&T) -> bool>,
+    gate_open: bool,
+    directive: Option<(DirectiveParser<'a>, &'a Rule<'a, T>)>,
+    line_base: Option<LineBase<'a>>,
+    lines: (usize, usize),
+    /// The characters of the first line of the buffer that came before it,
+    /// dropped by a chunked lexer.
+    columns: usize,
+    map: Option<TokenMap<'a, T>>,
+    trivia: Option<TriviaHandler<'a>>,
+    trace: Option<Trace>,
+    modes: &'a [Mode<'a, T>],
+    mode: ModeStack,
+    pushed: [PushedBack<T>; PUSH_BACK_LIMIT],
+    peeked: [Option<Checkpoint<'a>>; PUSH_BACK_LIMIT],
+    span: Range<usize>,
+    exhausted: bool,
+    /// Whether the rules were checked on the first step of a debug build.
+    #[cfg(all(debug_assertions, feature = "alloc"))]
+    validated: bool,
+    recovery: RecoveryStrategy<'a, T>,
+    #[cfg(feature = "alloc")]
+    checks: &'a [WarningCheck],
+    #[cfg(feature = "alloc")]
+    warnings: alloc::vec::Vec<LexWarning>,
+    /// The matcher of the slice being grown, and the mode it matches for.
+    #[cfg(feature = "alloc")]
+    matcher: Option<(Option<usize>, Matcher<'a, T>)>,
+    #[cfg(feature = "unicode-segmentation")]
+    graphemes: bool,
 }
 
-impl<'a, T: core::fmt::Debug> Lexer<'a, T> {
+impl<'a, T> Lexer<'a, T> {
+    /// Creates a lexer without checking the rule, so that it can be used in
+    /// const contexts. In debug builds with the `alloc` feature, the rule and
+    /// the modes are checked with [`Lexer::debug_validate`] on the first step
+    /// instead, once per lexer, panicking on any problem; use
+    /// [`Lexer::try_new`] to handle them.
     pub const fn new(rule: Rule<'a, T>) -> Self {
         Self {
             data: "",
             buffer: "",
             rule,
             index: (0, 0),
+            matched: None,
+            strict: false,
+            terminator: None,
+            terminate: false,
+            gate: None,
+            gate_open: true,
+            directive: None,
+            line_base: None,
+            lines: (0, 0),
+            columns: 0,
+            map: None,
+            trivia: None,
+            trace: None,
+            modes: &[],
+            mode: ModeStack::new(),
+            pushed: [const { None }; PUSH_BACK_LIMIT],
+            peeked: [None; PUSH_BACK_LIMIT],
+            span: 0..0,
+            exhausted: false,
+            #[cfg(all(debug_assertions, feature = "alloc"))]
+            validated: false,
+            recovery: RecoveryStrategy::Fail,
+            #[cfg(feature = "alloc")]
+            checks: &[],
+            #[cfg(feature = "alloc")]
+            warnings: alloc::vec::Vec::new(),
+            #[cfg(feature = "alloc")]
+            matcher: None,
+            #[cfg(feature = "unicode-segmentation")]
+            graphemes: false,
         }
     }
 
     pub const fn with_buffer(rule: Rule<'a, T>, buffer: &'a str) -> Self {
-        Self {
-            data: "",
-            buffer,
-            rule,
-            index: (0, 0),
-        }
+        let mut lexer = Self::new(rule);
+        lexer.buffer = buffer;
+        lexer
+    }
+
+    /// Makes the lexer grow the candidate slice by whole extended grapheme
+    /// clusters instead of single units, so a rule never sees half of a
+    /// cluster such as `👩‍👩‍👧` or a letter followed by a combining mark.
+    ///
+    /// Byte offsets and spans still refer to the underlying UTF-8 buffer.
+    #[cfg(feature = "unicode-segmentation")]
+    pub const fn graphemes(mut self, enabled: bool) -> Self {
+        self.graphemes = enabled;
+        self
+    }
+
+    /// Enables strict mode, under which committing a slice that more than one
+    /// alternative of the top-level `Rule::Any` fully matches raises
+    /// [`Error::Ambiguous`] instead of silently picking the first one.
+    /// Alternatives outranked by a [`Rule::Priority`] don't count.
+    ///
+    /// The slice is still committed, so lexing can continue after the error.
+    pub const fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    /// Inserts the token made by `make` at the next newline, or at EOF, after
+    /// a token satisfying `ends_statement`, like Go's automatic semicolons.
+    ///
+    /// The newline must be skipped rather than be part of a token. The
+    /// synthetic token is zero-width: it is produced before the newline is
+    /// committed.
+    pub const fn with_auto_terminator(
+        mut self,
+        ends_statement: fn(&T) -> bool,
+        make: fn() -> T,
+    ) -> Self {
+        self.terminator = Some((ends_statement, make));
+        self
+    }
+
+    /// Ties the gate that [`Rule::Gated`] checks to the previous token: the
+    /// gate is open at the start of the input and after every token
+    /// satisfying `opens`, and closed after any other token. Skipped text
+    /// leaves it as is.
+    ///
+    /// This lets one rule set tell apart text that means different things
+    /// depending on what precedes it, such as the minus of `a-5` and that of
+    /// `(-5)`.
+    pub const fn with_gate(mut self, opens: fn(&T) -> bool) -> Self {
+        self.gate = Some(opens);
+        self
+    }
+
+    /// Passes every emitted token, along with its byte range, through `map`
+    /// before it is returned. Returning `None` drops the token.
+    ///
+    /// Synthetic tokens, such as automatic terminators, are mapped too.
+    pub fn map_tokens(mut self, map: TokenMap<'a, T>) -> Self {
+        self.map = Some(map);
+        self
+    }
+
+    /// Passes all text committed without a token, such as whitespace and
+    /// comments under [`Rule::Ignore`], along with its byte range, to
+    /// `handler`, so that tooling like formatters can preserve it.
+    ///
+    /// Tokens dropped by [`Lexer::map_tokens`] aren't trivia.
+    pub fn with_trivia(mut self, handler: TriviaHandler<'a>) -> Self {
+        self.trivia = Some(handler);
+        self
+    }
+
+    /// Calls `trace` with every candidate slice, and its byte range, before
+    /// matching it, to debug rules. For example, `|data, _| println!("{data}")`
+    /// prints each slice the lexer tries.
+    pub const fn with_trace(mut self, trace: Trace) -> Self {
+        self.trace = Some(trace);
+        self
     }
 
     pub fn reset(&mut self, buffer: &'a str) {
         self.data = "";
         self.buffer = buffer;
         self.index = (0, 0);
+        self.matched = None;
+        self.terminate = false;
+        self.gate_open = true;
+        self.line_base = None;
+        self.lines = (0, 0);
+        self.columns = 0;
+        self.mode = ModeStack::new();
+        self.pushed = [const { None }; PUSH_BACK_LIMIT];
+        self.peeked = [None; PUSH_BACK_LIMIT];
+        self.span = 0..0;
+        self.exhausted = false;
+
+        #[cfg(feature = "alloc")]
+        {
+            self.warnings.clear();
+            self.matcher = None;
+        }
+    }
+
+    /// Pushes `token` back into the stream, so that the next step returns it
+    /// before lexing any further. Tokens pushed back are returned last in,
+    /// first out, with [`Lexer::span`] reporting `span` for them; they are
+    /// not passed through [`Lexer::map_tokens`] again. Pushing back resumes
+    /// an exhausted iterator.
+    ///
+    /// This is useful for splitting a token, such as `>>` into two `>` when
+    /// closing nested generics.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`PUSH_BACK_LIMIT`] tokens are already pushed back.
+    pub fn push_back(&mut self, token: T, span: Range<usize>) {
+        self.push(Spanned::new(token, span), None);
+    }
+
+    /// Pushes `token` back, along with the progress `before` it, which
+    /// [`Lexer::checkpoint`] returns while it is next.
+    fn push(&mut self, token: Spanned<T>, before: Option<Checkpoint<'a>>) {
+        let slot = self
+            .pushed
+            .iter()
+            .position(Option::is_none)
+            .expect("too many tokens pushed back");
+
+        self.pushed[slot] = Some(token);
+        self.peeked[slot] = before;
+        self.exhausted = false;
+    }
+
+    /// Returns the next token if it satisfies `accept`, skipping trivia. A
+    /// token that doesn't, or the end of the input, raises
+    /// [`Error::Unexpected`] describing what was `expected` instead, such as
+    /// "expected identifier, found `+` at 3:14"; the token is pushed back,
+    /// so it can still be inspected.
+    pub fn expect_next(
+        &mut self,
+        accept: impl FnOnce(&T) -> bool,
+        expected: &'static str,
+    ) -> Result<'a, Spanned<T>> {
+        let span = match self.next_token()? {
+            Some(token) if accept(&token) => return Ok(Spanned::new(token, self.span())),
+            Some(token) => {
+                self.push_back(token, self.span());
+                self.span()
+            }
+            None => self.buffer.len()..self.buffer.len(),
+        };
+        let position = Self::position(self, span.start);
+
+        Err(Error::Unexpected {
+            expected,
+            found: (span.start < self.buffer.len()).then(|| self.text(span.clone())),
+            span,
+            line: position.line,
+            column: position.column,
+        })
+    }
+
+    /// Returns the next token if it is named like `expected` by their
+    /// [`TokenName`], as with [`Lexer::expect_next`] expecting that name,
+    /// such as "expected identifier, found `+` at 3:14".
+    pub fn expect_token(&mut self, expected: &T) -> Result<'a, Spanned<T>>
+    where
+        T: TokenName,
+    {
+        let expected = expected.name();
+
+        self.expect_next(|token| token.name() == expected, expected)
+    }
+
+    /// Returns the span of the token most recently returned.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Returns the number of bytes committed so far and the length of the
+    /// buffer, for reporting progress.
+    pub const fn progress(&self) -> (usize, usize) {
+        (self.index.0, self.buffer.len())
+    }
+
+    /// Returns `true` once every byte of the buffer has been committed.
+    pub const fn is_finished(&self) -> bool {
+        self.index.0 >= self.buffer.len()
+    }
+
+    /// Returns the whole buffer.
+    pub const fn buffer(&self) -> &'a str {
+        self.buffer
+    }
+
+    /// Returns the number of bytes committed to tokens or skipped so far.
+    ///
+    /// The consumed, pending and remaining bytes always add up to the length
+    /// of the buffer.
+    pub const fn consumed_bytes(&self) -> usize {
+        self.index.0
+    }
+
+    /// Returns the number of bytes committed to tokens or skipped so far, the
+    /// same as [`Lexer::consumed_bytes`].
+    pub const fn bytes_consumed(&self) -> usize {
+        self.consumed_bytes()
+    }
+
+    /// Returns the length of the candidate slice in flight, which is neither
+    /// committed nor left for later steps.
+    pub const fn pending_bytes(&self) -> usize {
+        self.index.1 - self.index.0
+    }
+
+    /// Returns the number of bytes after the candidate slice.
+    pub const fn remaining_bytes(&self) -> usize {
+        self.buffer.len() - self.index.1
+    }
+
+    /// Returns the offset of the first byte not yet committed, where the
+    /// next token starts.
+    pub const fn offset(&self) -> usize {
+        self.index.0
+    }
+
+    /// Returns the text committed to tokens or skipped so far.
+    ///
+    /// The consumed, pending and remaining text always make up the buffer.
+    pub fn consumed(&self) -> &'a str {
+        &self.buffer[..self.index.0]
+    }
+
+    /// Returns the candidate slice in flight, such as the start of a token
+    /// the input ended in.
+    pub fn pending(&self) -> &'a str {
+        &self.buffer[self.index.0..self.index.1]
+    }
+
+    /// Returns the text after the candidate slice.
+    pub fn remainder(&self) -> &'a str {
+        &self.buffer[self.index.1..]
+    }
+
+    /// Returns the span of the buffer `error`, raised by this lexer, is
+    /// about: the offending text, or the offset where it was raised as an
+    /// empty span.
+    pub fn error_span(&self, error: &Error<'a>) -> Range<usize> {
+        match error {
+            Error::Eof => self.buffer.len()..self.buffer.len(),
+            Error::UnknownToken { text, at, .. } => *at..*at + text.len(),
+            Error::Ambiguous { at, .. }
+            | Error::Committed { at, .. }
+            | Error::ModeOverflow { at }
+            | Error::TooManyTokens { at } => *at..*at,
+            Error::Unterminated { open, .. } => open.clone(),
+            Error::Unexpected { span, .. } => span.clone(),
+            Error::Overflow { offset, .. } => *offset..*offset,
+        }
     }
 
-    pub fn step(&mut self) -> Result<Option<T>> {
-        self.index.1 += 1;
+    /// Returns the line containing the first uncommitted byte, see
+    /// [`Lexer::line_containing`].
+    pub fn current_line(&self) -> &'a str {
+        self.line_containing(self.index.0).0
+    }
+
+    /// Returns the line containing the byte at `offset`, without its line
+    /// terminator (`\n` or `\r\n`), along with its byte range in the buffer.
+    /// Offsets past the end of the buffer refer to the last line.
+    pub fn line_containing(&self, offset: usize) -> (&'a str, Range<usize>) {
+        let bytes = self.buffer.as_bytes();
+        let offset = offset.min(bytes.len());
+
+        let start = bytes[..offset]
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |index| index + 1);
+        let mut end = bytes[offset..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(bytes.len(), |index| offset + index);
+
+        if end > start && bytes[end - 1] == b'\r' && bytes.get(end) == Some(&b'\n') {
+            end -= 1;
+        }
+
+        (&self.buffer[start..end], start..end)
+    }
+
+    /// Returns the text of the buffer covered by `span`, such as the span of
+    /// a token reported by [`Lexer::span`]. Spans are offsets into the whole
+    /// buffer, wherever the lexer currently is.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `span` is out of bounds or doesn't fall on character
+    /// boundaries.
+    pub fn text(&self, span: Range<usize>) -> &'a str {
+        &self.buffer[span]
+    }
+
+    /// Returns the length of the buffer in bytes.
+    pub const fn buffer_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Grows the candidate slice by one unit and matches it. Once the rule
+    /// no longer matches or partially matches the slice, the longest slice
+    /// that fully matched is committed, and evaluated again on its own to
+    /// pick the token.
+    pub fn step(&mut self) -> Result<'a, Option<T>> {
+        let result = self.advance();
+        self.debug_assert_cursors();
+        result
+    }
+
+    /// Steps like [`Lexer::step`], pairing a token with its span.
+    pub fn step_spanned(&mut self) -> Result<'a, Option<Spanned<T>>> {
+        Ok(self.step()?.map(|token| Spanned::new(token, self.span())))
+    }
+
+    fn advance(&mut self) -> Result<'a, Option<T>> {
+        let mut pushed = self.pushed.iter_mut().zip(&mut self.peeked).rev();
+
+        if let Some(token) = pushed.find_map(|(token, peeked)| {
+            *peeked = None;
+            token.take()
+        }) {
+            self.span = token.span;
+            return Ok(Some(token.value));
+        }
+
+        #[cfg(all(debug_assertions, feature = "alloc"))]
+        if !self.validated {
+            self.validated = true;
+
+            if let Err(error) = self.debug_validate() {
+                panic!("{error}");
+            }
+        }
+
+        if self.index.1 == self.index.0 && self.position_directive() {
+            return Ok(None);
+        }
 
         if self.index.1 >= self.buffer.len() {
+            if let Some(matched) = self.matched.take() {
+                return self.commit(matched);
+            }
+
+            if let Some(terminator) = self.terminator() {
+                return Ok(self.map(terminator, self.index.0..self.index.0));
+            }
+
+            self.index.1 = self.buffer.len();
+
+            let rest = Context::new(self.buffer, self.index.0..self.buffer.len());
+
+            if let Some(len) = self.active_rule().unterminated(rest) {
+                return Err(Error::Unterminated {
+                    open: self.index.0..self.index.0 + len,
+                    eof: self.buffer.len(),
+                });
+            }
+
+            if self.index.0 < self.buffer.len() {
+                let text = &self.buffer[self.index.0..];
+                self.index.1 = self.index.0;
+
+                return self.recover(text);
+            }
+
             return Err(Error::Eof);
         }
 
+        let mut end = self.index.1 + self.step_len();
+
+        self.index.1 = end;
         self.data = &self.buffer[self.index.0..self.index.1];
 
-        println!("Data: `{}`", self.data);
+        if let Some(region) = self.active_rule().embed_end(self.context().probe()) {
+            end = region;
+            self.index.1 = end;
+            self.data = &self.buffer[self.index.0..self.index.1];
+        }
+
+        if let Some(trace) = self.trace {
+            trace(self.data, self.index.0..self.index.1);
+        }
+
+        match self.probe() {
+            MatchResult::Match(_) => {
+                self.matched = Some(end);
+                Ok(None)
+            }
+            MatchResult::PartialMatch => Ok(None),
+            MatchResult::None => match self.matched.take() {
+                Some(matched) => self.commit(matched),
+                None => {
+                    let text = self.data;
+                    self.index.1 = self.index.0;
+
+                    self.recover(text)
+                }
+            },
+            MatchResult::Cut => {
+                let (rule, tag) = self.committed();
 
-        match self.rule.matches(self.data) {
-            MatchResult::None | MatchResult::PartialMatch => Ok(None),
-            MatchResult::Match(token) => {
-                self.index.0 = self.index.1;
-                Ok(token)
+                self.index.1 = self.index.0;
+                self.matched = None;
+
+                Err(Error::Committed {
+                    at: self.index.0,
+                    rule,
+                    tag,
+                })
             }
         }
     }
-}
 
-impl<'a, T: core::fmt::Debug> Iterator for Lexer<'a, T> {
-    type Item = Option<T>;
+    /// Reports `text`, starting at the committed cursor, as an unknown token.
+    fn unknown_token(&self, text: &'a str) -> Error<'a> {
+        let position = self.position(self.index.0);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.step() {
-            Ok(token) => Some(token),
-            Err(_) => None,
+        Error::UnknownToken {
+            text,
+            at: self.index.0,
+            line: position.line,
+            column: position.column,
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Commits the slice ending at `end`, the longest one that fully matched,
+    /// evaluating the rule again against exactly that slice to pick the
+    /// token.
+    fn commit(&mut self, end: usize) -> Result<'a, Option<T>> {
+        self.index.1 = end;
+        self.data = &self.buffer[self.index.0..self.index.1];
 
-    const TEST_CODE: &str = r#"
-function add(a, b)
-    return a + b
-end
-"#;
+        let rule = self.active_rule();
+        let (token, split, action) = match rule.matches_in(self.context()) {
+            MatchResult::Match(token) => (
+                token,
+                rule.split(self.context().probe()),
+                rule.mode_action(self.context().probe()),
+            ),
+            _ => (None, None, None),
+        };
 
-    #[derive(Debug)]
-    enum Token<'a> {
-        And,
-        Break,
-        Do,
-        Else,
-        ElseIf,
-        End,
-        False,
-        For,
-        Function,
-        If,
-        In,
-        Local,
-        Nil,
-        Not,
-        Or,
-        Repeat,
-        Return,
-        Then,
-        True,
-        Until,
-        While,
-        Identifier(&'a str),
+        if token.is_none()
+            && self.terminate
+            && self.data.contains('\n')
+            && !self.active_rule().continues_line(self.context().probe())
+        {
+            self.index.1 = self.index.0;
+
+            return Ok(self
+                .terminator()
+                .and_then(|token| self.map(token, self.index.0..self.index.0)));
+        }
+
+        let at = self.index.0;
+
+        if matches!(action, Some(ModeAction::Push(_))) && self.mode.is_full() {
+            self.index.1 = self.index.0;
+
+            return Err(Error::ModeOverflow { at });
+        }
+
+        let pieces = match split {
+            Some(split) => match self.pieces(split, at) {
+                Some(pieces) => Some(pieces),
+                None => {
+                    self.index.1 = self.index.0;
+
+                    return Err(Error::TooManyTokens { at });
+                }
+            },
+            None => None,
+        };
+
+        let before = self.lexeme_state();
+        let ambiguity = self.strict.then(|| self.ambiguity()).flatten();
+        self.index.0 = self.index.1;
+        self.count_lines();
+
+        #[cfg(feature = "alloc")]
+        self.check(at..self.index.0);
+
+        if token.is_none() && pieces.is_none() {
+            if let Some(trivia) = &mut self.trivia {
+                trivia(self.data, at..self.index.0);
+            }
+        }
+
+        let token = match pieces {
+            Some(pieces) => self.split(pieces, before),
+            None => {
+                let token = token.and_then(|token| self.map(token, at..self.index.0));
+
+                if let Some(token) = &token {
+                    self.follow(token);
+                }
+
+                token
+            }
+        };
+
+        if let Some(action) = action {
+            self.switch_mode(action);
+        }
+
+        if let Some(rules) = ambiguity {
+            return Err(Error::Ambiguous { at, rules });
+        }
+
+        Ok(token)
     }
 
-    #[test]
-    fn it_works() {
-        let lexer = Lexer::<Token>::with_buffer(
-            Rule::Any(&[
-                Rule::Ignore(&Rule::Whitespace),
-                Rule::Value(&Rule::Literal("and"), |_| Token::And),
-                Rule::Value(&Rule::Literal("break"), |_| Token::Break),
-                Rule::Value(&Rule::Literal("do"), |_| Token::Do),
-                Rule::Value(&Rule::Literal("else"), |_| Token::Else),
-                Rule::Value(&Rule::Literal("elseif"), |_| Token::ElseIf),
-                Rule::Value(&Rule::Literal("end"), |_| Token::End),
-                Rule::Value(&Rule::Literal("false"), |_| Token::False),
-                Rule::Value(&Rule::Literal("for"), |_| Token::For),
-                Rule::Value(
-                    &Rule::Either(&Rule::Literal("function"), &Rule::Literal("func")),
-                    |_| Token::Function,
-                ),
-                Rule::Value(&Rule::Literal("if"), |_| Token::If),
-                Rule::Value(&Rule::Literal("in"), |_| Token::In),
-                Rule::Value(&Rule::Literal("local"), |_| Token::Local),
-                Rule::Value(&Rule::Literal("nil"), |_| Token::Nil),
-                Rule::Value(&Rule::Literal("not"), |_| Token::Not),
-                Rule::Value(&Rule::Literal("or"), |_| Token::Or),
-                Rule::Value(&Rule::Literal("repeat"), |_| Token::Repeat),
-                Rule::Value(&Rule::Literal("return"), |_| Token::Return),
-                Rule::Value(&Rule::Literal("then"), |_| Token::Then),
-                Rule::Value(&Rule::Literal("true"), |_| Token::True),
-                Rule::Value(&Rule::Literal("until"), |_| Token::Until),
-                Rule::Value(&Rule::Literal("while"), |_| Token::While),
-                Rule::Value(&Rule::Alphabetic, Token::Identifier),
-            ]),
-            TEST_CODE,
+    /// Splits the lexeme starting at `at` with the callback of a
+    /// [`Rule::ValueMany`] into its tokens, or `None` if there are more than
+    /// [`PUSH_BACK_LIMIT`] of them.
+    fn pieces(&self, split: Split<'a, T>, at: usize) -> Option<[PushedBack<T>; PUSH_BACK_LIMIT]> {
+        let lexeme = self.data;
+        let mut pieces = [const { None }; PUSH_BACK_LIMIT];
+        let mut count = 0;
+
+        split(lexeme, &mut |range, token| {
+            let end = range.end.min(lexeme.len());
+            let start = range.start.min(end);
+
+            if let Some(piece) = pieces.get_mut(count) {
+                *piece = Some(Spanned::new(token, at + start..at + end));
+            }
+
+            count += 1;
+        });
+
+        (count <= PUSH_BACK_LIMIT).then_some(pieces)
+    }
+
+    /// Returns the first of the tokens split from a lexeme and pushes back
+    /// the others, each with the progress `before` the lexeme and how many
+    /// pieces come before it, for [`Lexer::checkpoint`].
+    fn split(
+        &mut self,
+        mut pieces: [PushedBack<T>; PUSH_BACK_LIMIT],
+        before: Checkpoint<'a>,
+    ) -> Option<T> {
+        for piece in &mut pieces {
+            *piece = piece.take().and_then(|Spanned { value, span }| {
+                self.map(value, span.clone())
+                    .map(|value| Spanned::new(value, span))
+            });
+        }
+
+        if let Some(last) = pieces.iter().flatten().last() {
+            self.follow(last);
+        }
+
+        let count = pieces.iter().flatten().count();
+        let mut pieces = pieces.into_iter().flatten();
+        let first = pieces.next()?;
+
+        for (skip, piece) in (1..count).rev().zip(pieces.rev()) {
+            self.push(piece, Some(before.skipping(skip)));
+        }
+
+        self.span = first.span;
+        Some(first.value)
+    }
+
+    /// Updates the automatic terminator and the gate after `token`.
+    fn follow(&mut self, token: &T) {
+        if let Some((ends_statement, _)) = self.terminator {
+            self.terminate = ends_statement(token);
+        }
+
+        if let Some(opens) = self.gate {
+            self.gate_open = opens(token);
+        }
+    }
+
+    fn map(&mut self, token: T, range: Range<usize>) -> Option<T> {
+        self.span = range.clone();
+
+        match &mut self.map {
+            Some(map) => map(token, range),
+            None => Some(token),
+        }
+    }
+
+    /// Takes the pending automatic terminator, if any.
+    fn terminator(&mut self) -> Option<T> {
+        let (_, make) = self.terminator?;
+
+        core::mem::take(&mut self.terminate).then(make)
+    }
+
+    /// Checks that the cursors split the buffer into consumed, pending and
+    /// remaining bytes.
+    fn debug_assert_cursors(&self) {
+        debug_assert!(
+            self.index.0 <= self.index.1 && self.index.1 <= self.buffer.len(),
+            "cursors {:?} out of order for a buffer of {} bytes",
+            self.index,
+            self.buffer.len()
         );
+    }
+
+    fn context(&self) -> Context<'a> {
+        Context::new(self.buffer, self.index.0..self.index.1).with_gate(self.gate_open)
+    }
+
+    /// Matches the slice against the active rule like [`Rule::matches_in`]
+    /// does in a probe context. With the `alloc` feature, a [`Matcher`] kept
+    /// while the slice grows from the same start only tests the added text
+    /// against the literals and character classes of the rule.
+    fn probe(&mut self) -> MatchResult<T> {
+        let cx = self.context().probe();
+
+        #[cfg(feature = "alloc")]
+        {
+            let mode = self.mode.top();
+
+            if !matches!(&self.matcher, Some((top, _)) if *top == mode) {
+                let matcher = self.active_rule().matcher(self.buffer, self.index.0);
+                self.matcher = Some((mode, matcher));
+            }
+
+            if let Some((_, matcher)) = &mut self.matcher {
+                matcher.grow(self.buffer, self.index.0, self.index.1);
 
-        for token in lexer {
-            if let Some(token) = token {
-                println!("Token: {token:?}");
+                return matcher.result(cx);
             }
         }
+
+        self.active_rule().matches_in(cx)
+    }
+
+    fn ambiguity(&self) -> Option<[usize; 2]> {
+        let Rule::Any(rules) = self.active_rule() else {
+            return None;
+        };
+
+        let first = rule::decider(rules, self.context())?;
+        let priority = rules[first].priority();
+        let (second, _) = rules.iter().enumerate().skip(first + 1).find(|(_, rule)| {
+            rule.priority() == priority && rule.matches_in(self.context().probe()).is_match()
+        })?;
+
+        Some([first, second])
+    }
+
+    /// Returns the index of the top-level `Rule::Any` alternative that cut,
+    /// and the tag of the `Rule::Commit` that did.
+    fn committed(&self) -> (usize, Option<&'a str>) {
+        let cx = self.context().probe();
+        let rule = self.active_rule();
+        let index = match rule {
+            Rule::Any(rules) => rules
+                .iter()
+                .position(|rule| rule.matches_in(cx).is_cut())
+                .unwrap_or(0),
+            _ => 0,
+        };
+
+        (index, rule.cut(cx))
+    }
+
+    /// Returns the length of the next unit to add to the candidate slice: a
+    /// character, or an extended grapheme cluster in grapheme mode.
+    fn step_len(&self) -> usize {
+        let Some(rest) = self.buffer.get(self.index.1..) else {
+            return 1;
+        };
+
+        #[cfg(feature = "unicode-segmentation")]
+        if self.graphemes {
+            use unicode_segmentation::UnicodeSegmentation;
+
+            return rest.graphemes(true).next().map_or(1, str::len);
+        }
+
+        rest.chars().next().map_or(1, char::len_utf8)
+    }
+
+    /// Discards the character at the committed cursor, or the grapheme
+    /// cluster when stepping by graphemes, such as one that raised
+    /// [`Error::UnknownToken`], returning the skipped text.
+    pub fn skip_char(&mut self) -> &'a str {
+        self.index.1 = self.index.0;
+        self.bump(self.step_len())
+    }
+
+    /// Discards the next `len` bytes from the committed cursor, along with
+    /// any pending partial match, returning the skipped text. Skipping past
+    /// the end of the buffer stops at the end. Iteration resumes after an
+    /// error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the skipped text doesn't end on a character boundary.
+    pub fn bump(&mut self, len: usize) -> &'a str {
+        let start = self.index.0;
+        let end = (start + len).min(self.buffer.len());
+
+        assert!(
+            self.buffer.is_char_boundary(end),
+            "byte {end} isn't on a character boundary"
+        );
+
+        self.data = "";
+        self.index = (end, end);
+        self.matched = None;
+        self.exhausted = false;
+        self.debug_assert_cursors();
+
+        &self.buffer[start..end]
+    }
+
+    /// Discards the rest of the current line, including any pending partial
+    /// match, so lexing resumes at the start of the next line.
+    ///
+    /// Returns the number of bytes skipped from the committed cursor, counting
+    /// the `\n` itself. At the last line this skips to the end of the buffer.
+    /// Iteration resumes after an error.
+    pub fn skip_line(&mut self) -> usize {
+        let start = self.index.0;
+        let end = self.buffer[start..]
+            .find('\n')
+            .map_or(self.buffer.len(), |offset| start + offset + 1);
+
+        self.data = "";
+        self.index = (end, end);
+        self.matched = None;
+        self.exhausted = false;
+        self.debug_assert_cursors();
+
+        end - start
+    }
+
+    /// Returns an iterator pairing every token with the text skipped right
+    /// before it, such that interleaving gaps and token slices reconstructs the
+    /// input.
+    pub fn with_gaps(&mut self) -> Gaps<'_, 'a, T> {
+        Gaps::new(self)
+    }
+
+    /// Returns an iterator over the tokens along with their spans, stopping
+    /// at the first error.
+    pub fn spanned(&mut self) -> SpannedTokens<'_, 'a, T> {
+        SpannedTokens::new(self)
+    }
+
+    /// Returns an iterator over the tokens `significant` accepts, dropping
+    /// the others, such as whitespace and comment tokens kept for a
+    /// formatter but not wanted by a parser. Errors pass through unchanged.
+    pub fn filtered(self, significant: fn(&T) -> bool) -> FilteredLexer<'a, T> {
+        FilteredLexer::new(self, significant)
+    }
+
+    /// Returns the next token `significant` accepts, or the next error,
+    /// dropping the tokens before it.
+    pub fn next_significant(&mut self, significant: fn(&T) -> bool) -> Option<Result<'a, T>> {
+        self.find(|token| token.as_ref().map_or(true, significant))
+    }
+
+    /// Collects the remaining tokens, or returns the first error.
+    #[cfg(feature = "alloc")]
+    pub fn tokenize(&mut self) -> Result<'a, alloc::vec::Vec<T>> {
+        self.collect()
+    }
+
+    /// Returns an iterator that splits template strings delimited as in
+    /// `config` into chunks and interpolations, lexing everything else with
+    /// this lexer's rule.
+    #[cfg(feature = "alloc")]
+    pub fn with_templates<'c>(&mut self, config: TemplateConfig<'c>) -> Templates<'_, 'a, 'c, T> {
+        Templates::new(self, config)
+    }
+
+    /// Finds the first occurrence of `rule` at or after the byte offset
+    /// `start`, without moving the lexer's cursor.
+    ///
+    /// Every character boundary is tried as a candidate start and the match is
+    /// extended greedily, so `Literal("end")` is also found inside `append`.
+    /// Use [`Lexer::find_token_from`] to only consider whole tokens.
+    pub fn find_from(&self, start: usize, rule: &Rule<'a, T>) -> Option<Range<usize>> {
+        let mut start = start;
+
+        while start < self.buffer.len() {
+            if self.buffer.is_char_boundary(start) {
+                if let Some(end) = rule.longest_match(self.buffer, start) {
+                    return Some(start..end);
+                }
+            }
+
+            start += 1;
+        }
+
+        None
+    }
+
+    /// Finds the first token at or after the byte offset `start` that fully
+    /// matches `rule`, without moving the lexer's cursor.
+    ///
+    /// The buffer is split into tokens using the lexer's own rule, so matches
+    /// inside other tokens (identifiers, strings, comments) are not reported.
+    /// Text that no rule matches is skipped one character at a time.
+    pub fn find_token_from(&self, start: usize, rule: &Rule<'a, T>) -> Option<Range<usize>> {
+        let mut start = start;
+
+        while start < self.buffer.len() {
+            if !self.buffer.is_char_boundary(start) {
+                start += 1;
+                continue;
+            }
+
+            match self.rule.longest_match(self.buffer, start) {
+                Some(end) => {
+                    if rule
+                        .matches_in(Context::new(self.buffer, start..end).probe())
+                        .is_match()
+                    {
+                        return Some(start..end);
+                    }
+
+                    start = end;
+                }
+                None => start += 1,
+            }
+        }
+
+        None
+    }
+}
+
+/// Yields each token, stepping past skipped text, until the end of the
+/// buffer. An error is yielded once, after which the iterator is exhausted
+/// until [`Lexer::skip_char`], [`Lexer::bump`] or [`Lexer::skip_line`]
+/// recovers past it; [`Lexer::reset`] starts over.
+/// [`Lexer::with_recovery`] recovers from unknown text without an error.
+///
+/// Once exhausted, the iterator returns `None` without stepping, leaving
+/// the cursors as they are, until one of these methods or
+/// [`Lexer::push_back`] resumes it.
+impl<'a, T> Iterator for Lexer<'a, T> {
+    type Item = Result<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exhausted {
+            return None;
+        }
+
+        loop {
+            match self.step() {
+                Ok(Some(token)) => return Some(Ok(token)),
+                Ok(None) => {}
+                Err(Error::Eof) => {
+                    self.exhausted = true;
+                    return None;
+                }
+                Err(error) => {
+                    self.exhausted = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+
+    /// Bounds the remaining items by the tokens pushed back plus
+    /// [`PUSH_BACK_LIMIT`] items for each byte not yet committed, as a
+    /// [`Rule::ValueMany`] may split a byte into that many tokens, and one
+    /// more for an automatic terminator at the end of the input.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.exhausted {
+            return (0, Some(0));
+        }
+
+        let pushed = self.pushed.iter().flatten().count();
+        let upper = (self.buffer.len() - self.index.0)
+            .checked_mul(PUSH_BACK_LIMIT)
+            .and_then(|upper| upper.checked_add(pushed + 1));
+
+        (pushed, upper)
+    }
+}
+
+impl<T> core::iter::FusedIterator for Lexer<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_CODE: &str = r#"
+function add(a, b)
+    return a + b
+end
+"#;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Token<'a> {
+        And,
+        Break,
+        Do,
+        Else,
+        ElseIf,
+        End,
+        False,
+        For,
+        Function,
+        If,
+        In,
+        Local,
+        Nil,
+        Not,
+        Or,
+        Repeat,
+        Return,
+        Then,
+        True,
+        Until,
+        While,
+        Assign,
+        Identifier(&'a str),
+        Number(&'a str),
+        Operator(&'a str),
+    }
+
+    fn lua<'a>() -> Rule<'a, Token<'a>> {
+        Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::IdentifierOrKeyword {
+                ident: &Rule::Alphabetic,
+                keywords: &[
+                    ("and", || Token::And),
+                    ("break", || Token::Break),
+                    ("do", || Token::Do),
+                    ("else", || Token::Else),
+                    ("elseif", || Token::ElseIf),
+                    ("end", || Token::End),
+                    ("false", || Token::False),
+                    ("for", || Token::For),
+                    ("func", || Token::Function),
+                    ("function", || Token::Function),
+                    ("if", || Token::If),
+                    ("in", || Token::In),
+                    ("local", || Token::Local),
+                    ("nil", || Token::Nil),
+                    ("not", || Token::Not),
+                    ("or", || Token::Or),
+                    ("repeat", || Token::Repeat),
+                    ("return", || Token::Return),
+                    ("then", || Token::Then),
+                    ("true", || Token::True),
+                    ("until", || Token::Until),
+                    ("while", || Token::While),
+                ],
+                fallback: Token::Identifier,
+            },
+        ])
+    }
+
+    #[test]
+    fn it_works() {
+        let lexer = Lexer::<Token>::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Literal("and"), &|_| Token::And),
+                Rule::Value(&Rule::Literal("break"), &|_| Token::Break),
+                Rule::Value(&Rule::Literal("do"), &|_| Token::Do),
+                Rule::Value(&Rule::Literal("else"), &|_| Token::Else),
+                Rule::Value(&Rule::Literal("elseif"), &|_| Token::ElseIf),
+                Rule::Value(&Rule::Literal("end"), &|_| Token::End),
+                Rule::Value(&Rule::Literal("false"), &|_| Token::False),
+                Rule::Value(&Rule::Literal("for"), &|_| Token::For),
+                Rule::Value(
+                    &Rule::Either(&Rule::Literal("function"), &Rule::Literal("func")),
+                    &|_| Token::Function,
+                ),
+                Rule::Value(&Rule::Literal("if"), &|_| Token::If),
+                Rule::Value(&Rule::Literal("in"), &|_| Token::In),
+                Rule::Value(&Rule::Literal("local"), &|_| Token::Local),
+                Rule::Value(&Rule::Literal("nil"), &|_| Token::Nil),
+                Rule::Value(&Rule::Literal("not"), &|_| Token::Not),
+                Rule::Value(&Rule::Literal("or"), &|_| Token::Or),
+                Rule::Value(&Rule::Literal("repeat"), &|_| Token::Repeat),
+                Rule::Value(&Rule::Literal("return"), &|_| Token::Return),
+                Rule::Value(&Rule::Literal("then"), &|_| Token::Then),
+                Rule::Value(&Rule::Literal("true"), &|_| Token::True),
+                Rule::Value(&Rule::Literal("until"), &|_| Token::Until),
+                Rule::Value(&Rule::Literal("while"), &|_| Token::While),
+                Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+            ]),
+            TEST_CODE,
+        );
+        let tokens = lexer.flatten().collect::<Vec<_>>();
+
+        for token in &tokens {
+            println!("Token: {token:?}");
+        }
+
+        assert_eq!(
+            tokens,
+            Lexer::with_buffer(lua(), TEST_CODE)
+                .flatten()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(all(feature = "serde", feature = "alloc"))]
+    #[test]
+    fn lua_from_spec() {
+        fn keywords() -> &'static [Keyword<'static, Token<'static>>] {
+            match lua() {
+                Rule::Any([_, Rule::IdentifierOrKeyword { keywords, .. }]) => keywords,
+                _ => unreachable!(),
+            }
+        }
+
+        fn token<'a>(name: &str, value: &'a str) -> Token<'a> {
+            match keywords().iter().find(|(keyword, _)| *keyword == value) {
+                Some((_, token)) if name != "Identifier" => token(),
+                _ => Token::Identifier(value),
+            }
+        }
+
+        let mut rules = Vec::from([RuleSpec::new(RuleKind::Ignore {
+            rule: Box::new(RuleSpec::new(RuleKind::Whitespace)),
+        })]);
+
+        rules.extend(keywords().iter().map(|(keyword, token)| {
+            RuleSpec::new(RuleKind::Literal {
+                value: String::from(*keyword),
+            })
+            .token(format!("{:?}", token()))
+        }));
+        rules.push(RuleSpec::new(RuleKind::Alphabetic).token("Identifier"));
+
+        let json = serde_json::to_string_pretty(&GrammarSpec { rules }).unwrap();
+        let spec = serde_json::from_str::<GrammarSpec>(&json).unwrap();
+        let rule = Rule::from_spec(&spec, token);
+
+        assert!(json.contains(r#""token": "ElseIf""#));
+        assert_eq!(
+            Lexer::with_buffer(rule.as_rule(), TEST_CODE).collect::<Vec<_>>(),
+            Lexer::with_buffer(lua(), TEST_CODE).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Lexer::with_buffer(rule.as_rule(), "android elseif for\n").collect::<Vec<_>>(),
+            [
+                Ok(Token::Identifier("android")),
+                Ok(Token::ElseIf),
+                Ok(Token::For)
+            ]
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn lua_combinators() {
+        fn keyword<'a>(
+            keyword: &'a str,
+            out: &'a dyn Extract<'a, Token<'a>>,
+        ) -> OwnedRule<'a, Token<'a>> {
+            Rule::literal(keyword).map(out)
+        }
+
+        let rule = Rule::Whitespace
+            .ignored()
+            .or(keyword("and", &|_| Token::And))
+            .or(keyword("break", &|_| Token::Break))
+            .or(keyword("do", &|_| Token::Do))
+            .or(keyword("else", &|_| Token::Else))
+            .or(keyword("elseif", &|_| Token::ElseIf))
+            .or(keyword("end", &|_| Token::End))
+            .or(keyword("false", &|_| Token::False))
+            .or(keyword("for", &|_| Token::For))
+            .or(Rule::literal("function")
+                .or(Rule::literal("func"))
+                .map(&|_| Token::Function))
+            .or(keyword("if", &|_| Token::If))
+            .or(keyword("in", &|_| Token::In))
+            .or(keyword("local", &|_| Token::Local))
+            .or(keyword("nil", &|_| Token::Nil))
+            .or(keyword("not", &|_| Token::Not))
+            .or(keyword("or", &|_| Token::Or))
+            .or(keyword("repeat", &|_| Token::Repeat))
+            .or(keyword("return", &|_| Token::Return))
+            .or(keyword("then", &|_| Token::Then))
+            .or(keyword("true", &|_| Token::True))
+            .or(keyword("until", &|_| Token::Until))
+            .or(keyword("while", &|_| Token::While))
+            .or(Rule::Alphabetic.map(&Token::Identifier));
+
+        for input in [TEST_CODE, "android elseif func for\n"] {
+            assert_eq!(
+                Lexer::with_buffer(rule.as_rule(), input).collect::<Vec<_>>(),
+                Lexer::with_buffer(lua(), input).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn lua_is_valid() {
+        assert_eq!(lua().validate(), Ok(()));
+        assert!(Lexer::try_new(lua()).is_ok());
+    }
+
+    #[test]
+    fn step_spanned() {
+        let mut lexer = Lexer::with_buffer(lua(), TEST_CODE);
+        let mut tokens = Vec::new();
+
+        while tokens.len() < 2 {
+            if let Some(token) = lexer.step_spanned().unwrap() {
+                tokens.push(token);
+            }
+        }
+
+        assert!(tokens[0].eq_with_span(&Spanned::new(Token::Function, 1..9)));
+        assert!(tokens[1].eq_with_span(&Spanned::new(Token::Identifier("add"), 10..13)));
+        assert_eq!(lexer.text(tokens[1].span.clone()), "add");
+    }
+
+    #[test]
+    fn iterator() {
+        let mut lexer = Lexer::with_buffer(lua(), "local x @ end\n");
+
+        assert!(matches!(lexer.next(), Some(Ok(Token::Local))));
+        assert!(matches!(lexer.next(), Some(Ok(Token::Identifier("x")))));
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(Error::UnknownToken { text: "@", .. }))
+        ));
+        assert!(lexer.next().is_none());
+
+        lexer.reset("end\n");
+        assert!(matches!(lexer.next(), Some(Ok(Token::End))));
+        assert!(lexer.next().is_none());
+        assert!(lexer.next().is_none());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn tokenize() {
+        let mut lexer = Lexer::with_buffer(lua(), "local x\n");
+        assert_eq!(
+            lexer.tokenize().unwrap(),
+            [Token::Local, Token::Identifier("x")]
+        );
+
+        lexer.reset("local @\n");
+        assert!(matches!(
+            lexer.tokenize(),
+            Err(Error::UnknownToken { text: "@", .. })
+        ));
+    }
+
+    #[test]
+    fn final_token() {
+        let lex = |input| {
+            Lexer::with_buffer(lua(), input)
+                .flatten()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(lex("end"), [Token::End]);
+        assert_eq!(lex("local x"), [Token::Local, Token::Identifier("x")]);
+        assert_eq!(lex("x"), [Token::Identifier("x")]);
+
+        let mut lexer =
+            Lexer::with_buffer(Rule::Value(&Rule::Literal("end"), &|_| Token::End), "enden");
+
+        assert!(matches!(lexer.step(), Ok(None)));
+        assert!(matches!(lexer.step(), Ok(None)));
+        assert!(matches!(lexer.step(), Ok(None)));
+        assert!(matches!(lexer.step(), Ok(Some(Token::End))));
+        assert!(matches!(lexer.step(), Ok(None)));
+        assert!(matches!(lexer.step(), Ok(None)));
+        assert!(matches!(
+            lexer.step(),
+            Err(Error::UnknownToken { text: "en", .. })
+        ));
+    }
+
+    #[test]
+    fn identifier_or_keyword() {
+        let tokens = Lexer::with_buffer(lua(), "android and func elseif\n")
+            .flatten()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Identifier("android"),
+                Token::And,
+                Token::Function,
+                Token::ElseIf
+            ]
+        );
+
+        // Each step only looks one character past the slice.
+        let long = "a".repeat(2000);
+        let tokens = Lexer::with_buffer(lua(), &long)
+            .flatten()
+            .collect::<Vec<_>>();
+        assert_eq!(tokens, [Token::Identifier(&long)]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn rule_set() {
+        // As if read from a configuration file.
+        let keywords = Vec::from([
+            ("return", Token::Return),
+            ("function", Token::Function),
+            ("end", Token::End),
+            ("and", Token::And),
+        ]);
+
+        let rule = RuleSet::new()
+            .ignore(&Rule::Whitespace)
+            .rule(OwnedRule::Value(
+                Box::new(OwnedRule::Literals(Vec::from(["(", ")", ",", "+"]))),
+                &Token::Operator,
+            ))
+            .keywords(keywords)
+            .keyword("end", Token::Until)
+            .ident(Token::Identifier)
+            .build();
+
+        assert!(rule.as_rule().validate().is_ok());
+
+        let tokens = Lexer::with_buffer(rule.as_rule(), TEST_CODE)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Function,
+                Token::Identifier("add"),
+                Token::Operator("("),
+                Token::Identifier("a"),
+                Token::Operator(","),
+                Token::Identifier("b"),
+                Token::Operator(")"),
+                Token::Return,
+                Token::Identifier("a"),
+                Token::Operator("+"),
+                Token::Identifier("b"),
+                Token::End,
+            ]
+        );
+
+        let keywords_only = RuleSet::new().keyword("and", Token::And).build();
+        assert!(keywords_only.matches("and").is_match());
+        assert!(keywords_only.matches("android").is_none());
+    }
+
+    #[test]
+    fn adjacent_tokens() {
+        const RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+            Rule::Value(&Rule::Numeric, &Token::Number),
+            Rule::Value(&Rule::Literal("="), &|_| Token::Assign),
+            Rule::Value(&Rule::Literals(&["+", ";"]), &Token::Operator),
+        ]);
+
+        let tokens = Lexer::with_buffer(RULE, "x=1;y=2")
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Identifier("x"),
+                Token::Assign,
+                Token::Number("1"),
+                Token::Operator(";"),
+                Token::Identifier("y"),
+                Token::Assign,
+                Token::Number("2"),
+            ]
+        );
+
+        let tokens = Lexer::with_buffer(RULE, "a+b")
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Identifier("a"),
+                Token::Operator("+"),
+                Token::Identifier("b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn maximal_munch() {
+        // The keyword comes first, but a longer identifier match still wins.
+        let rule = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Literal("do"), &|_| Token::Do),
+            Rule::Value(&Rule::Literal("="), &|_| Token::Assign),
+            Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+        ]);
+
+        let tokens = Lexer::with_buffer(rule, "double d = do\n")
+            .flatten()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Identifier("double"),
+                Token::Identifier("d"),
+                Token::Assign,
+                Token::Do,
+            ]
+        );
+    }
+
+    #[test]
+    fn keywords() {
+        let rule = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Keywords(&[
+                ("do", || Token::Do),
+                ("else", || Token::Else),
+                ("elseif", || Token::ElseIf),
+                ("end", || Token::End),
+                ("in", || Token::In),
+            ]),
+            Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+        ]);
+
+        let pair = Rule::Keywords(&[("in", || Token::In), ("int", || Token::Local)]);
+
+        assert!(matches!(
+            pair.matches("in"),
+            MatchResult::Match(Some(Token::In))
+        ));
+        assert!(pair.matches("i").is_partial_match());
+        assert!(pair.matches("io").is_none());
+        assert!(pair.matches("inte").is_none());
+
+        let tokens = Lexer::with_buffer(rule, "else elseif elsewhere in int double do\n")
+            .flatten()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Else,
+                Token::ElseIf,
+                Token::Identifier("elsewhere"),
+                Token::In,
+                Token::Identifier("int"),
+                Token::Identifier("double"),
+                Token::Do,
+            ]
+        );
+    }
+
+    /// Declares what every variant reports for the empty slice. The match is
+    /// exhaustive so that a new variant has to declare its own.
+    fn empty_slice_result(rule: &Rule<'_, Token<'_>>) -> &'static str {
+        match rule {
+            Rule::Literal(_)
+            | Rule::LiteralIgnoreCase(_)
+            | Rule::Literals(_)
+            | Rule::Keywords(_)
+            | Rule::Numeric
+            | Rule::Integer
+            | Rule::Float
+            | Rule::HexInteger
+            | Rule::Number
+            | Rule::Signed(_)
+            | Rule::Alphabetic
+            | Rule::Whitespace
+            | Rule::WhitespaceOf(_)
+            | Rule::Chars(_)
+            | Rule::CharRange(_)
+            | Rule::Value(..)
+            | Rule::ValueFilter(..)
+            | Rule::ValueMany(..)
+            | Rule::Ignore(_)
+            | Rule::LineComment(_)
+            | Rule::BlockComment { .. }
+            | Rule::Delimited { .. }
+            | Rule::EndsWith(_)
+            | Rule::StartsWith(_)
+            | Rule::LineContinuation { .. }
+            | Rule::Except(..)
+            | Rule::Not(_)
+            | Rule::Only(_)
+            | Rule::Both(..)
+            | Rule::Either(..)
+            | Rule::All(..)
+            | Rule::Seq(_)
+            | Rule::Repeat { .. }
+            | Rule::Any(_)
+            | Rule::Priority(..)
+            | Rule::TrailingContext { .. }
+            | Rule::IdentifierOrKeyword { .. }
+            | Rule::AtLineStart(_)
+            | Rule::Gated(_)
+            | Rule::PushMode(..)
+            | Rule::PopMode(_)
+            | Rule::Embed { .. } => "PartialMatch",
+            #[cfg(feature = "alloc")]
+            Rule::ValueCow(..) | Rule::Owned(_) => "PartialMatch",
+            Rule::Quoted { .. } | Rule::Commit { .. } => "None",
+            #[cfg(feature = "unicode-segmentation")]
+            Rule::Grapheme => "None",
+        }
+    }
+
+    #[test]
+    fn empty_slice() {
+        #[cfg(feature = "alloc")]
+        let owned = OwnedRule::Literals(Vec::from(["end"]));
+
+        let table: &[Rule<Token>] = &[
+            Rule::Literal("end"),
+            Rule::Literal(""),
+            Rule::LiteralIgnoreCase("end"),
+            Rule::Literals(&["and", "or"]),
+            Rule::Keywords(&[("and", || Token::And), ("or", || Token::Or)]),
+            Rule::Numeric,
+            Rule::Integer,
+            Rule::Float,
+            Rule::HexInteger,
+            Rule::Number,
+            Rule::Signed(&Rule::Integer),
+            Rule::Alphabetic,
+            Rule::Whitespace,
+            Rule::WhitespaceOf(" \t"),
+            Rule::Chars(|c| c == '_'),
+            Rule::char_range('a'..='f'),
+            #[cfg(feature = "unicode-segmentation")]
+            Rule::Grapheme,
+            Rule::Value(&Rule::Literal("end"), &|_| Token::End),
+            Rule::ValueFilter(&Rule::Alphabetic, |value| Some(Token::Identifier(value))),
+            Rule::ValueMany(&Rule::Alphabetic, |value, emit| {
+                emit(0..value.len(), Token::Identifier(value))
+            }),
+            #[cfg(feature = "alloc")]
+            Rule::ValueCow(&Rule::Alphabetic, |_| Token::End),
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Quoted {
+                quote: '"',
+                escape: '\\',
+            },
+            Rule::LineComment("//"),
+            Rule::BlockComment {
+                open: "/*",
+                close: "*/",
+                nested: false,
+            },
+            Rule::Delimited {
+                open: "[[",
+                close: "]]",
+                escape: Some('\\'),
+            },
+            Rule::LineContinuation { marker: "\\" },
+            Rule::EndsWith("\n"),
+            Rule::StartsWith("--"),
+            Rule::Not(&Rule::Literal("end")),
+            Rule::Except(&Rule::Alphabetic, &Rule::Literal("end")),
+            Rule::Only(&Rule::Alphabetic),
+            Rule::Both(&Rule::Alphabetic, &Rule::Literal("end")),
+            Rule::Either(&Rule::Literal("and"), &Rule::Literal("or")),
+            Rule::All(&[Rule::Alphabetic, Rule::Literal("end")], &|_| Token::End),
+            Rule::Seq(&[Rule::Literal("a"), Rule::Numeric]),
+            Rule::Repeat {
+                rule: &Rule::Literal("a"),
+                min: 0,
+                max: None,
+            },
+            Rule::Any(&[Rule::Literal("end"), Rule::Numeric]),
+            Rule::Priority(1, &Rule::Literal("end")),
+            Rule::TrailingContext {
+                main: &Rule::Alphabetic,
+                trail: &Rule::Literal("("),
+            },
+            Rule::IdentifierOrKeyword {
+                ident: &Rule::Alphabetic,
+                keywords: &[("end", || Token::End)],
+                fallback: Token::Identifier,
+            },
+            Rule::AtLineStart(&Rule::Alphabetic),
+            Rule::Gated(&Rule::Alphabetic),
+            Rule::PushMode(&Rule::Literal("\""), "string"),
+            Rule::PopMode(&Rule::Literal("\"")),
+            Rule::Embed {
+                open: &Rule::Literal("`"),
+                lex: |_, _| (Token::End, 0),
+            },
+            Rule::Commit {
+                tag: "string",
+                prefix: &Rule::Literal("\""),
+                rule: &Rule::Quoted {
+                    quote: '"',
+                    escape: '\\',
+                },
+            },
+            #[cfg(feature = "alloc")]
+            Rule::Owned(&owned),
+        ];
+
+        for rule in table {
+            assert_eq!(
+                format!("{:?}", rule.matches("")),
+                empty_slice_result(rule),
+                "{rule:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn char_classes() {
+        const IDENTIFIER: Rule<Token> = Rule::Chars(|c| c.is_ascii_alphabetic() || c == '_');
+        const OPERATOR: Rule<Token> = Rule::char_in("+-*/%");
+        const DIGIT: Rule<Token> = Rule::char_range('0'..='9');
+
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Value(&IDENTIFIER, &Token::Identifier),
+                Rule::Value(&OPERATOR, &Token::Operator),
+                Rule::Value(&DIGIT, &Token::Number),
+            ]),
+            "a+b*2",
+        )
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Identifier("a"),
+                Token::Operator("+"),
+                Token::Identifier("b"),
+                Token::Operator("*"),
+                Token::Number("2")
+            ]
+        );
+
+        let hex = Rule::<Token>::char_range('a'..='f');
+        assert!(hex.matches("cafe").is_match());
+        assert!(hex.matches("face!").is_none());
+        assert!(hex.matches("").is_partial_match());
+        assert!(Rule::<Token>::Chars(|c| c == '_').matches("__").is_match());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn classes_checked_incrementally() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static CHECKED: AtomicUsize = AtomicUsize::new(0);
+
+        let rule = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(
+                &Rule::Chars(|c| {
+                    CHECKED.fetch_add(1, Ordering::Relaxed);
+                    c.is_alphabetic()
+                }),
+                &Token::Identifier,
+            ),
+        ]);
+        let input = "a".repeat(10_000) + " b";
+        let tokens = Lexer::with_buffer(rule, &input)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            tokens,
+            [Token::Identifier(&input[..10_000]), Token::Identifier("b")]
+        );
+        // Each step only tests the added character, rather than the whole
+        // identifier again.
+        assert!(CHECKED.load(Ordering::Relaxed) < 8 * input.len());
+    }
+
+    #[test]
+    fn seq() {
+        const HEX: Rule<'static, Token<'static>> = Rule::Seq(&[
+            Rule::Literal("0x"),
+            Rule::Repeat {
+                rule: &Rule::Literals(&[
+                    "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "a", "b", "c", "d", "e", "f",
+                ]),
+                min: 1,
+                max: None,
+            },
+        ]);
+
+        assert!(HEX.matches("0").is_partial_match());
+        assert!(HEX.matches("0x").is_partial_match());
+        assert!(HEX.matches("0x1f").is_match());
+        assert!(HEX.matches("0x1g").is_none());
+        assert!(HEX.matches("1f").is_none());
+
+        let signed: Rule<Token> = Rule::Seq(&[
+            Rule::Alphabetic,
+            Rule::Repeat {
+                rule: &Rule::Literal("-"),
+                min: 0,
+                max: Some(1),
+            },
+            Rule::Repeat {
+                rule: &Rule::Literals(&["1", "2", "3"]),
+                min: 1,
+                max: None,
+            },
+        ]);
+
+        assert!(signed.matches("foo123").is_match());
+        assert!(signed.matches("foo-123").is_match());
+        assert!(signed.matches("foo--123").is_none());
+        assert!(signed.matches("foo-").is_partial_match());
+        assert!(signed.matches("foo-bar").is_none());
+
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&HEX, &Token::Number),
+                Rule::Value(
+                    &Rule::Seq(&[Rule::Alphabetic, Rule::Numeric]),
+                    &Token::Identifier,
+                ),
+            ]),
+            "foo123 0xff a1",
+        )
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Identifier("foo123"),
+                Token::Number("0xff"),
+                Token::Identifier("a1")
+            ]
+        );
+    }
+
+    #[test]
+    fn repeat() {
+        const DIGIT: Rule<'static, Token<'static>> =
+            Rule::Literals(&["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"]);
+        const DIGITS: Rule<'static, Token<'static>> = Rule::Repeat {
+            rule: &DIGIT,
+            min: 1,
+            max: None,
+        };
+
+        let optional: Rule<Token> = Rule::Repeat {
+            rule: &Rule::Literal("ab"),
+            min: 0,
+            max: None,
+        };
+        assert!(optional.matches("").is_partial_match());
+        assert!(optional.matches("a").is_partial_match());
+        assert!(optional.matches("abab").is_match());
+        assert!(optional.matches("aba").is_partial_match());
+        assert!(optional.matches("abba").is_none());
+
+        let exact: Rule<Token> = Rule::Repeat {
+            rule: &Rule::Literal("a"),
+            min: 2,
+            max: Some(2),
+        };
+        assert!(exact.matches("a").is_partial_match());
+        assert!(exact.matches("aa").is_match());
+        assert!(exact.matches("aaa").is_none());
+
+        let seq: Rule<Token> = Rule::Seq(&[
+            Rule::Literal("<"),
+            Rule::Repeat {
+                rule: &Rule::Literal("-"),
+                min: 0,
+                max: None,
+            },
+            Rule::Literal(">"),
+        ]);
+        assert!(seq.matches("<>").is_match());
+        assert!(seq.matches("<--->").is_match());
+
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(
+                    &Rule::Seq(&[
+                        DIGITS,
+                        Rule::Repeat {
+                            rule: &Rule::Seq(&[Rule::Literal("."), DIGITS]),
+                            min: 0,
+                            max: Some(1),
+                        },
+                    ]),
+                    &Token::Number,
+                ),
+            ]),
+            "123.456 7 8.9",
+        )
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Number("123.456"),
+                Token::Number("7"),
+                Token::Number("8.9")
+            ]
+        );
+    }
+
+    const FIND_RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+        Rule::Ignore(&Rule::Whitespace),
+        Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+    ]);
+
+    #[test]
+    fn unknown_token() {
+        let mut lexer = Lexer::with_buffer(FIND_RULE, "ab @€ cd\n");
+        let mut tokens = Vec::new();
+        let mut unknown = Vec::new();
+
+        loop {
+            match lexer.step() {
+                Ok(Some(token)) => tokens.push(token),
+                Ok(None) => {}
+                Err(error @ Error::UnknownToken { text, .. }) => {
+                    unknown.push((text, lexer.error_span(&error)));
+                    assert_eq!(lexer.skip_char(), text);
+                }
+                Err(_) => break,
+            }
+        }
+
+        assert_eq!(tokens, [Token::Identifier("ab"), Token::Identifier("cd")]);
+        assert_eq!(unknown, [("@", 3..4), ("€", 4..7)]);
+    }
+
+    #[test]
+    fn skip_line() {
+        let mut lexer = Lexer::with_buffer(
+            Rule::Value(&Rule::Literal("end"), &|_| Token::End),
+            "@@ bad\nend\n",
+        );
+
+        assert!(matches!(
+            lexer.step(),
+            Err(Error::UnknownToken { text: "@", .. })
+        ));
+        assert_eq!(lexer.skip_line(), 7);
+
+        let token = lexer.by_ref().flatten().next();
+        assert!(matches!(token, Some(Token::End)));
+
+        assert_eq!(lexer.skip_line(), 1);
+        assert_eq!(lexer.skip_line(), 0);
+    }
+
+    #[test]
+    fn progress() {
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Literal("end"), &|_| Token::End),
+            ]),
+            "end end\n",
+        );
+
+        assert_eq!(lexer.buffer_len(), 8);
+        assert_eq!(lexer.consumed_bytes(), 0);
+        assert!(!lexer.is_finished());
+
+        assert!(matches!(lexer.by_ref().flatten().next(), Some(Token::End)));
+        assert_eq!(lexer.consumed_bytes(), 3);
+        assert!(!lexer.is_finished());
+
+        assert_eq!(lexer.bytes_consumed(), 3);
+
+        assert!(matches!(lexer.by_ref().flatten().next(), Some(Token::End)));
+        assert_eq!(lexer.consumed_bytes(), 7);
+        assert_eq!(lexer.pending_bytes(), 0);
+        assert_eq!(lexer.remaining_bytes(), 1);
+
+        assert!(lexer.by_ref().flatten().next().is_none());
+        assert_eq!(lexer.consumed_bytes(), 8);
+        assert_eq!(lexer.pending_bytes(), 0);
+        assert_eq!(lexer.remaining_bytes(), 0);
+        assert!(lexer.is_finished());
+
+        assert_eq!(lexer.skip_line(), 0);
+        assert_eq!(lexer.consumed_bytes(), 8);
+
+        lexer.reset("end");
+        assert_eq!(lexer.buffer_len(), 3);
+        assert_eq!(lexer.consumed_bytes(), 0);
+        assert!(!lexer.is_finished());
+    }
+
+    #[test]
+    fn fused() {
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Literal("end"), &|_| Token::End),
+            ]),
+            "end end\n",
+        );
+
+        assert_eq!(lexer.size_hint(), (0, Some(8 * PUSH_BACK_LIMIT + 1)));
+        assert_eq!(lexer.by_ref().flatten().count(), 2);
+        assert_eq!(lexer.progress(), (8, 8));
+
+        let cursors =
+            |lexer: &Lexer<Token>| (lexer.progress(), lexer.pending_bytes(), lexer.span());
+        let exhausted = cursors(&lexer);
+
+        for _ in 0..10 {
+            assert!(lexer.next().is_none());
+            assert_eq!(cursors(&lexer), exhausted);
+        }
+
+        assert_eq!(lexer.size_hint(), (0, Some(0)));
+
+        lexer.push_back(Token::End, 4..7);
+        assert_eq!(lexer.size_hint(), (1, Some(2)));
+        assert!(matches!(lexer.next(), Some(Ok(Token::End))));
+        assert!(lexer.next().is_none());
+
+        lexer.reset("end");
+        assert_eq!(lexer.progress(), (0, 3));
+        assert!(matches!(lexer.next(), Some(Ok(Token::End))));
+        assert!(lexer.next().is_none());
+
+        lexer.reset("@");
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(Error::UnknownToken { .. }))
+        ));
+        assert!(lexer.next().is_none());
+        assert_eq!(lexer.progress(), (0, 1));
+    }
+
+    #[test]
+    fn byte_counts() {
+        fn assert_counts(lexer: &Lexer<Token>) {
+            assert_eq!(
+                lexer.consumed_bytes() + lexer.pending_bytes() + lexer.remaining_bytes(),
+                lexer.buffer().len()
+            );
+        }
+
+        let rule = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+        ]);
+        let mut lexer = Lexer::with_buffer(rule, "ab cd\n@@\nef");
+
+        assert_eq!(lexer.buffer(), "ab cd\n@@\nef");
+        assert_eq!(lexer.remaining_bytes(), 11);
+
+        assert!(matches!(lexer.step(), Ok(None)));
+        assert_eq!(lexer.pending_bytes(), 1);
+        assert_counts(&lexer);
+
+        while let Ok(token) = lexer.step() {
+            assert_counts(&lexer);
+
+            if token == Some(Token::Identifier("cd")) {
+                lexer.push_back(Token::Identifier("cd"), 3..5);
+                assert!(matches!(lexer.step(), Ok(Some(_))));
+                assert_counts(&lexer);
+
+                lexer.skip_line();
+                lexer.skip_line();
+                assert_eq!(lexer.consumed_bytes(), 9);
+                assert_eq!(lexer.pending_bytes(), 0);
+                assert_counts(&lexer);
+            }
+        }
+
+        assert_counts(&lexer);
+        assert_eq!(lexer.remaining_bytes(), 0);
+
+        lexer.reset("ab cd\n");
+        assert_eq!(lexer.with_gaps().count(), 3);
+        assert_counts(&lexer);
+
+        #[cfg(feature = "alloc")]
+        {
+            lexer.reset("ab `x${cd}` ef\n");
+            assert_eq!(lexer.with_templates(TemplateConfig::JAVASCRIPT).count(), 8);
+            assert_counts(&lexer);
+        }
+    }
+
+    #[test]
+    fn recovery() {
+        let buffer = "ab @cd #!ef";
+        let mut lexer = Lexer::with_buffer(FIND_RULE, buffer);
+
+        assert!(matches!(lexer.next(), Some(Ok(Token::Identifier("ab")))));
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(Error::UnknownToken {
+                text: "@",
+                at: 3,
+                ..
+            }))
+        ));
+        assert_eq!(lexer.offset(), 3);
+        assert_eq!(lexer.consumed(), "ab ");
+        assert_eq!(lexer.skip_char(), "@");
+        assert_eq!([lexer.consumed(), lexer.remainder()].concat(), buffer);
+
+        assert!(matches!(lexer.step(), Ok(None)));
+        assert_eq!(lexer.pending(), "c");
+        assert_eq!(
+            [lexer.consumed(), lexer.pending(), lexer.remainder()].concat(),
+            buffer
+        );
+
+        assert!(matches!(lexer.next(), Some(Ok(Token::Identifier("cd")))));
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(Error::UnknownToken { .. }))
+        ));
+        assert_eq!(lexer.bump(2), "#!");
+        assert_eq!(lexer.offset(), 9);
+        assert_eq!(lexer.remainder(), "ef");
+
+        assert!(matches!(lexer.next(), Some(Ok(Token::Identifier("ef")))));
+        assert!(lexer.next().is_none());
+        assert_eq!(lexer.consumed(), buffer);
+        assert_eq!(lexer.bump(1), "");
+    }
+
+    #[test]
+    fn non_ascii() {
+        #[derive(Debug, PartialEq)]
+        enum Token<'a> {
+            Let,
+            Assign,
+            Identifier(&'a str),
+            Number(&'a str),
+            Other(&'a str),
+        }
+
+        const RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Literal("let"), &|_| Token::Let),
+            Rule::Value(&Rule::Literal("="), &|_| Token::Assign),
+            Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+            Rule::Value(&Rule::Numeric, &Token::Number),
+            Rule::Value(&Rule::Literal("🦀"), &Token::Other),
+        ]);
+
+        let tokens = Lexer::with_buffer(RULE, "let π = 3 λx ünïcödé 🦀 ٣\n")
+            .flatten()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Let,
+                Token::Identifier("π"),
+                Token::Assign,
+                Token::Number("3"),
+                Token::Identifier("λx"),
+                Token::Identifier("ünïcödé"),
+                Token::Other("🦀"),
+                Token::Number("٣"),
+            ]
+        );
+
+        // A combining mark isn't alphabetic, so only the precomposed letter
+        // continues an identifier.
+        assert!(RULE.matches("e\u{301}").is_none());
+        assert!(matches!(
+            RULE.matches("\u{e9}"),
+            MatchResult::Match(Some(Token::Identifier("\u{e9}")))
+        ));
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn graphemes() {
+        #[derive(Debug)]
+        struct Cluster<'a>(&'a str);
+
+        let lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Grapheme, &Cluster),
+            ]),
+            "👩\u{200d}👩\u{200d}👧 e\u{301}x ",
+        )
+        .graphemes(true);
+
+        let clusters = lexer.flatten().map(|Cluster(c)| c).collect::<Vec<_>>();
+
+        assert_eq!(clusters, ["👩\u{200d}👩\u{200d}👧", "e\u{301}", "x"]);
+    }
+
+    #[test]
+    fn strict() {
+        const RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Literal("end"), &|_| Token::End),
+            Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+        ]);
+
+        let mut lexer = Lexer::with_buffer(RULE, "end ").strict(true);
+
+        loop {
+            match lexer.step() {
+                Ok(_) => {}
+                Err(Error::Ambiguous { at, rules }) => {
+                    assert_eq!(at, 0);
+                    assert_eq!(rules, [1, 2]);
+                    break;
+                }
+                Err(error) => panic!("unexpected error: {error:?}"),
+            }
+        }
+
+        let tokens = Lexer::with_buffer(RULE, "end ")
+            .flatten()
+            .collect::<Vec<_>>();
+        assert!(matches!(tokens.as_slice(), [Token::End]));
+
+        let mut lexer = Lexer::with_buffer(RULE, "x ").strict(true);
+        assert!(matches!(lexer.step(), Ok(None)));
+        assert!(matches!(lexer.step(), Ok(Some(Token::Identifier("x")))));
+
+        // A priority settles the same overlap, so there is nothing to report.
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+                Rule::Value(&Rule::Priority(1, &Rule::Literal("end")), &|_| Token::End),
+            ]),
+            "end ",
+        )
+        .strict(true);
+
+        assert!(matches!(lexer.next(), Some(Ok(Token::End))));
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn trailing_context() {
+        #[derive(Debug, PartialEq)]
+        enum Token<'a> {
+            Identifier(&'a str),
+            Number(&'a str),
+            Range,
+            RangeInclusive,
+        }
+
+        fn lex(input: &str) -> Vec<Token<'_>> {
+            Lexer::with_buffer(
+                Rule::Any(&[
+                    Rule::Ignore(&Rule::Whitespace),
+                    Rule::TrailingContext {
+                        main: &Rule::Value(&Rule::Literal(".."), &|_| Token::Range),
+                        trail: &Rule::Either(&Rule::Numeric, &Rule::Alphabetic),
+                    },
+                    Rule::Value(&Rule::Literal("..="), &|_| Token::RangeInclusive),
+                    Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+                    Rule::Value(&Rule::Numeric, &Token::Number),
+                ]),
+                input,
+            )
+            .flatten()
+            .collect()
+        }
+
+        assert_eq!(
+            lex("a..b "),
+            [Token::Identifier("a"), Token::Range, Token::Identifier("b")]
+        );
+        assert_eq!(
+            lex("a..=1 "),
+            [
+                Token::Identifier("a"),
+                Token::RangeInclusive,
+                Token::Number("1")
+            ]
+        );
+
+        let rule: Rule<()> = Rule::TrailingContext {
+            main: &Rule::Literal(".."),
+            trail: &Rule::Numeric,
+        };
+
+        assert!(rule.matches_in(Context::new("..5", 0..2)).is_match());
+        assert!(rule
+            .matches_in(Context::new("..=", 0..2))
+            .is_partial_match());
+        assert!(rule.matches("..").is_partial_match());
+    }
+
+    #[test]
+    fn whitespace_of() {
+        const NBSP: &str = "\u{a0}";
+
+        let ascii = Rule::<()>::WhitespaceOf(" \t\r\n");
+
+        assert!(Rule::<()>::Whitespace.matches(NBSP).is_match());
+        assert!(ascii.matches(NBSP).is_none());
+        assert!(ascii.matches(" \t\r\n").is_match());
+
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::WhitespaceOf(" ;")),
+                Rule::Value(&Rule::Literal("end"), &|_| Token::End),
+            ]),
+            "end; ;end ",
+        )
+        .flatten()
+        .collect::<Vec<_>>();
+
+        assert!(matches!(tokens.as_slice(), [Token::End, Token::End]));
+    }
+
+    #[test]
+    fn auto_terminator() {
+        #[derive(Debug, PartialEq)]
+        enum Go<'a> {
+            Identifier(&'a str),
+            Number(&'a str),
+            Punctuation(&'a str),
+            Return,
+            Semicolon,
+        }
+
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Literal("return"), &|_| Go::Return),
+                Rule::Value(
+                    &Rule::Literals(&["=", "(", ")", "{", "}"]),
+                    &Go::Punctuation,
+                ),
+                Rule::Value(&Rule::Alphabetic, &Go::Identifier),
+                Rule::Value(&Rule::Numeric, &Go::Number),
+            ]),
+            "a = 1\nf(a)\n{\n}\nreturn\n",
+        )
+        .with_auto_terminator(
+            |token| {
+                matches!(
+                    token,
+                    Go::Identifier(_) | Go::Number(_) | Go::Return | Go::Punctuation(")" | "}")
+                )
+            },
+            || Go::Semicolon,
+        )
+        .flatten()
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                Go::Identifier("a"),
+                Go::Punctuation("="),
+                Go::Number("1"),
+                Go::Semicolon,
+                Go::Identifier("f"),
+                Go::Punctuation("("),
+                Go::Identifier("a"),
+                Go::Punctuation(")"),
+                Go::Semicolon,
+                Go::Punctuation("{"),
+                Go::Punctuation("}"),
+                Go::Semicolon,
+                Go::Return,
+                Go::Semicolon,
+            ]
+        );
+    }
+
+    #[test]
+    fn line_continuation() {
+        #[derive(Debug, PartialEq)]
+        enum Token<'a> {
+            Identifier(&'a str),
+            Operator(&'a str),
+            Semicolon,
+        }
+
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Ignore(&Rule::LineContinuation { marker: "\\" }),
+                Rule::Value(&Rule::Literals(&["=", "+"]), &Token::Operator),
+                Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+            ]),
+            "a = b \\\n  + c \\\r\n\t+ d\ne\n",
+        )
+        .with_auto_terminator(
+            |token| matches!(token, Token::Identifier(_)),
+            || Token::Semicolon,
+        )
+        .flatten()
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Identifier("a"),
+                Token::Operator("="),
+                Token::Identifier("b"),
+                Token::Operator("+"),
+                Token::Identifier("c"),
+                Token::Operator("+"),
+                Token::Identifier("d"),
+                Token::Semicolon,
+                Token::Identifier("e"),
+                Token::Semicolon,
+            ]
+        );
+    }
+
+    #[test]
+    fn any_ignores_partial_matches() {
+        fn lex<'a>(rules: &'a [Rule<'a, &'a str>], input: &'a str) -> Vec<&'a str> {
+            Lexer::with_buffer(Rule::Any(rules), input)
+                .flatten()
+                .collect()
+        }
+
+        const INPUT: &str = "= == =>= a\n";
+        const EXPECTED: [&str; 5] = ["=", "==", "=>", "=", "a"];
+
+        let orderings: [&[Rule<&str>]; 3] = [
+            &[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Literal("="), &|value| value),
+                Rule::Value(&Rule::Literal("=="), &|value| value),
+                Rule::Value(&Rule::Literal("=>"), &|value| value),
+                Rule::Value(&Rule::Alphabetic, &|value| value),
+            ],
+            &[
+                Rule::Value(&Rule::Literal("=>"), &|value| value),
+                Rule::Value(&Rule::Literal("=="), &|value| value),
+                Rule::Value(&Rule::Literal("="), &|value| value),
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Alphabetic, &|value| value),
+            ],
+            &[
+                Rule::Value(&Rule::Literal("a long keyword"), &|value| value),
+                Rule::Value(&Rule::Literal("=>"), &|value| value),
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Literals(&["==", "="]), &|value| value),
+                Rule::Value(&Rule::Alphabetic, &|value| value),
+            ],
+        ];
+
+        for rules in orderings {
+            assert_eq!(lex(rules, INPUT), EXPECTED);
+        }
+
+        let rule: Rule<()> = Rule::Any(&[Rule::Literal("ab"), Rule::Literal("a")]);
+        assert!(rule.matches("a").is_match());
+
+        let rule: Rule<()> = Rule::Either(&Rule::Literal("ab"), &Rule::Literal("a"));
+        assert!(rule.matches("a").is_match());
+    }
+
+    #[test]
+    fn any_first_listed_wins() {
+        fn lex<'a>(rules: &'a [Rule<'a, Token<'a>>], input: &'a str) -> Vec<Token<'a>> {
+            Lexer::with_buffer(Rule::Any(rules), input)
+                .flatten()
+                .collect()
+        }
+
+        let keyword_first = [
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Literal("in"), &|_| Token::In),
+            Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+        ];
+
+        assert_eq!(lex(&keyword_first, "inner\n"), [Token::Identifier("inner")]);
+        assert_eq!(
+            lex(&keyword_first, "in i\n"),
+            [Token::In, Token::Identifier("i")]
+        );
+
+        // Listed first, the identifier shadows the keyword.
+        let identifier_first = [
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+            Rule::Value(&Rule::Literal("in"), &|_| Token::In),
+        ];
+
+        assert_eq!(lex(&identifier_first, "in\n"), [Token::Identifier("in")]);
+    }
+
+    #[test]
+    fn priority() {
+        fn lex<'a>(rules: &'a [Rule<'a, Token<'a>>], input: &'a str) -> Vec<Token<'a>> {
+            Lexer::with_buffer(Rule::Any(rules), input)
+                .flatten()
+                .collect()
+        }
+
+        // Ranked above the identifier, the keyword wins although listed last.
+        let keyword_last = [
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+            Rule::Priority(1, &Rule::Value(&Rule::Literal("end"), &|_| Token::End)),
+        ];
+
+        assert_eq!(lex(&keyword_last, "end\n"), [Token::End]);
+        // The longest match still comes first.
+        assert_eq!(
+            lex(&keyword_last, "endless\n"),
+            [Token::Identifier("endless")]
+        );
+        assert_eq!(
+            lex(&keyword_last, "end endless\n"),
+            [Token::End, Token::Identifier("endless")]
+        );
+
+        // Between equal priorities, the first listed wins.
+        let tied = [
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Priority(2, &Rule::Literal("in")), &|_| Token::In),
+            Rule::Value(&Rule::Priority(2, &Rule::LiteralIgnoreCase("in")), &|_| {
+                Token::End
+            }),
+            Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+        ];
+
+        assert_eq!(
+            lex(&tied, "in IN inner\n"),
+            [Token::In, Token::End, Token::Identifier("inner")]
+        );
+
+        let rule = Rule::Any(&keyword_last);
+        let mut lexer = Lexer::with_buffer(rule.clone(), "end ").strict(true);
+        assert!(lexer.all(|token| token.is_ok()));
+        assert_eq!(
+            rule.const_matches("end"),
+            Some(MatchResult::Match(Some(())))
+        );
+        #[cfg(feature = "alloc")]
+        assert!(rule.validate().is_ok());
+
+        let mut lexer = Lexer::with_buffer(Rule::Any(&tied), "in ").strict(true);
+        assert!(matches!(
+            lexer.find_map(Result::err),
+            Some(Error::Ambiguous {
+                at: 0,
+                rules: [1, 2]
+            })
+        ));
+    }
+
+    #[test]
+    fn literal_ignore_case() {
+        const SELECT: Rule<'static, ()> = Rule::LiteralIgnoreCase("select");
+
+        assert!(SELECT.matches("select").is_match());
+        assert!(SELECT.matches("SELECT").is_match());
+        assert!(SELECT.matches("SeLeCt").is_match());
+        assert!(SELECT.matches("SEL").is_partial_match());
+        assert!(SELECT.matches("selects").is_none());
+        assert!(SELECT.matches("delete").is_none());
+
+        // Lowercase mapping, not full case folding.
+        let street: Rule<()> = Rule::LiteralIgnoreCase("straße");
+        assert!(street.matches("STRAẞE").is_match());
+        assert!(street.matches("STRASSE").is_none());
+
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::LiteralIgnoreCase("from"), &Token::Identifier),
+                Rule::Value(&Rule::Alphabetic, &|_| Token::Nil),
+            ]),
+            "FROM From fromage\n",
+        );
+
+        assert_eq!(
+            lexer.by_ref().flatten().collect::<Vec<_>>(),
+            [
+                Token::Identifier("FROM"),
+                Token::Identifier("From"),
+                Token::Nil
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_error_text() {
+        let input = "@".repeat(10_000);
+        let error = Error::UnknownToken {
+            text: &input,
+            at: 0,
+            line: 1,
+            column: 1,
+        };
+
+        let message = error.to_string();
+        assert_eq!(
+            message,
+            format!("unknown token `{}…` (10000 bytes) at 1:1", "@".repeat(64))
+        );
+        assert_eq!(
+            error.display(3).to_string(),
+            "unknown token `@@@…` (10000 bytes) at 1:1"
+        );
+        assert_eq!(error.display(Error::TEXT_LIMIT).truncated(), Some(10_000));
+        assert_eq!(error.display(10_000).truncated(), None);
+
+        let Error::UnknownToken { text, .. } = error else {
+            unreachable!();
+        };
+        assert_eq!(text.len(), 10_000);
+    }
+
+    #[test]
+    fn line_containing() {
+        let lexer = Lexer::with_buffer(FIND_RULE, "first\r\nsecond\nlast");
+
+        assert_eq!(lexer.current_line(), "first");
+        assert_eq!(lexer.line_containing(3), ("first", 0..5));
+        assert_eq!(lexer.line_containing(5), ("first", 0..5));
+        assert_eq!(lexer.line_containing(6), ("first", 0..5));
+        assert_eq!(lexer.line_containing(7), ("second", 7..13));
+        assert_eq!(lexer.line_containing(13), ("second", 7..13));
+        assert_eq!(lexer.line_containing(14), ("last", 14..18));
+        assert_eq!(lexer.line_containing(18), ("last", 14..18));
+        assert_eq!(lexer.line_containing(100), ("last", 14..18));
+
+        let lexer = Lexer::with_buffer(FIND_RULE, "a\n\nb\r");
+        assert_eq!(lexer.line_containing(2), ("", 2..2));
+        assert_eq!(lexer.line_containing(3), ("b\r", 3..5));
+    }
+
+    #[test]
+    fn push_back() {
+        #[derive(Debug, PartialEq)]
+        enum Token<'a> {
+            Identifier(&'a str),
+            Less,
+            Greater,
+            Shift,
+        }
+
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Literal(">>"), &|_| Token::Shift),
+                Rule::Value(&Rule::Literal(">"), &|_| Token::Greater),
+                Rule::Value(&Rule::Literal("<"), &|_| Token::Less),
+                Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+            ]),
+            "a<b<c>> d\n",
+        );
+
+        let mut tokens = Vec::new();
+
+        while let Some(token) = lexer.by_ref().flatten().next() {
+            let span = lexer.span();
+
+            if token == Token::Shift {
+                lexer.push_back(Token::Greater, span.start + 1..span.end);
+                tokens.push((Token::Greater, span.start..span.start + 1));
+            } else {
+                tokens.push((token, span));
+            }
+        }
+
+        assert_eq!(
+            tokens,
+            [
+                (Token::Identifier("a"), 0..1),
+                (Token::Less, 1..2),
+                (Token::Identifier("b"), 2..3),
+                (Token::Less, 3..4),
+                (Token::Identifier("c"), 4..5),
+                (Token::Greater, 5..6),
+                (Token::Greater, 6..7),
+                (Token::Identifier("d"), 8..9),
+            ]
+        );
+
+        lexer.push_back(Token::Less, 0..1);
+        lexer.push_back(Token::Greater, 1..2);
+
+        assert!(matches!(lexer.step(), Ok(Some(Token::Greater))));
+        assert_eq!(lexer.span(), 1..2);
+        assert!(matches!(lexer.step(), Ok(Some(Token::Less))));
+        assert!(matches!(lexer.step(), Err(Error::Eof)));
+    }
+
+    #[test]
+    fn value_many() {
+        #[derive(Debug, PartialEq)]
+        enum Token<'a> {
+            Identifier(&'a str),
+            Less,
+            Greater,
+            Suffix(&'a str),
+        }
+
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::ValueMany(&Rule::Literal(">>"), |_, emit| {
+                    emit(0..1, Token::Greater);
+                    emit(1..2, Token::Greater);
+                }),
+                Rule::Value(&Rule::Literal(">"), &|_| Token::Greater),
+                Rule::Value(&Rule::Literal("<"), &|_| Token::Less),
+                Rule::ValueMany(
+                    &Rule::Seq(&[Rule::Numeric, Rule::Literals(&["ms", "s"])]),
+                    |value, emit| {
+                        let digits = value.trim_end_matches(char::is_alphabetic);
+
+                        emit(0..digits.len(), Token::Identifier(digits));
+                        emit(
+                            digits.len()..value.len(),
+                            Token::Suffix(&value[digits.len()..]),
+                        );
+                    },
+                ),
+                Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+            ]),
+            "a<b<c>> 10ms\n",
+        );
+
+        let tokens = lexer
+            .spanned()
+            .map(|token| (token.value, token.span))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                (Token::Identifier("a"), 0..1),
+                (Token::Less, 1..2),
+                (Token::Identifier("b"), 2..3),
+                (Token::Less, 3..4),
+                (Token::Identifier("c"), 4..5),
+                (Token::Greater, 5..6),
+                (Token::Greater, 6..7),
+                (Token::Identifier("10"), 8..10),
+                (Token::Suffix("ms"), 10..12),
+            ]
+        );
+    }
+
+    #[test]
+    fn value_many_overflow() {
+        const RULE: Rule<'static, usize> = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::ValueMany(&Rule::Chars(|c| c == '>'), |value, emit| {
+                for (index, _) in value.char_indices() {
+                    emit(index..index + 1, index);
+                }
+            }),
+        ]);
+
+        let mut lexer = Lexer::with_buffer(RULE, ">>>> >>>>>\n");
+
+        assert!(lexer.by_ref().take(4).all(|token| token.is_ok()));
+        assert_eq!(lexer.span(), 3..4);
+        assert_eq!(lexer.next(), Some(Err(Error::TooManyTokens { at: 5 })));
+        assert_eq!(lexer.offset(), 5);
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn value_many_indentation() {
+        #[derive(Debug, PartialEq)]
+        enum Token<'a> {
+            Identifier(&'a str),
+            Newline,
+            Indent(usize),
+        }
+
+        const RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+            Rule::ValueMany(
+                &Rule::Seq(&[Rule::Literal("\n"), Rule::WhitespaceOf(" ")]),
+                |value, emit| {
+                    emit(0..1, Token::Newline);
+
+                    if value.len() > 1 {
+                        emit(1..value.len(), Token::Indent(value.len() - 1));
+                    }
+                },
+            ),
+            Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+        ]);
+
+        let mut lexer = Lexer::with_buffer(RULE, "if\n    x\ny");
+        let mut steps = Vec::new();
+
+        while let Some(token) = lexer.next() {
+            steps.push((token.unwrap(), lexer.span(), lexer.offset()));
+        }
+
+        // Queued tokens come out one step at a time, each with its piece of
+        // the match, without consuming more input.
+        assert_eq!(
+            steps,
+            [
+                (Token::Identifier("if"), 0..2, 2),
+                (Token::Newline, 2..3, 7),
+                (Token::Indent(4), 3..7, 7),
+                (Token::Identifier("x"), 7..8, 8),
+                (Token::Newline, 8..9, 9),
+                (Token::Identifier("y"), 9..10, 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn map_tokens() {
+        #[derive(Debug, PartialEq)]
+        enum Token<'a> {
+            Comment,
+            Keyword(&'a str),
+            Identifier(&'a str),
+        }
+
+        let mut identifiers = 0;
+        let mut ranges = Vec::new();
+        let mut map = |token, range| {
+            ranges.push(range);
+
+            match token {
+                Token::Comment => None,
+                Token::Keyword("end") => Some(Token::Identifier("end")),
+                Token::Identifier(_) => {
+                    identifiers += 1;
+                    Some(token)
+                }
+                token => Some(token),
+            }
+        };
+
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Literal("#"), &|_| Token::Comment),
+                Rule::Value(&Rule::Literals(&["do", "end"]), &Token::Keyword),
+                Rule::Value(&Rule::Numeric, &Token::Identifier),
+            ]),
+            "do # 1 end ",
+        )
+        .map_tokens(&mut map)
+        .flatten()
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Keyword("do"),
+                Token::Identifier("1"),
+                Token::Identifier("end")
+            ]
+        );
+        assert_eq!(identifiers, 1);
+        assert_eq!(ranges, [0..2, 3..4, 5..6, 7..10]);
+    }
+
+    #[test]
+    fn capturing_callbacks() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        };
+
+        #[derive(Debug, PartialEq)]
+        enum Token {
+            Symbol(usize),
+            Number,
+        }
+
+        let numbers = AtomicUsize::new(0);
+        let symbols = Mutex::new(Vec::new());
+
+        let intern = |name| {
+            let mut symbols = symbols.lock().unwrap();
+            let index = symbols.iter().position(|symbol| *symbol == name);
+
+            Token::Symbol(index.unwrap_or_else(|| {
+                symbols.push(name);
+                symbols.len() - 1
+            }))
+        };
+        let count = |_| {
+            numbers.fetch_add(1, Ordering::Relaxed);
+            Token::Number
+        };
+
+        let rules = [
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Alphabetic, &intern),
+            Rule::Value(&Rule::Numeric, &count),
+        ];
+        let tokens = Lexer::with_buffer(Rule::Any(&rules), "a bc 1 a 22 d")
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Symbol(0),
+                Token::Symbol(1),
+                Token::Number,
+                Token::Symbol(0),
+                Token::Number,
+                Token::Symbol(2)
+            ]
+        );
+        assert_eq!(numbers.load(Ordering::Relaxed), 2);
+        assert_eq!(*symbols.lock().unwrap(), ["a", "bc", "d"]);
+    }
+
+    #[test]
+    fn trivia() {
+        let input = "local x --[[ note ]]\n  end";
+        let mut trivia = Vec::new();
+        let mut handler = |text: &str, range: Range<usize>| {
+            assert_eq!(text, &input[range.clone()]);
+            trivia.push(range);
+        };
+
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Ignore(&Rule::BlockComment {
+                    open: "--[[",
+                    close: "]]",
+                    nested: false,
+                }),
+                Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+            ]),
+            input,
+        )
+        .with_trivia(&mut handler)
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Identifier("local"),
+                Token::Identifier("x"),
+                Token::Identifier("end")
+            ]
+        );
+        assert_eq!(trivia, [5..6, 7..8, 8..20, 20..23]);
+    }
+
+    #[test]
+    fn trace() {
+        static TRACE: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+            ]),
+            "ab c",
+        )
+        .with_trace(|data, range| {
+            TRACE
+                .lock()
+                .unwrap()
+                .push(format!("Data: `{data}` at {range:?}"));
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+
+        assert_eq!(tokens, [Token::Identifier("ab"), Token::Identifier("c")]);
+        assert_eq!(
+            TRACE.lock().unwrap()[..4],
+            [
+                "Data: `a` at 0..1",
+                "Data: `ab` at 0..2",
+                "Data: `ab ` at 0..3",
+                "Data: ` ` at 2..3",
+            ]
+        );
+    }
+
+    #[test]
+    fn value_filter() {
+        #[derive(Debug, PartialEq)]
+        enum Token<'a> {
+            Identifier(&'a str),
+            TooLong(&'a str),
+        }
+
+        const IDENT: Rule<'static, Token<'static>> = Rule::IdentifierOrKeyword {
+            ident: &Rule::Alphabetic,
+            keywords: &[],
+            fallback: Token::TooLong,
+        };
+
+        fn short(value: &str) -> Option<Token<'_>> {
+            (value.len() <= 8).then_some(Token::Identifier(value))
+        }
+
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::ValueFilter(&IDENT, short),
+                IDENT,
+            ]),
+            "short identifier\n",
+        )
+        .flatten()
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [Token::Identifier("short"), Token::TooLong("identifier")]
+        );
+
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::ValueFilter(&IDENT, short),
+            ]),
+            "short identifier\n",
+        );
+
+        let tokens = lexer.by_ref().flatten().collect::<Vec<_>>();
+
+        assert_eq!(tokens, [Token::Identifier("short")]);
+        assert_eq!(lexer.consumed_bytes(), 6);
+    }
+
+    #[test]
+    fn except() {
+        const WORD: Rule<'static, Token<'static>> =
+            Rule::Except(&Rule::Alphabetic, &Rule::Literal("end"));
+
+        assert!(WORD.matches("ending").is_match());
+        assert!(WORD.matches("en").is_match());
+        assert!(WORD.matches("end").is_none());
+
+        let lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&WORD, &Token::Identifier),
+                Rule::Value(&Rule::Literal("end"), &|_| Token::End),
+            ]),
+            "ending end\n",
+        );
+
+        assert_eq!(
+            lexer.flatten().collect::<Vec<_>>(),
+            [Token::Identifier("ending"), Token::End]
+        );
+    }
+
+    #[test]
+    fn commit() {
+        #[derive(Debug, PartialEq)]
+        enum Token<'a> {
+            String(&'a str),
+            Identifier(&'a str),
+        }
+
+        // Strings end on the line they start on, and anything else that isn't
+        // whitespace is an identifier, including a lone quote.
+        let string = Rule::Value(
+            &Rule::Seq(&[
+                Rule::Literal("\""),
+                Rule::Chars(|c| c != '"' && c != '\n'),
+                Rule::Literal("\""),
+            ]),
+            &Token::String,
+        );
+        let identifier = Rule::Value(&Rule::Chars(|c| !c.is_whitespace()), &Token::Identifier);
+
+        let committed = [
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Commit {
+                tag: "string",
+                prefix: &Rule::Literal("\""),
+                rule: &string,
+            },
+            identifier.clone(),
+        ];
+        let mut lexer = Lexer::with_buffer(Rule::Any(&committed), "\"ok\" \"open\nx");
+        assert_eq!(lexer.next(), Some(Ok(Token::String("\"ok\""))));
+
+        let error = lexer.next().unwrap().unwrap_err();
+        assert_eq!(
+            error,
+            Error::Committed {
+                at: 5,
+                rule: 1,
+                tag: Some("string"),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid string at byte 5: alternative 1 failed after committing"
+        );
+
+        // Nothing of the failed string is left to commit.
+        assert_eq!(lexer.index, (5, 5));
+        assert!(lexer.matched.is_none());
+
+        // Without the commit, the quote falls back to an identifier.
+        let plain = [Rule::Ignore(&Rule::Whitespace), string.clone(), identifier];
+        let fallback = Lexer::with_buffer(Rule::Any(&plain), "\"open\nx");
+        assert_eq!(
+            fallback.flatten().collect::<Vec<_>>(),
+            [Token::Identifier("\"open"), Token::Identifier("x")]
+        );
+    }
+
+    #[test]
+    fn at_line_start() {
+        #[derive(Debug, PartialEq)]
+        enum Token<'a> {
+            Directive,
+            Hash,
+            Identifier(&'a str),
+        }
+
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::AtLineStart(&Rule::Value(&Rule::Literal("#define"), &|_| {
+                    Token::Directive
+                })),
+                Rule::Value(&Rule::Literal("#"), &|_| Token::Hash),
+                Rule::IdentifierOrKeyword {
+                    ident: &Rule::Alphabetic,
+                    keywords: &[],
+                    fallback: Token::Identifier,
+                },
+            ]),
+            "#define x\nx#define\n",
+        )
+        .flatten()
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Directive,
+                Token::Identifier("x"),
+                Token::Identifier("x"),
+                Token::Hash,
+                Token::Identifier("define")
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated() {
+        fn lex(input: &str) -> Result<'_, Option<()>> {
+            let mut lexer = Lexer::with_buffer(
+                Rule::Any(&[
+                    Rule::Ignore(&Rule::Whitespace),
+                    Rule::Ignore(&Rule::BlockComment {
+                        open: "/*",
+                        close: "*/",
+                        nested: true,
+                    }),
+                    Rule::Value(
+                        &Rule::Quoted {
+                            quote: '"',
+                            escape: '\\',
+                        },
+                        &|_| (),
+                    ),
+                ]),
+                input,
+            );
+
+            loop {
+                match lexer.step() {
+                    Ok(_) => {}
+                    result => return result,
+                }
+            }
+        }
+
+        assert!(matches!(lex("/* a */ \"b\" \n"), Err(Error::Eof)));
+        assert!(matches!(
+            lex("\"a\" \"b\\\" c\n"),
+            Err(Error::Unterminated { open, eof: 11 }) if open == (4..5)
+        ));
+        assert!(matches!(
+            lex(" /* a /* b */ c\n"),
+            Err(Error::Unterminated { open, eof: 16 }) if open == (1..3)
+        ));
+    }
+
+    #[test]
+    fn line_comment() {
+        let ends: Rule<Token> = Rule::EndsWith("\n");
+        assert!(ends.matches("-- a").is_partial_match());
+        assert!(ends.matches("-- a\n").is_match());
+        assert!(ends.matches("-- a\nb\n").is_none());
+
+        let starts: Rule<Token> = Rule::StartsWith("--");
+        assert!(starts.matches("-").is_partial_match());
+        assert!(starts.matches("-- a").is_match());
+        assert!(starts.matches("-+").is_none());
+        assert!(Rule::<Token>::Both(&starts, &ends)
+            .matches("-- a\n")
+            .is_match());
+
+        let mut trivia = Vec::new();
+        let mut handler = |_: &str, range| trivia.push(range);
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Ignore(&Rule::Seq(&[Rule::Literal("--"), Rule::EndsWith("\n")])),
+                Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+            ]),
+            "-- a comment\n x",
+        )
+        .with_trivia(&mut handler)
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+
+        assert_eq!(tokens, [Token::Identifier("x")]);
+        assert_eq!(trivia, [0..13, 13..14]);
+    }
+
+    #[test]
+    fn comments() {
+        fn lex(
+            comment: &'static Rule<'static, &'static str>,
+            input: &'static str,
+        ) -> Vec<&'static str> {
+            let rules = Vec::from([
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(comment, &|value| value),
+                Rule::Value(&Rule::Alphabetic, &|value| value),
+            ]);
+
+            Lexer::with_buffer(Rule::Any(rules.leak()), input)
+                .map(|token| token.unwrap())
+                .collect()
+        }
+
+        let line: Rule<Token> = Rule::LineComment("--");
+        assert!(line.matches("-").is_partial_match());
+        assert!(line.matches("--").is_match());
+        assert!(line.matches("-- a").is_match());
+        assert!(line.matches("-- a\n").is_none());
+        assert!(line.matches("-+").is_none());
+
+        assert_eq!(
+            lex(&Rule::LineComment("--"), "x -- a -- b\r\ny\n-- last"),
+            ["x", "-- a -- b\r", "y", "-- last"]
+        );
+
+        let nested = &Rule::BlockComment {
+            open: "/*",
+            close: "*/",
+            nested: true,
+        };
+        assert_eq!(
+            lex(nested, "a /* one /* two */ still one */ b"),
+            ["a", "/* one /* two */ still one */", "b"]
+        );
+
+        let flat = &Rule::BlockComment {
+            open: "/*",
+            close: "*/",
+            nested: false,
+        };
+        assert_eq!(
+            lex(flat, "a /* one /* two */ b"),
+            ["a", "/* one /* two */", "b"]
+        );
+
+        let lua = &Rule::BlockComment {
+            open: "--[[",
+            close: "]]",
+            nested: true,
+        };
+        assert_eq!(
+            lex(lua, "--[[ a --[[ b ]] ]]c"),
+            ["--[[ a --[[ b ]] ]]", "c"]
+        );
+    }
+
+    #[test]
+    fn delimited() {
+        const STRING: Rule<'static, Token<'static>> = Rule::Delimited {
+            open: "\"",
+            close: "\"",
+            escape: Some('\\'),
+        };
+        const COMMENT: Rule<'static, Token<'static>> = Rule::Delimited {
+            open: "--[[",
+            close: "]]",
+            escape: None,
+        };
+
+        assert!(STRING.matches("\"\"").is_match());
+        assert!(STRING.matches("\"a \\\" b\"").is_match());
+        assert!(STRING.matches("\"a \\\"").is_partial_match());
+        assert!(STRING.matches("\"a\" b").is_none());
+        assert!(COMMENT.matches("--").is_partial_match());
+        assert!(COMMENT.matches("--[[ a ]]").is_match());
+        assert!(COMMENT.matches("--[[ a ] ]").is_partial_match());
+
+        let input = "\"say \\\"hi\\\"\" --[[ note ]] \"\"";
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Ignore(&COMMENT),
+                Rule::Value(&STRING, &Token::Identifier),
+            ]),
+            input,
+        );
+
+        assert_eq!(
+            lexer.by_ref().collect::<Result<Vec<_>>>().unwrap(),
+            [
+                Token::Identifier("\"say \\\"hi\\\"\""),
+                Token::Identifier("\"\"")
+            ]
+        );
+
+        lexer.reset("\"ok\" \"open \\\"");
+        assert!(matches!(
+            lexer.next(),
+            Some(Ok(Token::Identifier("\"ok\"")))
+        ));
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(Error::Unterminated { open, eof: 13 })) if open == (5..6)
+        ));
+    }
+
+    #[test]
+    fn embed() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Debug, PartialEq)]
+        enum Sql<'a> {
+            Keyword(&'a str),
+            Name(&'a str),
+            Close,
+        }
+
+        #[derive(Debug, PartialEq)]
+        enum Token<'a> {
+            Text(&'a str),
+            Query(Vec<(Sql<'a>, Range<usize>)>),
+        }
+
+        fn query(buffer: &str, open: Range<usize>) -> (Token<'_>, usize) {
+            let mut tokens = Vec::new();
+            let mut lexer = Lexer::with_buffer(
+                Rule::Any(&[
+                    Rule::Ignore(&Rule::Whitespace),
+                    Rule::Value(&Rule::Literal("}}"), &|_| Sql::Close),
+                    Rule::IdentifierOrKeyword {
+                        ident: &Rule::Alphabetic,
+                        keywords: &[
+                            ("from", || Sql::Keyword("from")),
+                            ("select", || Sql::Keyword("select")),
+                        ],
+                        fallback: Sql::Name,
+                    },
+                ]),
+                &buffer[open.end..],
+            );
+
+            while let Some(token) = lexer.by_ref().flatten().next() {
+                if token == Sql::Close {
+                    break;
+                }
+
+                let end = open.end + lexer.consumed_bytes();
+                let start = end - token_len(&token);
+                tokens.push((token, start..end));
+            }
+
+            (Token::Query(tokens), lexer.consumed_bytes())
+        }
+
+        fn token_len(token: &Sql) -> usize {
+            match token {
+                Sql::Keyword(text) | Sql::Name(text) => text.len(),
+                Sql::Close => 2,
+            }
+        }
+
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Embed {
+                    open: &Rule::Literal("{{"),
+                    lex: query,
+                },
+                Rule::IdentifierOrKeyword {
+                    ident: &Rule::Alphabetic,
+                    keywords: &[],
+                    fallback: Token::Text,
+                },
+            ]),
+            "hi {{ select name from users }} bye\n",
+        )
+        .flatten()
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Text("hi"),
+                Token::Query(Vec::from([
+                    (Sql::Keyword("select"), 6..12),
+                    (Sql::Name("name"), 13..17),
+                    (Sql::Keyword("from"), 18..22),
+                    (Sql::Name("users"), 23..28),
+                ])),
+                Token::Text("bye"),
+            ]
+        );
+
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        fn raw(buffer: &str, open: Range<usize>) -> (Token<'_>, usize) {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+
+            let len = buffer[open.end..].find("}}").map_or(0, |index| index + 2);
+            (Token::Text(&buffer[open.start..open.end + len]), len)
+        }
+
+        let region = ["{{", &"x".repeat(10_000), "}}"].concat();
+        let input = region.clone() + " bye\n";
+        let tokens = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Embed {
+                    open: &Rule::Literal("{{"),
+                    lex: raw,
+                },
+                Rule::Value(&Rule::Alphabetic, &Token::Text),
+            ]),
+            &input,
+        )
+        .flatten()
+        .collect::<Vec<_>>();
+
+        assert_eq!(tokens, [Token::Text(&region), Token::Text("bye")]);
+        // The lexer skips over the region instead of calling `lex` for every
+        // byte of it.
+        assert!(CALLS.load(Ordering::Relaxed) < 10);
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<X: Send + Sync>() {}
+
+        assert_send_sync::<Lexer<'static, Token<'static>>>();
+        assert_send_sync::<Rule<'static, Token<'static>>>();
+        assert_send_sync::<MatchResult<Token<'static>>>();
+        assert_send_sync::<PrefixResult<Token<'static>>>();
+        assert_send_sync::<Context<'static>>();
+        assert_send_sync::<Error<'static>>();
+        #[cfg(feature = "alloc")]
+        assert_send_sync::<OwnedRule<'static, Token<'static>>>();
+    }
+
+    #[test]
+    fn match_result_eq() {
+        let rule = Rule::Value(&Rule::Literal("and"), &|_| Token::And);
+
+        assert_eq!(rule.matches("and"), MatchResult::Match(Some(Token::And)));
+        assert_eq!(rule.matches("an"), MatchResult::PartialMatch);
+        assert_ne!(rule.matches("and"), MatchResult::Match(None));
+        assert_eq!(
+            rule.match_prefix("and)"),
+            PrefixResult::Match {
+                len: 3,
+                token: Some(Token::And)
+            }
+        );
+
+        let result = MatchResult::<()>::Cut;
+        let copy = result;
+        assert_eq!(result, copy);
+    }
+
+    #[test]
+    fn clone_rules() {
+        const RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::CharRange('a'..='z'), &Token::Identifier),
+        ]);
+
+        let rule = RULE;
+        let table = [rule.clone(), Rule::Keywords(&[("and", || Token::And)])];
+
+        assert_eq!(table[0], rule);
+        assert_eq!(table[1].clone(), table[1]);
+
+        let tokens = std::thread::scope(|scope| {
+            let lexers = ["one two\n", "three\n"].map(|input| {
+                let rule = rule.clone();
+
+                scope.spawn(move || Lexer::with_buffer(rule, input).flatten().count())
+            });
+
+            lexers.map(|lexer| lexer.join().unwrap())
+        });
+
+        assert_eq!(tokens, [2, 1]);
+    }
+
+    #[test]
+    fn cross_thread() {
+        let input = "local android end\n";
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        sender.send(Lexer::with_buffer(lua(), input)).unwrap();
+
+        let tokens = std::thread::scope(|scope| {
+            scope
+                .spawn(move || receiver.recv().unwrap().flatten().collect::<Vec<_>>())
+                .join()
+                .unwrap()
+        });
+
+        assert_eq!(
+            tokens,
+            [Token::Local, Token::Identifier("android"), Token::End]
+        );
+    }
+
+    #[test]
+    fn non_debug_tokens() {
+        struct Handle(usize);
+
+        struct Symbol(Handle);
+
+        const RULE: Rule<'static, Symbol> = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Alphabetic, &|value| Symbol(Handle(value.len()))),
+        ]);
+
+        let lengths = Lexer::with_buffer(RULE, "intern these words\n")
+            .flatten()
+            .map(|Symbol(Handle(len))| len)
+            .collect::<Vec<_>>();
+
+        assert_eq!(lengths, [6, 5, 5]);
+        assert!(RULE.matches("words").is_match());
+        assert!(RULE.match_prefix("words!").is_match());
+
+        let significant = Lexer::with_buffer(RULE, "a bc def\n")
+            .filtered(|Symbol(Handle(len))| *len > 1)
+            .count();
+        assert_eq!(significant, 2);
+
+        #[cfg(feature = "alloc")]
+        assert_eq!(crate::tokenize(RULE, "a @ b\n").tokens.len(), 2);
+    }
+
+    #[test]
+    fn ignore_discards_tokens() {
+        let rule = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Ignore(&Rule::Literal(";")),
+            Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+        ]);
+
+        assert!(matches!(
+            Rule::Ignore(&Rule::Value(&Rule::Literal(";"), &|_| Token::End)).matches(";"),
+            MatchResult::Match(None)
+        ));
+
+        let tokens = Lexer::with_buffer(rule, "a; b;\n")
+            .flatten()
+            .collect::<Vec<_>>();
+
+        assert_eq!(tokens, [Token::Identifier("a"), Token::Identifier("b")]);
+
+        #[cfg(feature = "alloc")]
+        {
+            let owned = OwnedRule::Ignore(Box::new(OwnedRule::Value(
+                Box::new(OwnedRule::Rule(&Rule::Alphabetic)),
+                &Token::Identifier,
+            )));
+
+            assert!(matches!(owned.matches("a"), MatchResult::Match(None)));
+        }
+    }
+
+    #[test]
+    fn expect_next() {
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Literal("end"), &|_| Token::End),
+                Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+            ]),
+            "local\n  end x\n",
+        );
+        let identifier = |token: &Token| matches!(token, Token::Identifier(_));
+
+        let token = lexer.expect_next(identifier, "identifier").unwrap();
+        assert!(token.eq_with_span(&Spanned::new(Token::Identifier("local"), 0..5)));
+
+        let error = lexer.expect_next(identifier, "identifier").unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Unexpected {
+                expected: "identifier",
+                found: Some("end"),
+                ref span,
+                line: 2,
+                column: 3,
+            } if *span == (8..11)
+        ));
+        assert_eq!(error.to_string(), "expected identifier, found `end` at 2:3");
+
+        assert!(matches!(lexer.step(), Ok(Some(Token::End))));
+        assert!(lexer.expect_next(identifier, "identifier").is_ok());
+
+        let error = lexer.expect_next(identifier, "identifier").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected identifier, found end of input at 3:1"
+        );
+    }
+
+    #[test]
+    fn text() {
+        let mut lexer = Lexer::with_buffer(FIND_RULE, "héllo wörld\n");
+        let mut spans = Vec::new();
+
+        while let Some(token) = lexer.by_ref().flatten().next() {
+            assert_eq!(token, Token::Identifier(lexer.text(lexer.span())));
+            spans.push(lexer.span());
+        }
+
+        assert_eq!(spans, [0..6, 7..13]);
+        assert_eq!(lexer.text(spans[1].clone()), "wörld");
+        assert_eq!(lexer.text(0..spans[1].end), "héllo wörld");
+        assert_eq!(lexer.text(13..13), "");
+    }
+
+    #[test]
+    fn find_from() {
+        let lexer = Lexer::with_buffer(FIND_RULE, "append end");
+
+        assert_eq!(lexer.find_from(0, &Rule::Literal("end")), Some(3..6));
+        assert_eq!(lexer.find_from(4, &Rule::Literal("end")), Some(7..10));
+        assert_eq!(lexer.find_from(8, &Rule::Literal("end")), None);
+        assert_eq!(lexer.find_from(0, &Rule::Whitespace), Some(6..7));
+    }
+
+    #[test]
+    fn find_from_respects_char_boundaries() {
+        let lexer = Lexer::with_buffer(FIND_RULE, "πend");
+
+        assert_eq!(lexer.find_from(1, &Rule::Literal("end")), Some(2..5));
+        assert_eq!(lexer.find_from(0, &Rule::Alphabetic), Some(0..5));
+    }
+
+    #[test]
+    fn find_token_from() {
+        let lexer = Lexer::with_buffer(FIND_RULE, "append end");
+
+        assert_eq!(lexer.find_token_from(0, &Rule::Literal("end")), Some(7..10));
+        assert_eq!(lexer.find_token_from(0, &Rule::Alphabetic), Some(0..6));
+        assert_eq!(lexer.find_token_from(8, &Rule::Literal("end")), None);
     }
 }
@@ -0,0 +1,300 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{rule, Context, MatchResult, Rule};
+
+/// Matches a rule against a slice of a buffer that grows one character at a
+/// time, as returned by [`Rule::matcher`].
+///
+/// Literals and character classes remember whether the slice still matches
+/// them, so each character costs time proportional to the size of the rule
+/// rather than to the length of the slice. Composites of them, such as
+/// `Value`, `Both` or `Any`, combine the remembered results, and any other
+/// rule is evaluated against the whole slice as usual. Every result is the
+/// one [`Rule::matches_in`] gives for the slice.
+///
+/// The lexer keeps one across the steps of a token, see [`Lexer::step`](crate::Lexer::step).
+pub struct Matcher<'a, T> {
+    buffer: &'a str,
+    start: usize,
+    end: usize,
+    state: State<'a, T>,
+}
+
+enum State<'a, T> {
+    /// A literal, and whether the slice is still a prefix of it.
+    Literal(&'a str, bool),
+    /// A character class, and whether every character so far belongs to it.
+    Class(Rule<'a, T>, bool),
+    /// A rule combining the result of a single rule.
+    Unary(Rule<'a, T>, Box<State<'a, T>>),
+    /// A rule combining the results of two rules.
+    Binary(Rule<'a, T>, Box<[State<'a, T>; 2]>),
+    /// A rule combining the results of a list of rules.
+    List(Rule<'a, T>, Vec<State<'a, T>>),
+    /// Any other rule, evaluated against the whole slice.
+    Whole(Rule<'a, T>),
+}
+
+impl<'a, T> Rule<'a, T> {
+    /// Returns a [`Matcher`] for the slices of `buffer` starting at `start`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is out of bounds or not on a character boundary.
+    pub fn matcher(&self, buffer: &'a str, start: usize) -> Matcher<'a, T> {
+        assert!(buffer.is_char_boundary(start), "invalid matcher start");
+
+        Matcher {
+            buffer,
+            start,
+            end: start,
+            state: State::new(self),
+        }
+    }
+}
+
+impl<'a, T> Matcher<'a, T> {
+    /// Grows the slice of `buffer` starting at `start` to end at `end`, for
+    /// the lexer, which picks the ends itself. Starts over if the slice
+    /// doesn't lead up to `end`.
+    pub(crate) fn grow(&mut self, buffer: &'a str, start: usize, end: usize) {
+        if !core::ptr::eq(self.buffer, buffer) || self.start != start || self.end > end {
+            self.buffer = buffer;
+            self.start = start;
+            self.end = start;
+            self.state.reset();
+        }
+
+        if self.end < end {
+            self.state
+                .push(self.end - self.start, &self.buffer[self.end..end]);
+            self.end = end;
+        }
+    }
+
+    /// Returns the result for the slice, in the given context for it.
+    pub(crate) fn result(&self, cx: Context<'a>) -> MatchResult<T> {
+        self.state.result(cx)
+    }
+}
+
+impl<'a, T> Iterator for Matcher<'a, T> {
+    /// The end of the slice, after adding one more character, and its result.
+    type Item = (usize, MatchResult<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let c = self.buffer[self.end..].chars().next()?;
+        let end = self.end + c.len_utf8();
+
+        self.state
+            .push(self.end - self.start, &self.buffer[self.end..end]);
+        self.end = end;
+
+        let cx = Context::new(self.buffer, self.start..end);
+
+        Some((end, self.state.result(cx)))
+    }
+}
+
+impl<'a, T> State<'a, T> {
+    fn new(rule: &Rule<'a, T>) -> Self {
+        match rule {
+            Rule::Literal(literal) => Self::Literal(literal, true),
+            Rule::Numeric
+            | Rule::Alphabetic
+            | Rule::Whitespace
+            | Rule::WhitespaceOf(_)
+            | Rule::Chars(_)
+            | Rule::CharRange(_) => Self::Class(rule.clone(), true),
+            Rule::Value(inner, _)
+            | Rule::Ignore(inner)
+            | Rule::Not(inner)
+            | Rule::Only(inner)
+            | Rule::Priority(_, inner) => Self::Unary(rule.clone(), Box::new(Self::new(inner))),
+            Rule::Both(a, b) | Rule::Either(a, b) => {
+                Self::Binary(rule.clone(), Box::new([Self::new(a), Self::new(b)]))
+            }
+            Rule::All(rules, _) | Rule::Any(rules) => {
+                Self::List(rule.clone(), rules.iter().map(Self::new).collect())
+            }
+            _ => Self::Whole(rule.clone()),
+        }
+    }
+
+    /// Forgets every character added, as for an empty slice.
+    fn reset(&mut self) {
+        match self {
+            Self::Literal(_, prefix) => *prefix = true,
+            Self::Class(_, member) => *member = true,
+            Self::Unary(_, state) => state.reset(),
+            Self::Binary(_, states) => states.iter_mut().for_each(Self::reset),
+            Self::List(_, states) => states.iter_mut().for_each(Self::reset),
+            Self::Whole(_) => {}
+        }
+    }
+
+    /// Adds the character `c`, found `offset` bytes into the slice.
+    fn push(&mut self, offset: usize, c: &'a str) {
+        match self {
+            Self::Literal(literal, prefix) => {
+                *prefix = *prefix
+                    && literal
+                        .get(offset..)
+                        .is_some_and(|rest| rest.starts_with(c));
+            }
+            Self::Class(rule, member) => {
+                *member = *member && rule.eval(Context::from(c)).is_match();
+            }
+            Self::Unary(_, state) => state.push(offset, c),
+            Self::Binary(_, states) => states.iter_mut().for_each(|state| state.push(offset, c)),
+            Self::List(_, states) => states.iter_mut().for_each(|state| state.push(offset, c)),
+            Self::Whole(_) => {}
+        }
+    }
+
+    /// Returns the result for the slice of `cx`, which isn't empty.
+    fn result(&self, cx: Context<'a>) -> MatchResult<T> {
+        match self {
+            Self::Literal(literal, prefix) => match (prefix, cx.value().len() == literal.len()) {
+                (true, true) => MatchResult::Match(None),
+                (true, false) => MatchResult::PartialMatch,
+                (false, _) => MatchResult::None,
+            },
+            Self::Class(_, true) => MatchResult::Match(None),
+            Self::Class(_, false) => MatchResult::None,
+            Self::Unary(rule, state) => match rule {
+                Rule::Value(_, out) => rule::value(state.result(cx), *out, cx),
+                Rule::Ignore(_) => rule::ignore(state.result(cx.probe())),
+                Rule::Not(_) => rule::not(state.result(cx.probe())),
+                _ => state.result(cx),
+            },
+            Self::Binary(rule, states) => {
+                let [a, b] = &**states;
+                let b = || b.result(cx.probe());
+
+                match rule {
+                    Rule::Both(..) => rule::both(a.result(cx.probe()), b),
+                    _ => rule::either(a.result(cx.probe()), b),
+                }
+            }
+            Self::List(rule, states) => match rule {
+                Rule::All(_, out) => rule::all(
+                    states.iter().map(|state| state.result(cx.probe())),
+                    *out,
+                    cx,
+                ),
+                Rule::Any(rules) => rule::ranked(
+                    rules
+                        .iter()
+                        .zip(states)
+                        .map(|(rule, state)| (rule.priority(), move || state.result(cx))),
+                ),
+                _ => unreachable!(),
+            },
+            Self::Whole(rule) => rule.eval(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        Keyword(&'a str),
+        Word(&'a str),
+        Number,
+        String,
+    }
+
+    fn rule<'a>() -> Rule<'a, Token<'a>> {
+        Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(
+                &Rule::Either(&Rule::Literal("ab"), &Rule::Literal("ba")),
+                &Token::Keyword,
+            ),
+            Rule::Value(
+                &Rule::Both(&Rule::Alphabetic, &Rule::Not(&Rule::Literal("aaa"))),
+                &Token::Word,
+            ),
+            Rule::All(&[Rule::Numeric, Rule::Chars(|c| c < '8')], &|_| {
+                Token::Number
+            }),
+            Rule::Value(
+                &Rule::Quoted {
+                    quote: '"',
+                    escape: '\\',
+                },
+                &|_| Token::String,
+            ),
+            Rule::Only(&Rule::WhitespaceOf("_")),
+            Rule::Priority(1, &Rule::Value(&Rule::Literal("bab"), &Token::Keyword)),
+        ])
+    }
+
+    /// Summarizes a result as its variant and token, to compare them.
+    fn summary<T>(result: MatchResult<T>) -> (&'static str, Option<T>) {
+        match result {
+            MatchResult::None => ("None", None),
+            MatchResult::Match(token) => ("Match", token),
+            MatchResult::PartialMatch => ("PartialMatch", None),
+            MatchResult::Cut => ("Cut", None),
+        }
+    }
+
+    #[test]
+    fn agrees_with_matches() {
+        const ALPHABET: [char; 10] = ['a', 'b', 'é', '7', '9', ' ', '\n', '_', '"', '\\'];
+
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut inputs = Vec::new();
+
+        for _ in 0..500 {
+            let mut input = alloc::string::String::new();
+
+            for _ in 0..12 {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                input.push(ALPHABET[(seed % ALPHABET.len() as u64) as usize]);
+            }
+
+            inputs.push(input);
+        }
+
+        for input in &inputs {
+            let rule = rule();
+
+            for (start, _) in input.char_indices() {
+                for (end, result) in rule.matcher(input, start) {
+                    let expected = rule.matches_in(Context::new(input, start..end));
+
+                    assert_eq!(
+                        summary(result),
+                        summary(expected),
+                        "{:?}",
+                        &input[start..end]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ends() {
+        let rule = rule();
+        let mut matcher = rule.matcher("x ab", 2);
+
+        assert!(matches!(
+            matcher.next(),
+            Some((3, MatchResult::Match(Some(Token::Word("a")))))
+        ));
+        assert!(matches!(
+            matcher.next(),
+            Some((4, MatchResult::Match(Some(Token::Keyword("ab")))))
+        ));
+        assert!(matcher.next().is_none());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{Lexer, Rule};
+
+/// The maximum number of modes that can be pushed on top of each other.
+pub const MODE_DEPTH_LIMIT: usize = 16;
+
+/// A named rule set for [`Lexer::with_modes`], lexing the text while its
+/// mode is active.
+pub type Mode<'a, T> = (&'a str, Rule<'a, T>);
+
+/// The change of mode carried by a [`Rule::PushMode`] or [`Rule::PopMode`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ModeAction<'a> {
+    Push(&'a str),
+    Pop,
+}
+
+/// The active modes, as indices into the lexer's modes, innermost last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ModeStack {
+    pub(crate) modes: [u8; MODE_DEPTH_LIMIT],
+    pub(crate) len: u8,
+}
+
+impl ModeStack {
+    pub(crate) const fn new() -> Self {
+        Self {
+            modes: [0; MODE_DEPTH_LIMIT],
+            len: 0,
+        }
+    }
+
+    pub(crate) fn top(&self) -> Option<usize> {
+        self.len
+            .checked_sub(1)
+            .map(|top| usize::from(self.modes[usize::from(top)]))
+    }
+
+    pub(crate) const fn is_full(&self) -> bool {
+        self.len as usize == MODE_DEPTH_LIMIT
+    }
+}
+
+impl<'a, T> Lexer<'a, T> {
+    /// Adds modes that [`Rule::PushMode`] can switch to, such as the inside
+    /// of a string literal where the rules for code don't apply. The
+    /// lexer's own rule is the default mode, active while no mode is pushed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 256 modes.
+    pub const fn with_modes(mut self, modes: &'a [Mode<'a, T>]) -> Self {
+        assert!(modes.len() <= 256, "too many modes");
+
+        self.modes = modes;
+        self
+    }
+
+    /// Returns the name of the innermost active mode, or `None` in the
+    /// default mode.
+    pub fn current_mode(&self) -> Option<&'a str> {
+        Some(self.modes[self.mode.top()?].0)
+    }
+
+    /// Returns the rule of the innermost active mode.
+    pub(crate) fn active_rule(&self) -> &Rule<'a, T> {
+        match self.mode.top() {
+            Some(index) => &self.modes[index].1,
+            None => &self.rule,
+        }
+    }
+
+    /// Applies `action` once the slice carrying it is committed. Pushing a
+    /// mode that isn't one of the lexer's modes, or popping the default
+    /// mode, changes nothing.
+    pub(crate) fn switch_mode(&mut self, action: ModeAction<'a>) {
+        match action {
+            ModeAction::Push(name) => {
+                if let Some(index) = self.modes.iter().position(|(mode, _)| *mode == name) {
+                    self.mode.modes[usize::from(self.mode.len)] = index as u8;
+                    self.mode.len += 1;
+                }
+            }
+            ModeAction::Pop => self.mode.len = self.mode.len.saturating_sub(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        If,
+        End,
+        Identifier(&'a str),
+        Quote,
+        Text(&'a str),
+    }
+
+    const CODE: Rule<'static, Token<'static>> = Rule::Any(&[
+        Rule::Ignore(&Rule::Whitespace),
+        Rule::Keywords(&[("end", || Token::End), ("if", || Token::If)]),
+        Rule::Value(&Rule::Alphabetic, &Token::Identifier),
+        Rule::PushMode(
+            &Rule::Value(&Rule::Literal("\""), &|_| Token::Quote),
+            "string",
+        ),
+    ]);
+
+    const MODES: &[Mode<'static, Token<'static>>] = &[(
+        "string",
+        Rule::Any(&[
+            Rule::Value(&Rule::Chars(|c| c != '"'), &Token::Text),
+            Rule::PopMode(&Rule::Value(&Rule::Literal("\""), &|_| Token::Quote)),
+        ]),
+    )];
+
+    #[test]
+    fn string_mode() {
+        let mut lexer = Lexer::with_buffer(CODE, "if x \"if end\" end\n").with_modes(MODES);
+        let mut next = || {
+            let token = lexer.next().unwrap().unwrap();
+            (token, lexer.current_mode())
+        };
+
+        assert_eq!(next(), (Token::If, None));
+        assert_eq!(next(), (Token::Identifier("x"), None));
+        assert_eq!(next(), (Token::Quote, Some("string")));
+        assert_eq!(next(), (Token::Text("if end"), Some("string")));
+        assert_eq!(next(), (Token::Quote, None));
+        assert_eq!(next(), (Token::End, None));
+    }
+
+    #[test]
+    fn rewinds_modes() {
+        let mut lexer = Lexer::with_buffer(CODE, "x \"end\"\n").with_modes(MODES);
+
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Identifier("x"));
+
+        let checkpoint = lexer.checkpoint();
+        lexer.by_ref().take(2).for_each(drop);
+        assert_eq!(lexer.current_mode(), Some("string"));
+
+        lexer.rewind(checkpoint);
+        assert_eq!(lexer.current_mode(), None);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Quote);
+    }
+
+    #[test]
+    fn overflow() {
+        const NESTED: Rule<'static, ()> = Rule::Any(&[
+            Rule::PushMode(&Rule::Literal("("), "parens"),
+            Rule::PopMode(&Rule::Literal(")")),
+        ]);
+        const MODES: &[Mode<'static, ()>] = &[("parens", NESTED)];
+
+        let input = "(".repeat(MODE_DEPTH_LIMIT) + "()" + &")".repeat(MODE_DEPTH_LIMIT);
+        let mut lexer = Lexer::with_buffer(NESTED, input.leak()).with_modes(MODES);
+
+        assert!(matches!(
+            lexer.by_ref().find_map(Result::err),
+            Some(Error::ModeOverflow { at }) if at == MODE_DEPTH_LIMIT
+        ));
+        assert_eq!(lexer.current_mode(), Some("parens"));
+
+        let input = "(".repeat(MODE_DEPTH_LIMIT) + &")".repeat(MODE_DEPTH_LIMIT);
+        let mut lexer = Lexer::with_buffer(NESTED, input.leak()).with_modes(MODES);
+
+        assert!(lexer.by_ref().all(|result| result.is_ok()));
+        assert_eq!(lexer.current_mode(), None);
+    }
+}
@@ -0,0 +1,81 @@
+use core::fmt;
+
+/// Provides a human-readable name for a token, used wherever the crate renders
+/// tokens, such as in the errors of [`Lexer::expect_token`](crate::Lexer::expect_token).
+///
+/// With the `derive` feature, `#[derive(TokenName)]` names each variant after
+/// itself, as `Debug` shows unit variants, so adoption can start there;
+/// `#[name("identifier")]` on a variant then stops leaking Rust variant
+/// names to end users one variant at a time.
+pub trait TokenName {
+    /// Returns the name shown to users, e.g. `"identifier"`.
+    fn name(&self) -> &'static str;
+}
+
+impl<T: TokenName + ?Sized> TokenName for &T {
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+}
+
+/// Displays a token by its [`TokenName`].
+pub struct Named<'t, T: ?Sized>(pub &'t T);
+
+impl<T: TokenName + ?Sized> fmt::Display for Named<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lexer, Rule};
+
+    #[derive(Debug, PartialEq)]
+    enum Token {
+        Identifier,
+        Plus,
+    }
+
+    impl TokenName for Token {
+        fn name(&self) -> &'static str {
+            match self {
+                Self::Identifier => "identifier",
+                Self::Plus => "'+'",
+            }
+        }
+    }
+
+    #[test]
+    fn renders_names() {
+        let message = format!(
+            "expected {}, found {}",
+            Named(&Token::Identifier),
+            Named(&Token::Plus)
+        );
+
+        assert_eq!(message, "expected identifier, found '+'");
+    }
+
+    #[test]
+    fn names_expected_tokens() {
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Alphabetic, &|_| Token::Identifier),
+                Rule::Value(&Rule::Literal("+"), &|_| Token::Plus),
+            ]),
+            "a + +\n",
+        );
+
+        assert_eq!(
+            lexer.expect_token(&Token::Identifier).unwrap().value,
+            Token::Identifier
+        );
+        assert_eq!(lexer.expect_token(&Token::Plus).unwrap().value, Token::Plus);
+
+        let error = lexer.expect_token(&Token::Identifier).unwrap_err();
+        assert_eq!(error.to_string(), "expected identifier, found `+` at 1:5");
+    }
+}
@@ -0,0 +1,141 @@
+use core::ops::Range;
+
+use unicode_normalization::{char, UnicodeNormalization};
+
+/// An NFC-normalized copy of some input, along with a map from byte offsets in
+/// the normalized text back to the original.
+///
+/// Lex [`Normalized::as_str`] so that precomposed and decomposed spellings of
+/// the same text produce identical tokens, then use
+/// [`Normalized::original_range`] to report spans against the original input.
+#[derive(Debug, Clone)]
+pub struct Normalized {
+    text: String,
+    map: Vec<Range<usize>>,
+}
+
+impl Normalized {
+    /// Normalizes `input` to NFC.
+    ///
+    /// The input is normalized in segments made of a starter and everything
+    /// that may combine with it, each segment mapping as a whole onto the
+    /// original range it came from.
+    pub fn new(input: &str) -> Self {
+        let mut text = String::with_capacity(input.len());
+        let mut map = Vec::with_capacity(input.len());
+        let mut start = 0;
+        let mut previous = None;
+
+        for (offset, c) in input.char_indices() {
+            let boundary = char::canonical_combining_class(c) == 0
+                && previous.is_none_or(|p| char::compose(p, c).is_none());
+
+            if boundary && offset > start {
+                Self::push_segment(&mut text, &mut map, input, start..offset);
+                start = offset;
+            }
+
+            previous = Some(c);
+        }
+
+        Self::push_segment(&mut text, &mut map, input, start..input.len());
+
+        Self { text, map }
+    }
+
+    fn push_segment(
+        text: &mut String,
+        map: &mut Vec<Range<usize>>,
+        input: &str,
+        segment: Range<usize>,
+    ) {
+        text.extend(input[segment.clone()].nfc());
+        map.resize(text.len(), segment);
+    }
+
+    /// Returns the normalized text.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Maps a byte range of the normalized text back onto the original input,
+    /// widening it to whole segments where normalization merged characters.
+    pub fn original_range(&self, span: Range<usize>) -> Range<usize> {
+        let start = self
+            .map
+            .get(span.start)
+            .map_or_else(|| self.original_len(), |segment| segment.start);
+
+        if span.end <= span.start {
+            return start..start;
+        }
+
+        let end = self
+            .map
+            .get(span.end - 1)
+            .map_or_else(|| self.original_len(), |segment| segment.end);
+
+        start..end
+    }
+
+    fn original_len(&self) -> usize {
+        self.map.last().map_or(0, |segment| segment.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[derive(Debug)]
+    struct Identifier;
+
+    fn identifiers(normalized: &Normalized) -> Vec<(&str, core::ops::Range<usize>)> {
+        let lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Alphabetic, &|_| Identifier),
+            ]),
+            normalized.as_str(),
+        );
+        let mut start = 0;
+        let mut found = Vec::new();
+
+        while let Some(span) = lexer.find_token_from(start, &Rule::Alphabetic) {
+            start = span.end;
+            found.push((
+                &normalized.as_str()[span.clone()],
+                normalized.original_range(span),
+            ));
+        }
+
+        found
+    }
+
+    #[test]
+    fn both_encodings_lex_identically() {
+        let composed = Normalized::new("x caf\u{e9} y");
+        let decomposed = Normalized::new("x cafe\u{301} y");
+
+        assert_eq!(composed.as_str(), decomposed.as_str());
+        assert_eq!(
+            identifiers(&composed),
+            [("x", 0..1), ("caf\u{e9}", 2..7), ("y", 8..9)]
+        );
+        assert_eq!(
+            identifiers(&decomposed),
+            [("x", 0..1), ("caf\u{e9}", 2..8), ("y", 9..10)]
+        );
+    }
+
+    #[test]
+    fn original_range() {
+        let normalized = Normalized::new("e\u{301}\u{301}!");
+
+        assert_eq!(normalized.as_str(), "\u{e9}\u{301}!");
+        assert_eq!(normalized.original_range(0..2), 0..5);
+        assert_eq!(normalized.original_range(0..4), 0..5);
+        assert_eq!(normalized.original_range(4..5), 5..6);
+        assert_eq!(normalized.original_range(5..5), 6..6);
+    }
+}
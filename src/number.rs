@@ -0,0 +1,229 @@
+use crate::MatchResult;
+
+/// Matches a run of ASCII digits, partially matching the empty slice.
+pub(crate) fn integer<T>(value: &str) -> MatchResult<T> {
+    match digits(value, false) {
+        _ if value.is_empty() => MatchResult::PartialMatch,
+        len if len == value.len() => MatchResult::Match(None),
+        _ => MatchResult::None,
+    }
+}
+
+/// Matches `0x` or `0X` followed by hexadecimal digits in either case.
+pub(crate) fn hex_integer<T>(value: &str) -> MatchResult<T> {
+    let Some(rest) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    else {
+        return match value {
+            "" | "0" => MatchResult::PartialMatch,
+            _ => MatchResult::None,
+        };
+    };
+
+    match digits(rest, true) {
+        _ if rest.is_empty() => MatchResult::PartialMatch,
+        len if len == rest.len() => MatchResult::Match(None),
+        _ => MatchResult::None,
+    }
+}
+
+/// Matches digits followed by a fraction, an exponent or both, such as
+/// `3.14`, `1e-9` or `2.5E+3`. A dot has to be followed by a digit, so that
+/// `1..2` leaves the range operator alone.
+pub(crate) fn float<T>(value: &str) -> MatchResult<T> {
+    let int = digits(value, false);
+
+    if int == 0 {
+        return match value.is_empty() {
+            true => MatchResult::PartialMatch,
+            false => MatchResult::None,
+        };
+    }
+
+    let mut rest = &value[int..];
+    let fraction = rest.starts_with('.');
+
+    if fraction {
+        let len = digits(&rest[1..], false);
+
+        if len == 0 {
+            return match rest.len() {
+                1 => MatchResult::PartialMatch,
+                _ => MatchResult::None,
+            };
+        }
+
+        rest = &rest[1 + len..];
+    }
+
+    let Some(exponent) = rest.strip_prefix(['e', 'E']) else {
+        return match rest.is_empty() {
+            true if fraction => MatchResult::Match(None),
+            true => MatchResult::PartialMatch,
+            false => MatchResult::None,
+        };
+    };
+
+    let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+
+    match digits(exponent, false) {
+        _ if exponent.is_empty() => MatchResult::PartialMatch,
+        len if len == exponent.len() => MatchResult::Match(None),
+        _ => MatchResult::None,
+    }
+}
+
+/// Matches any of [`Rule::HexInteger`], [`Rule::Float`] and
+/// [`Rule::Integer`].
+pub(crate) fn number<T>(value: &str) -> MatchResult<T> {
+    crate::rule::any([hex_integer(value), float(value), integer(value)].into_iter())
+}
+
+/// Returns the length of the leading decimal, or hexadecimal, digits.
+fn digits(value: &str, hex: bool) -> usize {
+    value
+        .bytes()
+        .take_while(|byte| match hex {
+            true => byte.is_ascii_hexdigit(),
+            false => byte.is_ascii_digit(),
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lexer, Rule};
+
+    #[derive(Debug, PartialEq)]
+    enum Token {
+        Number(f64),
+        Range,
+        Dot,
+        Minus,
+    }
+
+    fn parse(value: &str) -> Token {
+        let number = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+            Some(hex) => i64::from_str_radix(hex, 16).unwrap() as f64,
+            None => value.parse().unwrap(),
+        };
+
+        Token::Number(number)
+    }
+
+    const RULE: Rule<'static, Token> = Rule::Any(&[
+        Rule::Ignore(&Rule::Whitespace),
+        Rule::Value(&Rule::Number, &parse),
+        Rule::Value(&Rule::Literal(".."), &|_| Token::Range),
+        Rule::Value(&Rule::Literal("."), &|_| Token::Dot),
+        Rule::Value(&Rule::Literal("-"), &|_| Token::Minus),
+    ]);
+
+    fn lex(input: &'static str) -> Vec<Token> {
+        Lexer::with_buffer(RULE, input)
+            .map(|token| token.unwrap())
+            .collect()
+    }
+
+    fn result(rule: &Rule<'static, ()>, value: &'static str) -> &'static str {
+        match rule.matches(value) {
+            MatchResult::Match(_) => "Match",
+            MatchResult::PartialMatch => "PartialMatch",
+            MatchResult::None => "None",
+            MatchResult::Cut => "Cut",
+        }
+    }
+
+    #[test]
+    fn partial_matches() {
+        let table = [
+            (Rule::Integer, "42", "Match"),
+            (Rule::Integer, "007", "Match"),
+            (Rule::Integer, "4a", "None"),
+            (Rule::Integer, "-4", "None"),
+            (Rule::HexInteger, "0", "PartialMatch"),
+            (Rule::HexInteger, "0x", "PartialMatch"),
+            (Rule::HexInteger, "0xFF", "Match"),
+            (Rule::HexInteger, "0Xdead", "Match"),
+            (Rule::HexInteger, "0xfg", "None"),
+            (Rule::HexInteger, "1x", "None"),
+            (Rule::Float, "3", "PartialMatch"),
+            (Rule::Float, "3.", "PartialMatch"),
+            (Rule::Float, "3.5", "Match"),
+            (Rule::Float, "3..", "None"),
+            (Rule::Float, "3.e5", "None"),
+            (Rule::Float, ".5", "None"),
+            (Rule::Float, "1e", "PartialMatch"),
+            (Rule::Float, "1e-", "PartialMatch"),
+            (Rule::Float, "1e-9", "Match"),
+            (Rule::Float, "2.5E+3", "Match"),
+            (Rule::Float, "1e+-9", "None"),
+            (Rule::Number, "3", "Match"),
+            (Rule::Number, "3.", "PartialMatch"),
+            (Rule::Number, "0", "Match"),
+            (Rule::Number, "0x", "PartialMatch"),
+            (Rule::Number, "0x1F", "Match"),
+            (Rule::Number, "0.5", "Match"),
+            (Rule::Signed(&Rule::Integer), "-", "PartialMatch"),
+            (Rule::Signed(&Rule::Integer), "-42", "Match"),
+            (Rule::Signed(&Rule::Integer), "+42", "Match"),
+            (Rule::Signed(&Rule::Integer), "42", "Match"),
+            (Rule::Signed(&Rule::Integer), "--42", "None"),
+        ];
+
+        for (rule, value, expected) in table {
+            assert_eq!(result(&rule, value), expected, "{rule:?} {value:?}");
+        }
+    }
+
+    #[test]
+    fn lexes_numbers() {
+        assert_eq!(
+            lex("2.75 0xFF 0Xab 1e-9 007 2E3"),
+            [
+                Token::Number(2.75),
+                Token::Number(255.0),
+                Token::Number(171.0),
+                Token::Number(1e-9),
+                Token::Number(7.0),
+                Token::Number(2000.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn trailing_dot() {
+        assert_eq!(lex("3."), [Token::Number(3.0), Token::Dot]);
+        assert_eq!(lex("3. "), [Token::Number(3.0), Token::Dot]);
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            lex("1..10"),
+            [Token::Number(1.0), Token::Range, Token::Number(10.0)]
+        );
+        assert_eq!(
+            lex("1.5..2"),
+            [Token::Number(1.5), Token::Range, Token::Number(2.0)]
+        );
+    }
+
+    #[test]
+    fn signs() {
+        assert_eq!(
+            lex("-42"),
+            [Token::Minus, Token::Number(42.0)],
+            "Number leaves the sign to the operator"
+        );
+
+        let signed = Rule::Value(&Rule::Signed(&Rule::Number), &parse);
+        let tokens = Lexer::with_buffer(signed, "-4.5e1")
+            .map(|token| token.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(tokens, [Token::Number(-45.0)]);
+    }
+}
@@ -0,0 +1,249 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{OwnedRule, Rule};
+
+impl<'a, T> Rule<'a, T> {
+    /// Returns an owned copy of this rule with redundant structure removed,
+    /// matching exactly like the original.
+    ///
+    /// - `Ignore(Ignore(x))` becomes `Ignore(x)`.
+    /// - Single-element `All`s become `Value`s.
+    /// - `Either` chains of literals and adjacent literals in an `Any` become
+    ///   a single `Literals` set.
+    /// - Later duplicates of an `Any` alternative are removed.
+    /// - `Any`s nested in another `Any` are spliced into it, unless one of
+    ///   their alternatives has a [`Rule::Priority`], which only ranks it
+    ///   within the nested `Any`.
+    pub fn optimize(&'a self) -> OwnedRule<'a, T> {
+        match self {
+            Self::Value(rule, out) => OwnedRule::Value(Box::new(rule.optimize()), *out),
+            Self::Ignore(rule) => match rule.optimize() {
+                OwnedRule::Ignore(rule) => OwnedRule::Ignore(rule),
+                rule => OwnedRule::Ignore(Box::new(rule)),
+            },
+            Self::Not(rule) => OwnedRule::Not(Box::new(rule.optimize())),
+            Self::Only(rule) => OwnedRule::Only(Box::new(rule.optimize())),
+            Self::Both(a, b) => OwnedRule::Both(Box::new(a.optimize()), Box::new(b.optimize())),
+            Self::Either(a, b) => {
+                let (a, b) = (a.optimize(), b.optimize());
+
+                match (literals(&a), literals(&b)) {
+                    (Some(mut a), Some(b)) => {
+                        a.extend(b);
+                        OwnedRule::Literals(dedup(a))
+                    }
+                    _ => OwnedRule::Either(Box::new(a), Box::new(b)),
+                }
+            }
+            Self::All([rule], out) => OwnedRule::Value(Box::new(rule.optimize()), *out),
+            Self::All(rules, out) => {
+                OwnedRule::All(rules.iter().map(Self::optimize).collect(), *out)
+            }
+            Self::Any(rules) => any(rules.iter().map(Self::optimize).collect()),
+            _ => OwnedRule::Rule(self),
+        }
+    }
+}
+
+fn any<'a, T>(rules: Vec<OwnedRule<'a, T>>) -> OwnedRule<'a, T> {
+    let rules: Vec<_> = rules
+        .into_iter()
+        .flat_map(|rule| match rule {
+            OwnedRule::Any(inner) if inner.iter().all(|rule| rule.priority() == 0) => inner,
+            rule => Vec::from([rule]),
+        })
+        .collect();
+
+    let mut merged: Vec<OwnedRule<'a, T>> = Vec::with_capacity(rules.len());
+
+    for rule in dedup(rules) {
+        let previous = merged.last().and_then(literals);
+
+        match (previous, literals(&rule)) {
+            (Some(mut previous), Some(current)) => {
+                previous.extend(current);
+                merged.pop();
+                merged.push(OwnedRule::Literals(dedup(previous)));
+            }
+            _ => merged.push(rule),
+        }
+    }
+
+    OwnedRule::Any(merged)
+}
+
+/// Returns the literals a rule matches if it is a plain literal or literal set.
+fn literals<'a, T>(rule: &OwnedRule<'a, T>) -> Option<Vec<&'a str>> {
+    match rule {
+        OwnedRule::Rule(Rule::Literal(literal)) => Some(Vec::from([*literal])),
+        OwnedRule::Rule(Rule::Literals(literals)) => Some(literals.to_vec()),
+        OwnedRule::Literals(literals) => Some(literals.clone()),
+        _ => None,
+    }
+}
+
+/// Removes later duplicates, which can never decide a first-wins result.
+fn dedup<V: PartialEq>(values: Vec<V>) -> Vec<V> {
+    let mut unique: Vec<V> = Vec::with_capacity(values.len());
+
+    for value in values {
+        if !unique.contains(&value) {
+            unique.push(value);
+        }
+    }
+
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::String};
+
+    use super::*;
+    use crate::Lexer;
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Token<'a> {
+        Word(&'a str),
+        Number(&'a str),
+    }
+
+    fn word(value: &str) -> Token<'_> {
+        Token::Word(value)
+    }
+
+    fn number(value: &str) -> Token<'_> {
+        Token::Number(value)
+    }
+
+    fn rules<'a>() -> [Rule<'a, Token<'a>>; 5] {
+        [
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Ignore(&Rule::Whitespace)),
+                Rule::Literal("a"),
+                Rule::Literal("ab"),
+                Rule::Value(
+                    &Rule::Either(
+                        &Rule::Either(&Rule::Literal("x"), &Rule::Literal("xy")),
+                        &Rule::Literal("y"),
+                    ),
+                    &word,
+                ),
+                Rule::All(&[Rule::Numeric], &number),
+                Rule::Literal("a"),
+                Rule::Any(&[Rule::Literal("c"), Rule::Value(&Rule::Alphabetic, &word)]),
+            ]),
+            Rule::Any(&[
+                Rule::Any(&[Rule::Literal("ab"), Rule::Literal("c")]),
+                Rule::Value(&Rule::Alphabetic, &word),
+                Rule::Value(&Rule::Alphabetic, &word),
+            ]),
+            Rule::Either(
+                &Rule::Not(&Rule::Literal("b")),
+                &Rule::Both(&Rule::Alphabetic, &Rule::Only(&Rule::Literal("ba"))),
+            ),
+            Rule::Any(&[
+                Rule::Priority(2, &Rule::Value(&Rule::Literal("ab"), &word)),
+                Rule::Any(&[Rule::Priority(
+                    5,
+                    &Rule::Value(&Rule::Literal("ab"), &number),
+                )]),
+            ]),
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Alphabetic, &word),
+                Rule::Any(&[
+                    Rule::Value(&Rule::Literal("a"), &number),
+                    Rule::Priority(1, &Rule::Value(&Rule::Literal("cab"), &number)),
+                ]),
+                Rule::Value(&Rule::Numeric, &number),
+            ]),
+        ]
+    }
+
+    #[test]
+    fn structure() {
+        let expected = OwnedRule::Any(Vec::from([
+            OwnedRule::Ignore(Box::new(OwnedRule::Rule(&Rule::Whitespace))),
+            OwnedRule::Literals(Vec::from(["a", "ab"])),
+            OwnedRule::Value(
+                Box::new(OwnedRule::Literals(Vec::from(["x", "xy", "y"]))),
+                &word,
+            ),
+            OwnedRule::Value(Box::new(OwnedRule::Rule(&Rule::Numeric)), &number),
+            OwnedRule::Rule(&Rule::Literal("c")),
+            OwnedRule::Value(Box::new(OwnedRule::Rule(&Rule::Alphabetic)), &word),
+        ]));
+
+        let rules = rules();
+
+        assert_eq!(rules[0].optimize(), expected);
+
+        assert_eq!(
+            rules[1].optimize(),
+            OwnedRule::Any(Vec::from([
+                OwnedRule::Literals(Vec::from(["ab", "c"])),
+                OwnedRule::Value(Box::new(OwnedRule::Rule(&Rule::Alphabetic)), &word),
+            ]))
+        );
+    }
+
+    /// Lexes `input` to the end, listing every result with its span.
+    fn tokens<'a>(rule: Rule<'a, Token<'a>>, input: &'a str) -> Vec<String> {
+        let mut lexer = Lexer::with_buffer(rule, input);
+        // The rules are redundant on purpose, skip the first step check.
+        #[cfg(debug_assertions)]
+        {
+            lexer.validated = true;
+        }
+        let mut tokens = Vec::new();
+
+        while let Some(token) = lexer.next() {
+            tokens.push(format!("{token:?} {:?}", lexer.span()));
+        }
+
+        tokens
+    }
+
+    #[test]
+    fn preserves_semantics() {
+        const ALPHABET: &[char] = &['a', 'b', 'c', 'x', 'y', '1', ' '];
+
+        let mut seed = 0x2545_f491_u32;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as usize
+        };
+
+        let inputs = (0..2000)
+            .map(|_| {
+                let len = next() % 5;
+                (0..len)
+                    .map(|_| ALPHABET[next() % ALPHABET.len()])
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+
+        for index in 0..rules().len() {
+            let rules = rules();
+            let rule = &rules[index];
+            let optimized = rule.optimize();
+
+            for input in &inputs {
+                assert_eq!(
+                    format!("{:?}", optimized.matches(input)),
+                    format!("{:?}", rule.matches(input)),
+                    "{rule:?} on {input:?}"
+                );
+                assert_eq!(
+                    tokens(optimized.as_rule(), input),
+                    tokens(rule.clone(), input),
+                    "{rule:?} on {input:?}"
+                );
+            }
+        }
+    }
+}
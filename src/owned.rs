@@ -0,0 +1,312 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{rule, Context, Extract, MatchResult, Rule};
+
+/// An allocation-backed rule tree, for grammars built at runtime.
+///
+/// Owned rules mirror the composite variants of [`Rule`] and can embed any
+/// borrowed rule through [`OwnedRule::Rule`]. Use [`OwnedRule::as_rule`] to
+/// hand one to a [`Lexer`](crate::Lexer).
+#[derive(Debug)]
+pub enum OwnedRule<'a, T> {
+    /// Matches if the provided borrowed rule matches.
+    Rule(&'a Rule<'a, T>),
+    /// Matches if the provided rule, stored inline, matches, for rules such
+    /// as literals and delimiters built at runtime that don't borrow other
+    /// rules.
+    Inline(Rule<'a, T>),
+    /// Matches if equal to one of the provided literals, like an `Any` of
+    /// literals.
+    Literals(Vec<&'a str>),
+    /// Value extraction if matching the provided rule.
+    Value(Box<OwnedRule<'a, T>>, &'a dyn Extract<'a, T>),
+    /// Value extraction if matching the provided rule, making the token
+    /// through the factory from the token name and the matched slice, as
+    /// tokens of grammars loaded at runtime are named rather than built by
+    /// closures.
+    Named(Box<OwnedRule<'a, T>>, &'a str, TokenFactory<'a, T>),
+    /// Matches like the provided rule, discarding any token it produces.
+    Ignore(Box<OwnedRule<'a, T>>),
+    /// Matches if the provided rule doesn't match.
+    Not(Box<OwnedRule<'a, T>>),
+    /// Matches if only the provided rule matches.
+    Only(Box<OwnedRule<'a, T>>),
+    /// Matches if both of the provided rules match.
+    Both(Box<OwnedRule<'a, T>>, Box<OwnedRule<'a, T>>),
+    /// Matches if either of the provided rules match.
+    Either(Box<OwnedRule<'a, T>>, Box<OwnedRule<'a, T>>),
+    /// Matches if all of the provided rules match.
+    All(Vec<OwnedRule<'a, T>>, &'a dyn Extract<'a, T>),
+    /// Matches like [`Rule::Seq`], if the slice splits into consecutive
+    /// matches of the provided rules.
+    Seq(Vec<OwnedRule<'a, T>>),
+    /// Matches like [`Rule::Repeat`], if the slice splits into between `min`
+    /// and `max` consecutive matches of the provided rule.
+    Repeat {
+        rule: Box<OwnedRule<'a, T>>,
+        min: usize,
+        max: Option<usize>,
+    },
+    /// Matches if any of the provided rules match. Among the rules that
+    /// fully match, the one with the highest [`Rule::Priority`] decides the
+    /// token, the first one in declaration order breaking ties; rules that
+    /// only partially match can't change it.
+    Any(Vec<OwnedRule<'a, T>>),
+    /// Matches like [`Rule::IdentifierOrKeyword`], with keywords that hold
+    /// their tokens, so that they can be built at runtime. A keyword produces
+    /// its token through `token`, typically [`Clone::clone`]. Without a
+    /// `fallback`, identifiers that aren't keywords don't match.
+    IdentifierOrKeyword {
+        ident: &'a Rule<'a, T>,
+        keywords: Vec<(&'a str, T)>,
+        token: fn(&T) -> T,
+        fallback: Option<fn(&'a str) -> T>,
+    },
+}
+
+/// Makes the token named by the first argument out of the matched slice, for
+/// [`OwnedRule::Named`].
+pub type TokenFactory<'a, T> = fn(&'a str, &'a str) -> T;
+
+impl<'a, T> OwnedRule<'a, T> {
+    /// Returns a borrowed rule that matches like this one.
+    pub const fn as_rule(&'a self) -> Rule<'a, T> {
+        Rule::Owned(self)
+    }
+
+    pub fn matches(&self, value: &'a str) -> MatchResult<T> {
+        self.matches_in(Context::from(value))
+    }
+
+    /// Matches the candidate slice of `cx`, letting context-dependent rules
+    /// inspect the surrounding text. Like [`Rule::matches_in`], the empty
+    /// slice never matches.
+    pub fn matches_in(&self, cx: Context<'a>) -> MatchResult<T> {
+        match self.eval(cx) {
+            MatchResult::Match(_) if cx.range().is_empty() => MatchResult::PartialMatch,
+            result => result,
+        }
+    }
+
+    /// Matches like [`Rule::eval`], letting the empty slice match.
+    pub(crate) fn eval(&self, cx: Context<'a>) -> MatchResult<T> {
+        let value = cx.value();
+
+        match self {
+            Self::Rule(rule) => rule.eval(cx),
+            Self::Inline(rule) => rule.eval(cx),
+            Self::Literals(literals) => rule::literals(literals.iter().copied(), value),
+            Self::Value(rule, out) => rule::value(rule.eval(cx), *out, cx),
+            Self::Named(rule, name, make) => {
+                rule::value(rule.eval(cx), &|value| make(name, value), cx)
+            }
+            Self::Ignore(rule) => rule::ignore(rule.eval(cx.probe())),
+            Self::Not(rule) => rule::not(rule.eval(cx.probe())),
+            Self::Only(rule) => rule.eval(cx),
+            Self::Both(a, b) => rule::both(a.eval(cx.probe()), || b.eval(cx.probe())),
+            Self::Either(a, b) => rule::either(a.eval(cx.probe()), || b.eval(cx.probe())),
+            Self::All(rules, out) => {
+                rule::all(rules.iter().map(|rule| rule.eval(cx.probe())), *out, cx)
+            }
+            Self::Seq(rules) => rule::seq(rules, cx),
+            Self::Repeat { rule, min, max } => rule::repeat(&**rule, 0, *min, *max, cx.probe()),
+            Self::Any(rules) => rule::ranked(
+                rules
+                    .iter()
+                    .map(|rule| (rule.priority(), move || rule.eval(cx))),
+            ),
+            Self::IdentifierOrKeyword {
+                ident,
+                keywords,
+                token,
+                fallback,
+            } => match ident.eval(cx.probe()) {
+                MatchResult::Match(_) if rule::continues(*ident, cx) => MatchResult::PartialMatch,
+                MatchResult::Match(_) => {
+                    let keyword = keywords.binary_search_by(|(keyword, _)| (*keyword).cmp(value));
+
+                    match (keyword, fallback) {
+                        (Ok(_), _) | (Err(_), Some(_)) if cx.is_probe() => MatchResult::Match(None),
+                        (Ok(index), _) => MatchResult::Match(Some(token(&keywords[index].1))),
+                        (Err(_), Some(fallback)) => MatchResult::Match(Some(fallback(value))),
+                        (Err(_), None) => MatchResult::None,
+                    }
+                }
+                result => result,
+            },
+        }
+    }
+
+    /// Returns the priority of the rule among the alternatives of an
+    /// [`OwnedRule::Any`], as set by an embedded [`Rule::Priority`].
+    pub(crate) fn priority(&self) -> u8 {
+        match self {
+            Self::Rule(rule) => rule.priority(),
+            Self::Inline(rule) => rule.priority(),
+            Self::Value(rule, _)
+            | Self::Named(rule, ..)
+            | Self::Ignore(rule)
+            | Self::Only(rule) => rule.priority(),
+            _ => 0,
+        }
+    }
+}
+
+impl<'a, T> rule::Eval<'a, T> for OwnedRule<'a, T> {
+    fn eval(&self, cx: Context<'a>) -> MatchResult<T> {
+        OwnedRule::eval(self, cx)
+    }
+}
+
+impl<'a, T> From<Rule<'a, T>> for OwnedRule<'a, T> {
+    fn from(rule: Rule<'a, T>) -> Self {
+        Self::Inline(rule)
+    }
+}
+
+impl<'a, T> From<&'a Rule<'a, T>> for OwnedRule<'a, T> {
+    fn from(rule: &'a Rule<'a, T>) -> Self {
+        Self::Rule(rule)
+    }
+}
+
+/// Builds an [`OwnedRule`] out of keywords and other alternatives known only
+/// at runtime, for example read from a file.
+///
+/// The built rule is an [`OwnedRule::Any`] of the alternatives, in the order
+/// they were added, followed by the keywords and identifiers, if any.
+#[derive(Debug)]
+pub struct RuleSet<'a, T> {
+    rules: Vec<OwnedRule<'a, T>>,
+    keywords: Vec<(&'a str, T)>,
+    ident: &'a Rule<'a, T>,
+    fallback: Option<fn(&'a str) -> T>,
+}
+
+impl<'a, T: Clone> RuleSet<'a, T> {
+    pub const fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            keywords: Vec::new(),
+            ident: &Rule::Alphabetic,
+            fallback: None,
+        }
+    }
+
+    /// Adds an alternative.
+    pub fn rule(mut self, rule: OwnedRule<'a, T>) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Adds an alternative whose matches are skipped, such as whitespace.
+    pub fn ignore(self, rule: &'a Rule<'a, T>) -> Self {
+        self.rule(OwnedRule::Ignore(Box::new(OwnedRule::Rule(rule))))
+    }
+
+    /// Adds a keyword producing a clone of `token`. If a keyword is added
+    /// twice, the first token wins.
+    pub fn keyword(mut self, keyword: &'a str, token: T) -> Self {
+        self.keywords.push((keyword, token));
+        self
+    }
+
+    /// Adds each of `keywords` like [`RuleSet::keyword`].
+    pub fn keywords(mut self, keywords: impl IntoIterator<Item = (&'a str, T)>) -> Self {
+        self.keywords.extend(keywords);
+        self
+    }
+
+    /// Lexes words of [`Rule::Alphabetic`] that aren't keywords through
+    /// `fallback`.
+    pub fn ident(mut self, fallback: fn(&'a str) -> T) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    /// Lexes identifiers, and the keywords among them, like `ident` rather
+    /// than [`Rule::Alphabetic`], making the others through `fallback`.
+    pub fn ident_with(mut self, ident: &'a Rule<'a, T>, fallback: fn(&'a str) -> T) -> Self {
+        self.ident = ident;
+        self.ident(fallback)
+    }
+
+    pub fn build(mut self) -> OwnedRule<'a, T> {
+        self.keywords.sort_by_key(|(keyword, _)| *keyword);
+        self.keywords.dedup_by_key(|(keyword, _)| *keyword);
+
+        if !self.keywords.is_empty() || self.fallback.is_some() {
+            self.rules.push(OwnedRule::IdentifierOrKeyword {
+                ident: self.ident,
+                keywords: self.keywords,
+                token: T::clone,
+                fallback: self.fallback,
+            });
+        }
+
+        OwnedRule::Any(self.rules)
+    }
+}
+
+impl<T: Clone> Default for RuleSet<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> PartialEq for OwnedRule<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Rule(a), Self::Rule(b)) => a == b,
+            (Self::Inline(a), Self::Inline(b)) => a == b,
+            (Self::Literals(a), Self::Literals(b)) => a == b,
+            (Self::Value(a, f), Self::Value(b, g)) => a == b && rule::extract_eq(*f, *g),
+            (Self::Named(a, m, f), Self::Named(b, n, g)) => {
+                a == b && m == n && core::ptr::fn_addr_eq(*f, *g)
+            }
+            (Self::Ignore(a), Self::Ignore(b)) => a == b,
+            (Self::Not(a), Self::Not(b)) => a == b,
+            (Self::Only(a), Self::Only(b)) => a == b,
+            (Self::Both(a, b), Self::Both(c, d)) => a == c && b == d,
+            (Self::Either(a, b), Self::Either(c, d)) => a == c && b == d,
+            (Self::All(a, f), Self::All(b, g)) => a == b && rule::extract_eq(*f, *g),
+            (Self::Seq(a), Self::Seq(b)) => a == b,
+            (
+                Self::Repeat { rule, min, max },
+                Self::Repeat {
+                    rule: other,
+                    min: other_min,
+                    max: other_max,
+                },
+            ) => rule == other && min == other_min && max == other_max,
+            (Self::Any(a), Self::Any(b)) => a == b,
+            (
+                Self::IdentifierOrKeyword {
+                    ident: a,
+                    keywords: k,
+                    token: t,
+                    fallback: f,
+                },
+                Self::IdentifierOrKeyword {
+                    ident: b,
+                    keywords: l,
+                    token: u,
+                    fallback: g,
+                },
+            ) => {
+                let fallbacks = match (f, g) {
+                    (Some(f), Some(g)) => core::ptr::fn_addr_eq(*f, *g),
+                    (f, g) => f.is_none() && g.is_none(),
+                };
+
+                a == b
+                    && fallbacks
+                    && core::ptr::fn_addr_eq(*t, *u)
+                    && k.iter()
+                        .map(|(keyword, _)| keyword)
+                        .eq(l.iter().map(|(keyword, _)| keyword))
+            }
+            _ => false,
+        }
+    }
+}
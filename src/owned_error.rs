@@ -0,0 +1,171 @@
+use alloc::string::{String, ToString};
+use core::{fmt, ops::Range};
+
+use crate::{Error, Lexer};
+
+/// The variant of an [`Error`], without its data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Eof,
+    UnknownToken,
+    Ambiguous,
+    Committed,
+    Unterminated,
+    Unexpected,
+    ModeOverflow,
+    TooManyTokens,
+    Overflow,
+}
+
+/// An [`Error`] packaged without borrowing the buffer, as returned by
+/// [`Lexer::map_err_span`], so that it can outlive the lexer and convert
+/// into an application's own error type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexErrorOwned {
+    pub kind: ErrorKind,
+    /// The offending text, or the offset where the error was raised as an
+    /// empty span.
+    pub span: Range<usize>,
+    /// What [`Lexer::expect_next`] expected instead, for
+    /// [`ErrorKind::Unexpected`].
+    pub expected: Option<&'static str>,
+    /// The error as displayed.
+    pub message: String,
+    /// The full length in bytes of the offending text, when `message` shows
+    /// it truncated.
+    pub truncated: Option<usize>,
+}
+
+impl fmt::Display for LexErrorOwned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl core::error::Error for LexErrorOwned {}
+
+impl<'a, T> Lexer<'a, T> {
+    /// Packages `error`, raised by this lexer, into a [`LexErrorOwned`],
+    /// resolving it into a span with [`Lexer::error_span`].
+    pub fn map_err_span(&self, error: Error<'a>) -> LexErrorOwned {
+        let (kind, expected) = match &error {
+            Error::Eof => (ErrorKind::Eof, None),
+            Error::UnknownToken { .. } => (ErrorKind::UnknownToken, None),
+            Error::Ambiguous { .. } => (ErrorKind::Ambiguous, None),
+            Error::Committed { .. } => (ErrorKind::Committed, None),
+            Error::Unterminated { .. } => (ErrorKind::Unterminated, None),
+            Error::Unexpected { expected, .. } => (ErrorKind::Unexpected, Some(*expected)),
+            Error::ModeOverflow { .. } => (ErrorKind::ModeOverflow, None),
+            Error::TooManyTokens { .. } => (ErrorKind::TooManyTokens, None),
+            Error::Overflow { .. } => (ErrorKind::Overflow, None),
+        };
+
+        LexErrorOwned {
+            kind,
+            span: self.error_span(&error),
+            expected,
+            message: error.to_string(),
+            truncated: error.display(Error::TEXT_LIMIT).truncated(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rule;
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        Word(&'a str),
+        Number(&'a str),
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum AppError {
+        Lex(LexErrorOwned),
+        #[allow(dead_code)]
+        Config,
+    }
+
+    impl From<LexErrorOwned> for AppError {
+        fn from(error: LexErrorOwned) -> Self {
+            Self::Lex(error)
+        }
+    }
+
+    fn parse(input: &str) -> Result<Range<usize>, AppError> {
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Alphabetic, &Token::Word),
+                Rule::Value(&Rule::Numeric, &Token::Number),
+            ]),
+            input,
+        );
+
+        let token = lexer
+            .expect_next(|token| matches!(token, Token::Word(_)), "word")
+            .map_err(|error| lexer.map_err_span(error))?;
+
+        Ok(token.span)
+    }
+
+    #[test]
+    fn into_app_error() {
+        assert_eq!(parse("word\n"), Ok(0..4));
+
+        let input = String::from(" 42\n");
+        let error = parse(&input);
+        drop(input);
+
+        assert_eq!(
+            error,
+            Err(AppError::Lex(LexErrorOwned {
+                kind: ErrorKind::Unexpected,
+                span: 1..3,
+                expected: Some("word"),
+                message: String::from("expected word, found `42` at 1:2"),
+                truncated: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let lexer = Lexer::<()>::with_buffer(Rule::Numeric, "12 ab\n");
+
+        let unknown = lexer.map_err_span(Error::UnknownToken {
+            text: &lexer.buffer()[3..5],
+            at: 3,
+            line: 1,
+            column: 4,
+        });
+        assert_eq!(unknown.kind, ErrorKind::UnknownToken);
+        assert_eq!(unknown.span, 3..5);
+        assert_eq!(unknown.message, "unknown token `ab` at 1:4");
+        assert_eq!(unknown.truncated, None);
+
+        let eof = lexer.map_err_span(Error::Eof);
+        assert_eq!(
+            (eof.kind, eof.span, eof.expected),
+            (ErrorKind::Eof, 6..6, None)
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let input = "@".repeat(100);
+        let lexer = Lexer::<()>::with_buffer(Rule::Numeric, &input);
+
+        let error = lexer.map_err_span(Error::UnknownToken {
+            text: lexer.buffer(),
+            at: 0,
+            line: 1,
+            column: 1,
+        });
+        assert_eq!(error.span, 0..100);
+        assert_eq!(error.truncated, Some(100));
+        assert!(error.message.contains("(100 bytes)"));
+    }
+}
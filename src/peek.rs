@@ -0,0 +1,315 @@
+use core::mem;
+
+use crate::{Error, Lexer, LineBase, ModeStack, Result, Spanned, PUSH_BACK_LIMIT};
+
+/// The progress of a [`Lexer`], as returned by [`Lexer::checkpoint`], for
+/// backtracking to it with [`Lexer::rewind`] after a failed speculative
+/// parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint<'a> {
+    index: (usize, usize),
+    matched: Option<usize>,
+    span: (usize, usize),
+    terminate: bool,
+    gate_open: bool,
+    line_base: Option<LineBase<'a>>,
+    mode: ModeStack,
+    exhausted: bool,
+    /// How many of the pieces split from the lexeme at `index` were taken.
+    skip: usize,
+    #[cfg(feature = "alloc")]
+    warnings: usize,
+}
+
+impl Checkpoint<'_> {
+    /// Moves a checkpoint taken before a lexeme split by a
+    /// [`Rule::ValueMany`](crate::Rule::ValueMany) past its first `skip`
+    /// pieces.
+    pub(crate) const fn skipping(self, skip: usize) -> Self {
+        Self { skip, ..self }
+    }
+}
+
+impl<'a, T> Lexer<'a, T> {
+    /// Returns the next token without consuming it, skipping trivia, or
+    /// `None` at the end of the input.
+    pub fn peek(&mut self) -> Result<'a, Option<&T>> {
+        self.peek_n(0)
+    }
+
+    /// Returns the token `n` tokens ahead of the next one without consuming
+    /// any, so that `peek_n(0)` peeks like [`Lexer::peek`].
+    ///
+    /// Peeked tokens are held like tokens pushed back with
+    /// [`Lexer::push_back`], and count towards the same limit. An error
+    /// stops peeking, keeping the tokens peeked before it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if peeking would hold more than [`PUSH_BACK_LIMIT`] tokens.
+    pub fn peek_n(&mut self, n: usize) -> Result<'a, Option<&T>> {
+        while self.pushed.iter().flatten().count() <= n {
+            let before = self.state();
+            let pushed = mem::replace(&mut self.pushed, [const { None }; PUSH_BACK_LIMIT]);
+            let peeked = mem::replace(&mut self.peeked, [None; PUSH_BACK_LIMIT]);
+            let span = self.span();
+
+            let token = self.next_token();
+            let token_span = mem::replace(&mut self.span, span);
+            let pieces = mem::replace(&mut self.pushed, pushed);
+            let pieces_before = mem::replace(&mut self.peeked, peeked);
+
+            match token {
+                Ok(Some(token)) => {
+                    self.push_under(Spanned::new(token, token_span), Some(before));
+
+                    // The rest of a lexeme split by a `Rule::ValueMany`.
+                    for (piece, before) in pieces.into_iter().zip(pieces_before).rev() {
+                        if let Some(piece) = piece {
+                            self.push_under(piece, before);
+                        }
+                    }
+                }
+                Ok(None) => return Ok(None),
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(self
+            .pushed
+            .iter()
+            .rev()
+            .flatten()
+            .nth(n)
+            .map(|token| &token.value))
+    }
+
+    /// Records the lexer's progress, before any peeked tokens.
+    pub fn checkpoint(&self) -> Checkpoint<'a> {
+        let peeked = self
+            .pushed
+            .iter()
+            .zip(&self.peeked)
+            .rev()
+            .find_map(|(token, peeked)| token.as_ref().and(*peeked));
+
+        Checkpoint {
+            span: (self.span.start, self.span.end),
+            ..peeked.unwrap_or_else(|| self.state())
+        }
+    }
+
+    /// Returns to `checkpoint`, taken from this lexer, so that the tokens
+    /// after it are lexed again. Tokens peeked since are dropped, as are
+    /// tokens pushed back with [`Lexer::push_back`] and the warnings found
+    /// after the checkpoint. A checkpoint taken between the pieces of a
+    /// lexeme split by a [`Rule::ValueMany`](crate::Rule::ValueMany) splits
+    /// it again, resuming after the pieces taken before it.
+    pub fn rewind(&mut self, checkpoint: Checkpoint<'a>) {
+        self.index = checkpoint.index;
+        self.data = &self.buffer[checkpoint.index.0..checkpoint.index.1];
+        self.matched = checkpoint.matched;
+        self.span = checkpoint.span.0..checkpoint.span.1;
+        self.terminate = checkpoint.terminate;
+        self.gate_open = checkpoint.gate_open;
+        self.line_base = checkpoint.line_base;
+        self.mode = checkpoint.mode;
+        self.exhausted = checkpoint.exhausted;
+        self.pushed = [const { None }; PUSH_BACK_LIMIT];
+        self.peeked = [None; PUSH_BACK_LIMIT];
+
+        #[cfg(feature = "alloc")]
+        self.warnings.truncate(checkpoint.warnings);
+
+        // Splits the lexeme again, dropping the pieces taken before the
+        // checkpoint.
+        for _ in 0..checkpoint.skip {
+            if !matches!(self.next_token(), Ok(Some(_))) {
+                break;
+            }
+        }
+
+        self.span = checkpoint.span.0..checkpoint.span.1;
+    }
+
+    /// Returns the progress before the lexeme being committed, for the
+    /// pieces a [`Rule::ValueMany`](crate::Rule::ValueMany) splits it into.
+    pub(crate) fn lexeme_state(&self) -> Checkpoint<'a> {
+        Checkpoint {
+            index: (self.index.0, self.index.0),
+            matched: None,
+            ..self.state()
+        }
+    }
+
+    fn state(&self) -> Checkpoint<'a> {
+        Checkpoint {
+            index: self.index,
+            matched: self.matched,
+            span: (self.span.start, self.span.end),
+            terminate: self.terminate,
+            gate_open: self.gate_open,
+            line_base: self.line_base,
+            mode: self.mode,
+            exhausted: self.exhausted,
+            skip: 0,
+            #[cfg(feature = "alloc")]
+            warnings: self.warnings.len(),
+        }
+    }
+
+    /// Steps until the next token, returning `None` at the end of the input.
+    pub(crate) fn next_token(&mut self) -> Result<'a, Option<T>> {
+        loop {
+            match self.step() {
+                Ok(Some(token)) => return Ok(Some(token)),
+                Ok(None) => {}
+                Err(Error::Eof) => return Ok(None),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Holds a peeked `token` after every token already held, along with the
+    /// progress `before` it was lexed.
+    fn push_under(&mut self, token: Spanned<T>, before: Option<Checkpoint<'a>>) {
+        let mut len = 0;
+
+        for slot in 0..PUSH_BACK_LIMIT {
+            if self.pushed[slot].is_some() {
+                self.pushed.swap(len, slot);
+                self.peeked.swap(len, slot);
+                len += 1;
+            }
+        }
+
+        assert!(len < PUSH_BACK_LIMIT, "too many tokens peeked");
+
+        self.pushed[..=len].rotate_right(1);
+        self.peeked[..=len].rotate_right(1);
+        self.pushed[0] = Some(token);
+        self.peeked[0] = before;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rule;
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        Word(&'a str),
+        Gt,
+    }
+
+    const RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+        Rule::Ignore(&Rule::Whitespace),
+        Rule::Value(&Rule::Alphabetic, &Token::Word),
+    ]);
+
+    const GENERICS: Rule<'static, Token<'static>> = Rule::Any(&[
+        RULE,
+        Rule::ValueMany(&Rule::Literal(">>"), |_, emit| {
+            emit(0..1, Token::Gt);
+            emit(1..2, Token::Gt);
+        }),
+    ]);
+
+    #[test]
+    fn peek() {
+        let mut lexer = Lexer::with_buffer(RULE, "a bc d\n");
+
+        assert_eq!(lexer.peek().unwrap(), Some(&Token::Word("a")));
+        assert_eq!(lexer.peek().unwrap(), Some(&Token::Word("a")));
+        assert_eq!(lexer.peek_n(2).unwrap(), Some(&Token::Word("d")));
+        assert_eq!(lexer.span(), 0..0);
+
+        assert_eq!(lexer.step().unwrap(), Some(Token::Word("a")));
+        assert_eq!(lexer.span(), 0..1);
+        assert_eq!(lexer.peek_n(1).unwrap(), Some(&Token::Word("d")));
+        assert_eq!(lexer.step().unwrap(), Some(Token::Word("bc")));
+        assert_eq!(lexer.span(), 2..4);
+        assert_eq!(lexer.step().unwrap(), Some(Token::Word("d")));
+
+        assert_eq!(lexer.peek().unwrap(), None);
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn rewind() {
+        let mut lexer = Lexer::with_buffer(RULE, "a bc d ef\n");
+        let three = |lexer: &mut Lexer<'static, Token<'static>>| {
+            [(); 3].map(|()| lexer.next().unwrap().unwrap())
+        };
+
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Word("a"));
+
+        let checkpoint = lexer.checkpoint();
+        let tokens = three(&mut lexer);
+        assert_eq!(
+            tokens,
+            [Token::Word("bc"), Token::Word("d"), Token::Word("ef")]
+        );
+
+        lexer.rewind(checkpoint);
+        assert_eq!(lexer.span(), 0..1);
+        assert_eq!(three(&mut lexer), tokens);
+
+        // Checkpoints taken while tokens are peeked come before them.
+        lexer.rewind(checkpoint);
+        assert_eq!(lexer.peek_n(1).unwrap(), Some(&Token::Word("d")));
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Word("bc"));
+
+        let checkpoint = lexer.checkpoint();
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Word("d"));
+        lexer.rewind(checkpoint);
+
+        assert_eq!(lexer.span(), 2..4);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Word("d"));
+    }
+
+    #[test]
+    fn peek_split() {
+        let tokens = [Token::Word("a"), Token::Gt, Token::Gt, Token::Word("b")];
+
+        let mut lexer = Lexer::with_buffer(GENERICS, "a >> b\n");
+        assert_eq!(lexer.peek_n(2).unwrap(), Some(&Token::Gt));
+        assert_eq!(lexer.peek_n(3).unwrap(), Some(&Token::Word("b")));
+        assert_eq!(lexer.map(Result::unwrap).collect::<Vec<_>>(), tokens);
+
+        // Peeking between the pieces keeps the one after.
+        let mut lexer = Lexer::with_buffer(GENERICS, "a >> b\n");
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Word("a"));
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Gt);
+        assert_eq!(lexer.peek().unwrap(), Some(&Token::Gt));
+        assert_eq!(lexer.peek_n(1).unwrap(), Some(&Token::Word("b")));
+        assert_eq!(lexer.map(Result::unwrap).collect::<Vec<_>>(), tokens[2..]);
+    }
+
+    #[test]
+    fn rewind_split() {
+        let mut lexer = Lexer::with_buffer(GENERICS, "a >> b\n");
+
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Word("a"));
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Gt);
+
+        let checkpoint = lexer.checkpoint();
+        let rest = [Token::Gt, Token::Word("b")];
+        assert_eq!(lexer.by_ref().flatten().collect::<Vec<_>>(), rest);
+
+        lexer.rewind(checkpoint);
+        assert_eq!(lexer.span(), 2..3);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Gt);
+        assert_eq!(lexer.span(), 3..4);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Word("b"));
+
+        // Checkpoints taken while split pieces are peeked come before them.
+        lexer.rewind(checkpoint);
+        assert_eq!(lexer.peek_n(1).unwrap(), Some(&Token::Word("b")));
+        let checkpoint = lexer.checkpoint();
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Gt);
+        lexer.rewind(checkpoint);
+        assert_eq!(lexer.by_ref().flatten().collect::<Vec<_>>(), rest);
+    }
+}
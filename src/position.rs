@@ -0,0 +1,266 @@
+use crate::{DirectiveParser, Error, Lexer, Rule};
+
+/// A human-readable position in the input, as returned by
+/// [`Lexer::position`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position<'a> {
+    /// The 1-based line number, as overridden by the latest position
+    /// directive, if any.
+    pub line: usize,
+    /// The 1-based column, counting characters.
+    pub column: usize,
+    /// The source named by the latest position directive, if any.
+    pub source: Option<&'a str>,
+}
+
+/// The logical line number of the line starting at the given offset, and the
+/// source it belongs to.
+pub(crate) type LineBase<'a> = (usize, usize, Option<&'a str>);
+
+impl<'a, T> Lexer<'a, T> {
+    /// Treats text matching `rule` at the start of a token as a position
+    /// directive, such as C's `#line 120 "orig.src"`. The directive is
+    /// skipped if `parser` accepts it, and makes [`Lexer::position`] report
+    /// the following lines as starting at the parsed line number, in the
+    /// parsed source if any. Byte offsets and spans are unaffected.
+    ///
+    /// The rule should not consume the line break ending the directive,
+    /// which is lexed as usual.
+    pub const fn with_position_directive(
+        mut self,
+        parser: DirectiveParser<'a>,
+        rule: &'a Rule<'a, T>,
+    ) -> Self {
+        self.directive = Some((parser, rule));
+        self
+    }
+
+    /// Returns the line and column of the byte at `offset`, taking position
+    /// directives into account. Only the latest directive is remembered, so
+    /// offsets before it report physical lines.
+    ///
+    /// Line breaks are `\n` and `\r\n`. The lexer counts them as it commits
+    /// slices, so positions at or after the first uncommitted byte are found
+    /// without rescanning the buffer.
+    pub fn position(&self, offset: usize) -> Position<'a> {
+        let offset = offset.min(self.buffer.len());
+        let (_, line) = self.line_containing(offset);
+        let mut column = self.buffer[line.start..offset].chars().count() + 1;
+
+        if line.start == 0 {
+            column += self.columns;
+        }
+
+        let (line, source) = match self.line_base {
+            Some((base, line, source)) if offset >= base => (
+                line + self.buffer[base..offset].matches('\n').count(),
+                source,
+            ),
+            _ => (1 + self.breaks(offset), None),
+        };
+
+        Position {
+            line,
+            column,
+            source,
+        }
+    }
+
+    /// Returns the position of the first uncommitted byte, where the next
+    /// token starts.
+    pub fn current_position(&self) -> Position<'a> {
+        self.position(self.index.0)
+    }
+
+    /// Returns the line and column where `error`, raised by this lexer,
+    /// starts and ends, see [`Lexer::error_span`].
+    pub fn error_positions(&self, error: &Error<'a>) -> (Position<'a>, Position<'a>) {
+        let span = self.error_span(error);
+
+        (self.position(span.start), self.position(span.end))
+    }
+
+    /// Returns the number of line breaks before `offset`, counting on from
+    /// the lines committed so far when possible.
+    fn breaks(&self, offset: usize) -> usize {
+        let (start, breaks) = match self.lines {
+            (start, breaks) if start <= offset => (start, breaks),
+            _ => (0, 0),
+        };
+
+        breaks + self.buffer[start..offset].matches('\n').count()
+    }
+
+    /// Counts the line breaks committed up to the first uncommitted byte.
+    pub(crate) fn count_lines(&mut self) {
+        if self.lines.0 <= self.index.0 {
+            self.lines = (self.index.0, self.breaks(self.index.0));
+        }
+    }
+
+    /// Skips a position directive at the first uncommitted byte, returning
+    /// whether there was one.
+    pub(crate) fn position_directive(&mut self) -> bool {
+        let Some((parser, rule)) = self.directive else {
+            return false;
+        };
+        let Some(end) = rule.longest_match(self.buffer, self.index.0) else {
+            return false;
+        };
+        let Some((line, source)) = parser(&self.buffer[self.index.0..end]) else {
+            return false;
+        };
+
+        let next = self.buffer[end..]
+            .find('\n')
+            .map_or(self.buffer.len(), |offset| end + offset + 1);
+
+        self.line_base = Some((next, line as usize, source));
+        self.index = (end, end);
+        self.matched = None;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        Word(&'a str),
+    }
+
+    fn parse_line(directive: &str) -> Option<(u32, Option<&str>)> {
+        let (line, source) = match directive.strip_prefix("#line ")?.split_once(' ') {
+            Some((line, source)) => (line, Some(source.trim_matches('"'))),
+            None => (directive.strip_prefix("#line ")?, None),
+        };
+
+        Some((line.parse().ok()?, source))
+    }
+
+    static DIRECTIVE: Rule<'static, Token<'static>> = Rule::AtLineStart(&Rule::Seq(&[
+        Rule::Literal("#line"),
+        Rule::Chars(|c| c != '\n'),
+    ]));
+
+    #[test]
+    fn line_directive() {
+        let input = "a\n#line 120 \"orig.src\"\nb\n  c\n";
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Alphabetic, &Token::Word),
+            ]),
+            input,
+        )
+        .with_position_directive(parse_line, &DIRECTIVE);
+
+        let positions = lexer
+            .spanned()
+            .map(|token| (token.value, token.span.start))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|(token, start)| (token, lexer.position(start)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            positions,
+            [
+                (
+                    Token::Word("a"),
+                    Position {
+                        line: 1,
+                        column: 1,
+                        source: None,
+                    }
+                ),
+                (
+                    Token::Word("b"),
+                    Position {
+                        line: 120,
+                        column: 1,
+                        source: Some("orig.src"),
+                    }
+                ),
+                (
+                    Token::Word("c"),
+                    Position {
+                        line: 121,
+                        column: 3,
+                        source: Some("orig.src"),
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn lines() {
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Alphabetic, &Token::Word),
+            ]),
+            "one\r\ntwo\n\n  πœ three\n",
+        );
+
+        let tokens = lexer.spanned().collect::<Vec<_>>();
+        let positions = tokens
+            .into_iter()
+            .map(|token| (token.value, lexer.position(token.span.start)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            positions
+                .iter()
+                .map(|(token, position)| (token, position.line, position.column))
+                .collect::<Vec<_>>(),
+            [
+                (&Token::Word("one"), 1, 1),
+                (&Token::Word("two"), 2, 1),
+                (&Token::Word("πœ"), 4, 3),
+                (&Token::Word("three"), 4, 6),
+            ]
+        );
+        assert_eq!(lexer.current_position().line, 5);
+        assert_eq!(lexer.position(0).line, 1);
+
+        let error = Error::UnknownToken {
+            text: &lexer.buffer()[12..16],
+            at: 12,
+            line: 4,
+            column: 3,
+        };
+        let (start, end) = lexer.error_positions(&error);
+
+        assert_eq!((start.line, start.column), (4, 3));
+        assert_eq!((end.line, end.column), (4, 5));
+    }
+
+    #[test]
+    fn rejected_directive() {
+        let mut lexer = Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Ignore(&Rule::Seq(&[
+                    Rule::Literal("#"),
+                    Rule::Chars(|c| c != '\n'),
+                ])),
+                Rule::Value(&Rule::Alphabetic, &Token::Word),
+            ]),
+            "#line x\nb\n",
+        )
+        .with_position_directive(parse_line, &DIRECTIVE);
+
+        let starts = lexer
+            .spanned()
+            .map(|token| token.span.start)
+            .collect::<Vec<_>>();
+
+        assert_eq!(starts, [8]);
+        assert_eq!(lexer.position(8).line, 2);
+    }
+}
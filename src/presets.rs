@@ -0,0 +1,14 @@
+//! Ready-made rule sets for common formats.
+//!
+//! The presets are built from the crate's own combinators only, so they also
+//! serve as examples of how to combine them.
+
+pub mod clike;
+pub mod json;
+mod signed;
+
+pub use signed::{signed_number, SignedNumber};
+
+const DIGIT: &[&str] = &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+const DIGITS: &str = "0123456789";
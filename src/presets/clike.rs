@@ -0,0 +1,189 @@
+//! Tokens of a C-like language: identifiers, keywords, decimal and hex
+//! numbers, strings, characters, operators and comments.
+
+use super::{DIGIT, DIGITS};
+use crate::{Lexeme, Rule};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'a> {
+    Identifier(&'a str),
+    Keyword(&'a str),
+    Number(&'a str),
+    /// A string literal, with its escapes decoded.
+    String(Lexeme<'a>),
+    /// A character literal, with its escapes decoded.
+    Char(Lexeme<'a>),
+    Operator(&'a str),
+    Punctuation(&'a str),
+}
+
+const IDENT_START: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_";
+
+const IDENT_CONTINUE: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_0123456789";
+
+const HEX: &[&str] = &[
+    "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "a", "b", "c", "d", "e", "f", "A", "B", "C",
+    "D", "E", "F",
+];
+
+const HEX_DIGITS: &str = "0123456789abcdefABCDEF";
+
+/// Returns the rule for C-like tokens, skipping whitespace and comments.
+pub fn rule<'a>() -> Rule<'a, Token<'a>> {
+    Rule::Any(&[
+        Rule::Ignore(&Rule::Whitespace),
+        Rule::Ignore(&Rule::Seq(&[
+            Rule::Literal("//"),
+            Rule::Chars(|c| c != '\n'),
+        ])),
+        Rule::Ignore(&Rule::BlockComment {
+            open: "/*",
+            close: "*/",
+            nested: false,
+        }),
+        Rule::IdentifierOrKeyword {
+            ident: &Rule::Seq(&[
+                Rule::Repeat {
+                    rule: &Rule::WhitespaceOf(IDENT_START),
+                    min: 1,
+                    max: Some(1),
+                },
+                Rule::WhitespaceOf(IDENT_CONTINUE),
+            ]),
+            keywords: &[
+                ("break", || Token::Keyword("break")),
+                ("case", || Token::Keyword("case")),
+                ("char", || Token::Keyword("char")),
+                ("const", || Token::Keyword("const")),
+                ("continue", || Token::Keyword("continue")),
+                ("default", || Token::Keyword("default")),
+                ("do", || Token::Keyword("do")),
+                ("double", || Token::Keyword("double")),
+                ("else", || Token::Keyword("else")),
+                ("enum", || Token::Keyword("enum")),
+                ("float", || Token::Keyword("float")),
+                ("for", || Token::Keyword("for")),
+                ("if", || Token::Keyword("if")),
+                ("int", || Token::Keyword("int")),
+                ("long", || Token::Keyword("long")),
+                ("return", || Token::Keyword("return")),
+                ("sizeof", || Token::Keyword("sizeof")),
+                ("static", || Token::Keyword("static")),
+                ("struct", || Token::Keyword("struct")),
+                ("switch", || Token::Keyword("switch")),
+                ("typedef", || Token::Keyword("typedef")),
+                ("void", || Token::Keyword("void")),
+                ("while", || Token::Keyword("while")),
+            ],
+            fallback: Token::Identifier,
+        },
+        Rule::Value(
+            &Rule::Any(&[
+                Rule::Seq(&[
+                    Rule::Literals(&["0x", "0X"]),
+                    Rule::Literals(HEX),
+                    Rule::WhitespaceOf(HEX_DIGITS),
+                ]),
+                Rule::Seq(&[
+                    Rule::Literals(DIGIT),
+                    Rule::WhitespaceOf(DIGITS),
+                    Rule::Repeat {
+                        rule: &Rule::Seq(&[Rule::Literal("."), Rule::WhitespaceOf(DIGITS)]),
+                        min: 0,
+                        max: Some(1),
+                    },
+                ]),
+            ]),
+            &Token::Number,
+        ),
+        Rule::ValueCow(
+            &Rule::Quoted {
+                quote: '"',
+                escape: '\\',
+            },
+            Token::String,
+        ),
+        Rule::ValueCow(
+            &Rule::Quoted {
+                quote: '\'',
+                escape: '\\',
+            },
+            Token::Char,
+        ),
+        Rule::Value(
+            &Rule::Literals(&[
+                "<<=", ">>=", "...", "==", "!=", "<=", ">=", "&&", "||", "++", "--", "+=", "-=",
+                "*=", "/=", "%=", "&=", "|=", "^=", "->", "<<", ">>", "+", "-", "*", "/", "%", "=",
+                "<", ">", "!", "&", "|", "^", "~", "?", ":", ".",
+            ]),
+            &Token::Operator,
+        ),
+        Rule::Value(
+            &Rule::Literals(&["(", ")", "[", "]", "{", "}", ";", ","]),
+            &Token::Punctuation,
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::Lexer;
+
+    #[test]
+    fn tokens() {
+        const INPUT: &str = r#"
+/* block */ int main(void) {
+    // line comment
+    char *s = "a\tb"; x_1 += 0x1F >> 2.5;
+    return s[0] == 'a' ? doubled : -1;
+}
+"#;
+
+        use Token::*;
+
+        let tokens = Lexer::with_buffer(rule(), INPUT)
+            .flatten()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                Keyword("int"),
+                Identifier("main"),
+                Punctuation("("),
+                Keyword("void"),
+                Punctuation(")"),
+                Punctuation("{"),
+                Keyword("char"),
+                Operator("*"),
+                Identifier("s"),
+                Operator("="),
+                String(Lexeme::from("a\tb")),
+                Punctuation(";"),
+                Identifier("x_1"),
+                Operator("+="),
+                Number("0x1F"),
+                Operator(">>"),
+                Number("2.5"),
+                Punctuation(";"),
+                Keyword("return"),
+                Identifier("s"),
+                Punctuation("["),
+                Number("0"),
+                Punctuation("]"),
+                Operator("=="),
+                Char(Lexeme::from("a")),
+                Operator("?"),
+                Identifier("doubled"),
+                Operator(":"),
+                Operator("-"),
+                Number("1"),
+                Punctuation(";"),
+                Punctuation("}"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! JSON tokens as specified by RFC 8259.
+
+use super::{DIGIT, DIGITS};
+use crate::{Lexeme, Rule};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'a> {
+    BeginArray,
+    EndArray,
+    BeginObject,
+    EndObject,
+    NameSeparator,
+    ValueSeparator,
+    /// A string, with its escapes decoded.
+    String(Lexeme<'a>),
+    Number(&'a str),
+    True,
+    False,
+    Null,
+}
+
+const NONZERO_DIGIT: &[&str] = &["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+/// Returns the rule for JSON tokens, skipping insignificant whitespace.
+///
+/// Strings accept any escape character, leaving validation of escapes to the
+/// parser.
+pub fn rule<'a>() -> Rule<'a, Token<'a>> {
+    Rule::Any(&[
+        Rule::Ignore(&Rule::WhitespaceOf(" \t\n\r")),
+        Rule::Value(&Rule::Literal("["), &|_| Token::BeginArray),
+        Rule::Value(&Rule::Literal("]"), &|_| Token::EndArray),
+        Rule::Value(&Rule::Literal("{"), &|_| Token::BeginObject),
+        Rule::Value(&Rule::Literal("}"), &|_| Token::EndObject),
+        Rule::Value(&Rule::Literal(":"), &|_| Token::NameSeparator),
+        Rule::Value(&Rule::Literal(","), &|_| Token::ValueSeparator),
+        Rule::Value(&Rule::Literal("true"), &|_| Token::True),
+        Rule::Value(&Rule::Literal("false"), &|_| Token::False),
+        Rule::Value(&Rule::Literal("null"), &|_| Token::Null),
+        Rule::ValueCow(
+            &Rule::Quoted {
+                quote: '"',
+                escape: '\\',
+            },
+            Token::String,
+        ),
+        Rule::Value(
+            &Rule::Seq(&[
+                Rule::Repeat {
+                    rule: &Rule::Literal("-"),
+                    min: 0,
+                    max: Some(1),
+                },
+                Rule::Either(
+                    &Rule::Literal("0"),
+                    &Rule::Seq(&[Rule::Literals(NONZERO_DIGIT), Rule::WhitespaceOf(DIGITS)]),
+                ),
+                Rule::Repeat {
+                    rule: &Rule::Seq(&[
+                        Rule::Literal("."),
+                        Rule::Literals(DIGIT),
+                        Rule::WhitespaceOf(DIGITS),
+                    ]),
+                    min: 0,
+                    max: Some(1),
+                },
+                Rule::Repeat {
+                    rule: &Rule::Seq(&[
+                        Rule::Literals(&["e", "E"]),
+                        Rule::Repeat {
+                            rule: &Rule::Literals(&["+", "-"]),
+                            min: 0,
+                            max: Some(1),
+                        },
+                        Rule::Literals(DIGIT),
+                        Rule::WhitespaceOf(DIGITS),
+                    ]),
+                    min: 0,
+                    max: Some(1),
+                },
+            ]),
+            &Token::Number,
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{Error, Lexer};
+
+    /// Lexes `input` to the end, returning the tokens if all of it lexed.
+    fn lex(input: &str) -> Option<Vec<Token<'_>>> {
+        let mut lexer = Lexer::with_buffer(rule(), input);
+        let mut tokens = Vec::new();
+
+        loop {
+            match lexer.step() {
+                Ok(Some(token)) => tokens.push(token),
+                Ok(None) => {}
+                // The lexer never looks at the final character, which is
+                // whitespace in every sample.
+                Err(Error::Eof) if lexer.consumed_bytes() + 1 >= input.len() => {
+                    return Some(tokens);
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+
+    // Lexically relevant samples from JSONTestSuite, named as there.
+    const ACCEPT: &[(&str, &str)] = &[
+        ("y_array_empty", "[]"),
+        ("y_array_heterogeneous", "[null, 1, \"1\", {}]"),
+        ("y_number_0e1", "[0e1]"),
+        ("y_number_minus_zero", "[-0]"),
+        ("y_number_negative_int", "[-123]"),
+        ("y_number_real_capital_e_neg_exp", "[1E-2]"),
+        ("y_number_real_fraction_exponent", "[123.456e78]"),
+        ("y_number_real_pos_exponent", "[1e+2]"),
+        ("y_object_basic", "{\"asd\":\"sdf\"}"),
+        ("y_object_empty_key", "{\"\":0}"),
+        (
+            "y_string_allowed_escapes",
+            "[\"\\\"\\\\\\/\\b\\f\\n\\r\\t\"]",
+        ),
+        ("y_string_surrogates_U+1D11E", "[\"\\uD834\\uDd1e\"]"),
+        ("y_string_utf8", "[\"€𝄞\"]"),
+        ("y_string_nonCharacterInUTF-8_U+FFFF", "[\"\u{ffff}\"]"),
+        ("y_string_pi", "[\"π\"]"),
+        ("y_structure_lonely_true", "true"),
+        ("y_structure_whitespace_array", " [] "),
+    ];
+
+    const REJECT: &[(&str, &str)] = &[
+        ("n_incomplete_false", "[fals]"),
+        ("n_incomplete_null", "[nul]"),
+        ("n_incomplete_true", "[tru]"),
+        ("n_number_+1", "[+1]"),
+        ("n_number_.2e-3", "[.2e-3]"),
+        ("n_number_0.e1", "[0.e1]"),
+        ("n_number_1.0e", "[1.0e]"),
+        ("n_number_2.e3", "[2.e3]"),
+        ("n_number_hex_1_digit", "[0x1]"),
+        ("n_number_infinity", "[Infinity]"),
+        ("n_number_minus_sign_with_trailing_garbage", "[-foo]"),
+        ("n_number_NaN", "[NaN]"),
+        ("n_object_single_quote", "{'a':0}"),
+        ("n_string_single_quote", "['single quote']"),
+        ("n_string_unclosed", "[\"asd]"),
+        ("n_structure_capitalized_True", "[True]"),
+    ];
+
+    #[test]
+    fn corpus() {
+        for (name, input) in ACCEPT {
+            assert!(lex(&alloc::format!("{input}\n")).is_some(), "{name}");
+        }
+
+        for (name, input) in REJECT {
+            assert!(lex(&alloc::format!("{input}\n")).is_none(), "{name}");
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            lex("{\"a\\u00e9\": [-1.5e3, true]}\n").unwrap(),
+            [
+                Token::BeginObject,
+                Token::String(Lexeme::from("aé")),
+                Token::NameSeparator,
+                Token::BeginArray,
+                Token::Number("-1.5e3"),
+                Token::ValueSeparator,
+                Token::True,
+                Token::EndArray,
+                Token::EndObject,
+            ]
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Numbers whose leading minus depends on the previous token.
+
+use alloc::boxed::Box;
+
+use super::{DIGIT, DIGITS};
+use crate::{Extract, Lexer, OwnedRule, Rule};
+
+/// The rules and lexer configuration returned by [`signed_number`].
+pub struct SignedNumber<'a, T> {
+    /// Matches a decimal integer with a leading minus, such as `-5`, but only
+    /// where the previous token allows a sign.
+    pub signed: OwnedRule<'a, T>,
+    /// Matches a decimal integer without a sign.
+    pub unsigned: OwnedRule<'a, T>,
+    allows_sign: fn(&T) -> bool,
+}
+
+impl<'a, T> SignedNumber<'a, T> {
+    /// Configures `lexer` to track whether the previous token allows a sign.
+    /// This takes the lexer's gate, see [`Lexer::with_gate`].
+    pub const fn configure<'l>(&self, lexer: Lexer<'l, T>) -> Lexer<'l, T> {
+        lexer.with_gate(self.allows_sign)
+    }
+}
+
+/// Returns rules telling a negative literal apart from a subtraction: `-5`
+/// lexes as one number at the start of the input or after a token
+/// satisfying `allows_sign`, such as `(` or an operator, and as a minus
+/// followed by `5` after any other token, such as an identifier.
+///
+/// Put [`SignedNumber::signed`] before the rule for the minus operator and
+/// [`SignedNumber::unsigned`] anywhere in the top-level `Any`, hand both
+/// numbers to `number`, and pass the lexer through
+/// [`SignedNumber::configure`]. Skipped whitespace doesn't change the
+/// previous token, so `a - 5` and `a-5` lex alike, but a sign must directly
+/// precede its digits: `(- 5)` lexes as a minus followed by `5`.
+pub fn signed_number<'a, T>(
+    allows_sign: fn(&T) -> bool,
+    number: &'a dyn Extract<'a, T>,
+) -> SignedNumber<'a, T> {
+    SignedNumber {
+        signed: OwnedRule::Value(
+            Box::new(OwnedRule::Rule(&Rule::Gated(&Rule::Seq(&[
+                Rule::Literal("-"),
+                Rule::Literals(DIGIT),
+                Rule::WhitespaceOf(DIGITS),
+            ])))),
+            number,
+        ),
+        unsigned: OwnedRule::Value(
+            Box::new(OwnedRule::Rule(&Rule::Seq(&[
+                Rule::Literals(DIGIT),
+                Rule::WhitespaceOf(DIGITS),
+            ]))),
+            number,
+        ),
+        allows_sign,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Expr<'a> {
+        Identifier(&'a str),
+        Number(&'a str),
+        Minus,
+        Open,
+        Close,
+    }
+
+    fn check(input: &str, expected: &[Expr]) {
+        let numbers = signed_number(
+            |token| !matches!(token, Expr::Identifier(_) | Expr::Number(_) | Expr::Close),
+            &Expr::Number,
+        );
+        let rule = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            numbers.signed.as_rule(),
+            numbers.unsigned.as_rule(),
+            Rule::Value(&Rule::Alphabetic, &Expr::Identifier),
+            Rule::Value(&Rule::Literal("-"), &|_| Expr::Minus),
+            Rule::Value(&Rule::Literal("("), &|_| Expr::Open),
+            Rule::Value(&Rule::Literal(")"), &|_| Expr::Close),
+        ]);
+
+        let tokens = numbers
+            .configure(Lexer::with_buffer(rule, input))
+            .flatten()
+            .collect::<Vec<_>>();
+
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn subtraction() {
+        use Expr::*;
+
+        check("a-5\n", &[Identifier("a"), Minus, Number("5")]);
+        check("a - 5\n", &[Identifier("a"), Minus, Number("5")]);
+    }
+
+    #[test]
+    fn negative_literal() {
+        use Expr::*;
+
+        check("(-5)\n", &[Open, Number("-5"), Close]);
+        check("--5\n", &[Minus, Number("-5")]);
+    }
+}
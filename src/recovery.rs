@@ -0,0 +1,200 @@
+use crate::{Lexer, Result};
+
+/// What the lexer does with text that no rule matches, set with
+/// [`Lexer::with_recovery`].
+pub enum RecoveryStrategy<'a, T> {
+    /// Raises [`Error::UnknownToken`](crate::Error::UnknownToken), after
+    /// which iteration stops until the caller recovers past it.
+    Fail,
+    /// Discards the first character of the text, like [`Lexer::skip_char`],
+    /// and lexes on from the next one.
+    SkipChar,
+    /// Discards the first character of the text and every character after it
+    /// up to the first one satisfying the predicate, such as
+    /// `char::is_whitespace`, which is lexed as usual.
+    SkipUntil(fn(char) -> bool),
+    /// Emits the token made from the unmatched run, which ends where a rule
+    /// matches again, so that errors flow through the token stream. The
+    /// token spans the run.
+    Emit(fn(&'a str) -> T),
+}
+
+/// Clones without requiring `T: Clone`, as a strategy only holds callbacks.
+impl<T> Clone for RecoveryStrategy<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for RecoveryStrategy<'_, T> {}
+
+impl<'a, T> Lexer<'a, T> {
+    /// Sets the strategy for text that no rule matches, so that the lexer
+    /// can carry on through broken input, as editors need. Defaults to
+    /// [`RecoveryStrategy::Fail`]. Other errors, such as
+    /// [`Error::Unterminated`](crate::Error::Unterminated), are raised
+    /// under any strategy.
+    pub const fn with_recovery(mut self, recovery: RecoveryStrategy<'a, T>) -> Self {
+        self.recovery = recovery;
+        self
+    }
+
+    /// Recovers from `text`, starting at the committed cursor, not matching,
+    /// following the strategy of the lexer.
+    pub(crate) fn recover(&mut self, text: &'a str) -> Result<'a, Option<T>> {
+        let at = self.index.0;
+
+        match self.recovery {
+            RecoveryStrategy::Fail => return Err(self.unknown_token(text)),
+            RecoveryStrategy::SkipChar => {
+                self.skip_char();
+            }
+            RecoveryStrategy::SkipUntil(stop) => {
+                let first = self.step_len();
+                let len = self.buffer[at + first..]
+                    .find(stop)
+                    .unwrap_or(self.buffer.len() - at - first);
+
+                self.bump(first + len);
+            }
+            RecoveryStrategy::Emit(make) => {
+                let mut end = at + self.step_len();
+
+                while let Some(c) = self.buffer[end..].chars().next() {
+                    if self.active_rule().longest_match(self.buffer, end).is_some() {
+                        break;
+                    }
+
+                    end += c.len_utf8();
+                }
+
+                let text = self.bump(end - at);
+                let token = self.map(make(text), at..end);
+
+                if let Some(token) = &token {
+                    self.follow(token);
+                }
+
+                return Ok(token);
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, Rule};
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Token<'a> {
+        Word(&'a str),
+        Number(&'a str),
+        Garbage(&'a str),
+    }
+
+    const RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+        Rule::Ignore(&Rule::Whitespace),
+        Rule::Value(&Rule::Alphabetic, &Token::Word),
+        Rule::Value(&Rule::Integer, &Token::Number),
+    ]);
+
+    const INPUT: &str = "one @#$ two 2#3 ~~\nthree ?";
+
+    fn lex(recovery: RecoveryStrategy<'static, Token<'static>>) -> Vec<(Token<'static>, usize)> {
+        let mut lexer = Lexer::with_buffer(RULE, INPUT).with_recovery(recovery);
+        let mut tokens = Vec::new();
+
+        while let Some(token) = lexer.next() {
+            tokens.push((token.unwrap(), lexer.span().start));
+        }
+
+        assert!(lexer.is_finished());
+        tokens
+    }
+
+    #[test]
+    fn emit() {
+        let mut lexer =
+            Lexer::with_buffer(RULE, INPUT).with_recovery(RecoveryStrategy::Emit(Token::Garbage));
+        let garbage = lexer
+            .spanned()
+            .filter(|token| matches!(token.value, Token::Garbage(_)))
+            .map(|token| (token.value, token.span))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            garbage,
+            [
+                (Token::Garbage("@#$"), 4..7),
+                (Token::Garbage("#"), 13..14),
+                (Token::Garbage("~~"), 16..18),
+                (Token::Garbage("?"), 25..26),
+            ]
+        );
+    }
+
+    #[test]
+    fn same_valid_tokens() {
+        let valid = [
+            (Token::Word("one"), 0),
+            (Token::Word("two"), 8),
+            (Token::Number("2"), 12),
+            (Token::Number("3"), 14),
+            (Token::Word("three"), 19),
+        ];
+        let strategies = [
+            RecoveryStrategy::SkipChar,
+            RecoveryStrategy::SkipUntil(char::is_whitespace),
+            RecoveryStrategy::Emit(Token::Garbage),
+        ];
+
+        for recovery in strategies {
+            let tokens = lex(recovery)
+                .into_iter()
+                .filter(|(token, _)| !matches!(token, Token::Garbage(_)))
+                .collect::<Vec<_>>();
+
+            match recovery {
+                // Skipping up to whitespace drops the `3` after the `#`.
+                RecoveryStrategy::SkipUntil(_) => {
+                    assert_eq!(tokens, [&valid[..3], &valid[4..]].concat())
+                }
+                _ => assert_eq!(tokens, valid),
+            }
+        }
+
+        let mut lexer = Lexer::with_buffer(RULE, INPUT);
+        let mut tokens = Vec::new();
+
+        while let Some(token) = lexer.next() {
+            match token {
+                Ok(token) => tokens.push((token, lexer.span().start)),
+                Err(Error::UnknownToken { .. }) => {
+                    lexer.skip_char();
+                }
+                Err(error) => panic!("unexpected error: {error:?}"),
+            }
+        }
+
+        assert_eq!(tokens, valid);
+    }
+
+    #[test]
+    fn fail() {
+        let mut lexer = Lexer::with_buffer(RULE, INPUT).with_recovery(RecoveryStrategy::Fail);
+
+        assert!(matches!(lexer.next(), Some(Ok(Token::Word("one")))));
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(Error::UnknownToken {
+                text: "@",
+                at: 4,
+                ..
+            }))
+        ));
+        assert!(lexer.next().is_none());
+    }
+}
@@ -1,8 +1,111 @@
-#[derive(Debug)]
+use core::{
+    fmt,
+    ops::{Range, RangeInclusive},
+};
+
+use crate::ModeAction;
+
+#[cfg(feature = "alloc")]
+use crate::{Lexeme, OwnedRule};
+
+/// A candidate slice together with the buffer it was taken from, for rules
+/// that depend on the surrounding text.
+#[derive(Debug, Clone, Copy)]
+pub struct Context<'a> {
+    buffer: &'a str,
+    start: usize,
+    end: usize,
+    gate: bool,
+    probe: bool,
+}
+
+impl<'a> Context<'a> {
+    /// Creates a context for `buffer[range]`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds or not on character boundaries.
+    pub fn new(buffer: &'a str, range: Range<usize>) -> Self {
+        assert!(buffer.get(range.clone()).is_some(), "invalid context range");
+
+        Self {
+            buffer,
+            start: range.start,
+            end: range.end,
+            gate: true,
+            probe: false,
+        }
+    }
+
+    /// Opens or closes the gate that [`Rule::Gated`] checks.
+    pub const fn with_gate(mut self, open: bool) -> Self {
+        self.gate = open;
+        self
+    }
+
+    /// Marks the context as only checking whether the slice matches, so that
+    /// [`Extract`] callbacks aren't called for tokens that would be dropped.
+    pub(crate) const fn probe(mut self) -> Self {
+        self.probe = true;
+        self
+    }
+
+    /// Returns whether the context only checks whether the slice matches.
+    #[cfg(feature = "alloc")]
+    pub(crate) const fn is_probe(&self) -> bool {
+        self.probe
+    }
+
+    /// Returns whether the gate is open, see [`Lexer::with_gate`](crate::Lexer::with_gate).
+    pub const fn gate(&self) -> bool {
+        self.gate
+    }
+
+    /// Returns the whole buffer.
+    pub const fn buffer(&self) -> &'a str {
+        self.buffer
+    }
+
+    /// Returns the byte range of the candidate slice in the buffer.
+    pub const fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Returns the candidate slice.
+    pub fn value(&self) -> &'a str {
+        &self.buffer[self.start..self.end]
+    }
+
+    /// Returns the text following the candidate slice.
+    pub fn remainder(&self) -> &'a str {
+        &self.buffer[self.end..]
+    }
+}
+
+impl<'a> From<&'a str> for Context<'a> {
+    /// Creates a context without surrounding text.
+    fn from(value: &'a str) -> Self {
+        Self {
+            buffer: value,
+            start: 0,
+            end: value.len(),
+            gate: true,
+            probe: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MatchResult<T> {
     None,
+    /// A full match, carrying the token to emit. `Match(None)` matches
+    /// without a token, such as under [`Rule::Ignore`], and the lexer skips
+    /// the matched text.
     Match(Option<T>),
     PartialMatch,
+    /// A [`Rule::Commit`] matched its prefix but can no longer match.
+    /// Enclosing rules pass it through instead of trying other alternatives.
+    Cut,
 }
 
 impl<T> MatchResult<T> {
@@ -17,145 +120,1768 @@ impl<T> MatchResult<T> {
     pub const fn is_partial_match(&self) -> bool {
         matches!(self, Self::PartialMatch)
     }
+
+    pub const fn is_cut(&self) -> bool {
+        matches!(self, Self::Cut)
+    }
 }
 
+/// The result of [`Rule::match_prefix`], matching the start of a slice rather
+/// than all of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixResult<T> {
+    /// No prefix of the slice matches, nor could a longer slice.
+    None,
+    /// The first `len` bytes are the longest prefix that fully matches,
+    /// carrying the token to emit, as with [`MatchResult::Match`].
+    Match { len: usize, token: Option<T> },
+    /// No prefix fully matches yet, but the whole slice is the start of a
+    /// match.
+    PartialMatch,
+    /// A [`Rule::Commit`] cut, see [`MatchResult::Cut`].
+    Cut,
+}
+
+impl<T> PrefixResult<T> {
+    pub const fn is_none(&self) -> bool {
+        matches!(self, Self::None)
+    }
+
+    pub const fn is_match(&self) -> bool {
+        matches!(self, Self::Match { .. })
+    }
+
+    pub const fn is_partial_match(&self) -> bool {
+        matches!(self, Self::PartialMatch)
+    }
+
+    pub const fn is_cut(&self) -> bool {
+        matches!(self, Self::Cut)
+    }
+
+    /// Returns the length of the matched prefix.
+    pub const fn matched_len(&self) -> Option<usize> {
+        match self {
+            Self::Match { len, .. } => Some(*len),
+            _ => None,
+        }
+    }
+}
+
+/// A keyword and the token it produces, for [`Rule::Keywords`] and
+/// [`Rule::IdentifierOrKeyword`].
+pub type Keyword<'a, T> = (&'a str, fn() -> T);
+
+/// Makes a token out of a matched slice for [`Rule::Value`] and [`Rule::All`].
+///
+/// Implemented for every `Fn(&str) -> T + Sync`, so both function pointers
+/// and closures capturing an interner or a counter work; the closure must be
+/// `Sync` so that rules can live in statics and be shared with other threads.
+/// While lexing, it is only called for the slice that is committed, not for
+/// the shorter slices tried before it.
+pub trait Extract<'a, T>: Fn(&'a str) -> T + Sync {}
+
+impl<'a, T, F: Fn(&'a str) -> T + Sync> Extract<'a, T> for F {}
+
+impl<T> fmt::Debug for dyn Extract<'_, T> + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("Extract(..)")
+    }
+}
+
+/// Splits a lexeme into tokens for [`Rule::ValueMany`], passing each token to
+/// the emitter along with the byte range of its piece of the lexeme.
+pub type Split<'a, T> = fn(&'a str, &mut dyn FnMut(Range<usize>, T));
+
 /// Represents a rule for text matching.
+///
+/// Rules compare equal when they have the same shape, literals and callbacks;
+/// callbacks are compared by function pointer, or by reference for
+/// [`Extract`] callbacks, so the same function compiled into two places may
+/// compare unequal.
 #[derive(Debug)]
-pub enum Rule<'a, T: core::fmt::Debug> {
+pub enum Rule<'a, T> {
     /// Matches if equal to the provided literal.
     Literal(&'a str),
+    /// Matches if equal to the provided literal ignoring case, such as
+    /// `select`, `SELECT` or `Select` for `LiteralIgnoreCase("select")`,
+    /// and partially matches a prefix of it ignoring case. Characters are
+    /// compared by their lowercase mapping rather than full case folding,
+    /// so `ß` doesn't match `SS`, while `ẞ` matches `ß`. Pair it with
+    /// [`Rule::Value`] to get the slice as written.
+    LiteralIgnoreCase(&'a str),
+    /// Matches if equal to one of the provided literals, like an `Any` of
+    /// literals.
+    Literals(&'a [&'a str]),
+    /// Matches any of the keywords, which must be sorted by keyword,
+    /// producing its token. Looks keywords up by binary search, so large
+    /// tables stay fast without allocating.
+    Keywords(&'a [Keyword<'a, T>]),
     /// Matches if all characters are numeric.
     Numeric,
+    /// Matches a decimal integer of ASCII digits, such as `42` or `007`.
+    /// Pair it with [`Rule::Value`] to parse the slice, and with
+    /// [`Rule::Signed`] to accept a sign.
+    Integer,
+    /// Matches a decimal number with a fraction, an exponent or both, such
+    /// as `3.14`, `1e-9` or `2.5E+3`. Partially matches an integer, or one
+    /// ending with `.`, `e` or a signed `e`, as digits may still follow. A
+    /// dot without a digit after it never matches, so `3.` lexes as `3`
+    /// followed by a dot and `1..2` keeps its range operator, given rules
+    /// that match the integer and the dots.
+    Float,
+    /// Matches `0x` or `0X` followed by hexadecimal digits in either case,
+    /// such as `0xFF`, partially matching `0` and `0x`.
+    HexInteger,
+    /// Matches any of [`Rule::HexInteger`], [`Rule::Float`] and
+    /// [`Rule::Integer`], so that `3` is an integer while `3.5` continues
+    /// into a float.
+    Number,
+    /// Matches like the provided rule, after an optional leading `+` or `-`.
+    /// The sign is part of the slice, so `Value(&Signed(&Number), ..)` hands
+    /// it to the callback. Lexing `a-1` with it yields `a` and `-1` rather
+    /// than a subtraction, so most grammars lex the sign as an operator and
+    /// leave it to the parser instead.
+    Signed(&'a Rule<'a, T>),
     /// Matches if all characters are alphabetic.
     Alphabetic,
     /// Matches if all characters are whitespace.
     Whitespace,
+    /// Matches if all characters are among the provided ones, which take the
+    /// place of Unicode whitespace. `WhitespaceOf(" \t\r\n")` rejects no-break
+    /// spaces, while `WhitespaceOf(" \t;")` also skips semicolons.
+    WhitespaceOf(&'a str),
+    /// Matches if all characters satisfy the predicate, such as
+    /// `Chars(|c| c.is_alphanumeric() || c == '_')` for identifiers.
+    Chars(fn(char) -> bool),
+    /// Matches if all characters are in the range, see [`Rule::char_range`].
+    CharRange(RangeInclusive<char>),
+    /// Matches if the value is a single extended grapheme cluster.
+    #[cfg(feature = "unicode-segmentation")]
+    Grapheme,
     /// Value extraction if matching the provided rule.
-    Value(&'a Rule<'a, T>, fn(&'a str) -> T),
-    /// Ignores if the provided rule matches.
+    Value(&'a Rule<'a, T>, &'a dyn Extract<'a, T>),
+    /// Value extraction if matching the provided rule, unless the callback
+    /// rejects the slice by returning `None`, which turns the match into
+    /// [`MatchResult::None`] so that later alternatives of an enclosing `Any`
+    /// can claim it. The callback only sees full matches of the provided
+    /// rule, so wrapping a rule that keeps partially matching while its
+    /// token continues, such as [`Rule::IdentifierOrKeyword`], defers the
+    /// decision to the slice that would be committed.
+    ValueFilter(&'a Rule<'a, T>, fn(&'a str) -> Option<T>),
+    /// Value extraction if matching the provided rule, splitting the slice
+    /// into several tokens, such as `>` `>` for a `>>` that closes two
+    /// template argument lists. The callback emits each token along with the
+    /// byte range of its piece, relative to the start of the slice, which
+    /// gives the token its span; ranges reaching past the end of the slice
+    /// are cut short at it. The lexer returns the tokens in order before
+    /// lexing any further, queuing them in the push-back slots, so emitting
+    /// more than [`PUSH_BACK_LIMIT`](crate::PUSH_BACK_LIMIT) tokens raises
+    /// [`Error::TooManyTokens`](crate::Error::TooManyTokens) instead.
+    /// Emitting nothing skips the slice.
+    ///
+    /// The callback may be called several times for the same slice, so it
+    /// should not have side effects.
+    ValueMany(&'a Rule<'a, T>, Split<'a, T>),
+    /// Value extraction if matching the provided rule, handing the callback
+    /// a [`Lexeme`] instead of the raw slice. [`Rule::Quoted`], and
+    /// [`Rule::Delimited`] with an escape, hand over their decoded contents,
+    /// borrowed unless they contain escapes; any other rule hands over the
+    /// matched slice.
+    #[cfg(feature = "alloc")]
+    ValueCow(&'a Rule<'a, T>, fn(Lexeme<'a>) -> T),
+    /// Matches like the provided rule, discarding any token it produces, so
+    /// that the lexer skips the matched text.
     Ignore(&'a Rule<'a, T>),
-    /// Matches if the ending matches provided literal.
+    /// Matches a string delimited by `quote`, in which `escape` makes the
+    /// next character part of the string.
+    Quoted { quote: char, escape: char },
+    /// Matches a comment that starts with `open` and runs to the end of the
+    /// line, such as `"//"` or `"--"`, without the line break. A comment on
+    /// the last line runs to the end of the buffer.
+    LineComment(&'a str),
+    /// Matches a comment that starts with `open` and ends with `close`. If
+    /// `nested`, each inner `open` needs its own `close`.
+    BlockComment {
+        open: &'a str,
+        close: &'a str,
+        nested: bool,
+    },
+    /// Matches text that starts with `open` and ends with the first `close`
+    /// after it that `escape`, if any, doesn't precede, such as strings with
+    /// multi-character quotes. The slice spans both delimiters.
+    Delimited {
+        open: &'a str,
+        close: &'a str,
+        escape: Option<char>,
+    },
+    /// Matches `marker` followed by a line break and any spaces or tabs that
+    /// indent the next line, such as a backslash at the end of a line in C.
+    /// Use it under [`Rule::Ignore`] to join the lines; the line break never
+    /// inserts an automatic terminator.
+    LineContinuation { marker: &'a str },
+    /// Matches if the slice ends with the provided literal and doesn't
+    /// contain it earlier, such as a line comment ending with `"\n"`, and
+    /// partially matches until the literal appears.
     EndsWith(&'a str),
-    /// Matches if the provided rule doesn't match.
+    /// Matches if the slice starts with the provided literal, and partially
+    /// matches a prefix of it. Anything may follow the literal, so combine it
+    /// with other rules to bound the slice.
+    StartsWith(&'a str),
+    /// Matches if the provided rule doesn't match, including while it only
+    /// partially matches. Partially matches when the provided rule matches,
+    /// as a longer slice may no longer match it.
     Not(&'a Rule<'a, T>),
+    /// Matches like the first rule, unless the second one also fully
+    /// matches the slice, so `Except(&Alphabetic, &Literals(&["end", "if"]))`
+    /// matches words other than the two keywords. Returns a partial match
+    /// instead while the first rule matches past the slice, as a longer
+    /// slice such as `ending` may no longer match the second one.
+    Except(&'a Rule<'a, T>, &'a Rule<'a, T>),
     /// Matches if only the provided rule matches.
     Only(&'a Rule<'a, T>),
-    /// Matches if both of the provided rules match.
+    /// Matches if both of the provided rules match, and partially matches if
+    /// each of them either matches or partially matches.
     Both(&'a Rule<'a, T>, &'a Rule<'a, T>),
-    /// Matches if either of the provided rules match.
+    /// Matches if either of the provided rules match, and otherwise partially
+    /// matches if either of them partially matches.
     Either(&'a Rule<'a, T>, &'a Rule<'a, T>),
     /// Matches if all of the provided rules match.
-    All(&'a [Rule<'a, T>], fn(&'a str) -> T),
-    /// Matches if any of the provided rules match.
+    All(&'a [Rule<'a, T>], &'a dyn Extract<'a, T>),
+    /// Matches if the slice splits into consecutive parts, each matching the
+    /// respective rule. Parts may be empty, and an empty part matches any
+    /// rule that would match the empty slice, so a [`Rule::Repeat`] with a
+    /// `min` of zero and a `max` of one makes a part optional.
+    ///
+    /// Each part takes the longest prefix its rule fully matches, extended
+    /// like the lexer extends a token, without backtracking into shorter
+    /// ones, so matching stays linear in the number of parts. A part
+    /// therefore can't leave text for a later part that it could match
+    /// itself: `Seq(&[Alphabetic, Literal("x")])` never matches, as the
+    /// `Alphabetic` part takes the `x`. Partially matches while the parts
+    /// matched so far can still be followed by the rest.
+    Seq(&'a [Rule<'a, T>]),
+    /// Matches if the slice splits into at least `min` and at most `max`
+    /// consecutive, non-empty parts, each matching `rule`, such as the
+    /// digits of a number with `Literals` of single digits. With `min` of
+    /// zero, it matches the empty slice as part of a [`Rule::Seq`].
+    ///
+    /// Like the parts of a `Seq`, each repetition takes the longest prefix
+    /// that `rule` fully matches, without backtracking.
+    Repeat {
+        rule: &'a Rule<'a, T>,
+        min: usize,
+        max: Option<usize>,
+    },
+    /// Matches if any of the provided rules match. Among the rules that
+    /// fully match, the one with the highest [`Rule::Priority`] decides the
+    /// token, the first one in declaration order breaking ties; rules that
+    /// only partially match can't change it. Otherwise returns a partial
+    /// match if any rule partially matches.
+    ///
+    /// As the lexer keeps the longest slice that matches, the order only
+    /// matters between rules matching the same slice: list keywords before
+    /// identifiers so that `in` is a keyword, while `inner` is still lexed
+    /// as an identifier.
     Any(&'a [Rule<'a, T>]),
+    /// Matches like the provided rule, ranking it above the alternatives of
+    /// the enclosing [`Rule::Any`] with a lower priority when several of
+    /// them fully match the same slice, such as keywords listed after
+    /// identifiers. Rules without one have priority 0. The priority shows
+    /// through `Value`, `Ignore` and `Only`, but not through rules combining
+    /// several alternatives.
+    ///
+    /// Priorities only break ties: the lexer still keeps the longest slice
+    /// that matches, so `Priority(1, &Literal("in"))` doesn't lex `inner`.
+    Priority(u8, &'a Rule<'a, T>),
+    /// Matches like `main`, but only if `trail` fully matches some prefix of
+    /// the text that follows, which is not consumed. Returns a partial match
+    /// while `main` matches without the trailing context, as a longer slice
+    /// may still be followed by it. Without surrounding text, as with
+    /// [`Rule::matches`], it never fully matches.
+    TrailingContext {
+        main: &'a Rule<'a, T>,
+        trail: &'a Rule<'a, T>,
+    },
+    /// Matches like `ident`, then classifies the longest identifier through
+    /// `keywords`, which must be sorted by keyword, falling back to
+    /// `fallback` for anything that isn't a keyword. Returns a partial match
+    /// while the identifier continues past the candidate slice, so that
+    /// `android` is never split into `and` and `roid`, checking only the
+    /// next character. The keyword is only looked up for the slice that is
+    /// committed.
+    IdentifierOrKeyword {
+        ident: &'a Rule<'a, T>,
+        keywords: &'a [Keyword<'a, T>],
+        fallback: fn(&'a str) -> T,
+    },
+    /// Matches like the provided rule, but only if the candidate slice starts
+    /// the buffer or follows a line break.
+    AtLineStart(&'a Rule<'a, T>),
+    /// Matches like the provided rule, but only while the gate of the
+    /// context is open, which [`Lexer::with_gate`](crate::Lexer::with_gate)
+    /// ties to the previous token.
+    Gated(&'a Rule<'a, T>),
+    /// Matches like the provided rule. Once the lexer commits a slice that
+    /// this rule decides, it switches to the named mode of
+    /// [`Lexer::with_modes`](crate::Lexer::with_modes) before the next step.
+    PushMode(&'a Rule<'a, T>, &'a str),
+    /// Matches like the provided rule. Once the lexer commits a slice that
+    /// this rule decides, it returns to the mode that was active before the
+    /// latest [`Rule::PushMode`].
+    PopMode(&'a Rule<'a, T>),
+    /// Hands the text after `open` to `lex`, for languages embedded in
+    /// others. `lex` receives the buffer and the span of the opening
+    /// delimiter, and returns the token to emit along with the number of
+    /// bytes it consumed after the delimiter, for example by running its own
+    /// [`Lexer`](crate::Lexer) over the remainder. Matches the delimiter and
+    /// the consumed bytes as a whole.
+    ///
+    /// Once the delimiter matches, the lexer calls `lex` and skips to the end
+    /// of the region rather than growing the slice through it, calling `lex`
+    /// a few more times to pick the token. It should not have side effects.
+    /// The lexer only skips for an `Embed` that it reaches through wrappers
+    /// and alternatives, not for one in a part of a sequence.
+    Embed {
+        open: &'a Rule<'a, T>,
+        lex: fn(&'a str, Range<usize>) -> (T, usize),
+    },
+    /// Matches like `rule`, but once the candidate slice starts with a
+    /// match of `prefix`, failing to match is final: the result becomes
+    /// [`MatchResult::Cut`], which stops an enclosing `Any` from trying its
+    /// other alternatives and makes the lexer report
+    /// [`Error::Committed`](crate::Error::Committed) with `tag`, such as
+    /// `"string"`, naming what was being lexed.
+    Commit {
+        tag: &'a str,
+        prefix: &'a Rule<'a, T>,
+        rule: &'a Rule<'a, T>,
+    },
+    /// Matches if the provided owned rule matches.
+    #[cfg(feature = "alloc")]
+    Owned(&'a OwnedRule<'a, T>),
 }
 
-impl<'a, T: core::fmt::Debug> Rule<'a, T> {
+impl<'a, T> Rule<'a, T> {
+    /// Matches if all characters are among `chars`, such as `"+-*/%"` for
+    /// operators. Same as [`Rule::WhitespaceOf`], under a name that reads
+    /// better outside of whitespace.
+    pub const fn char_in(chars: &'a str) -> Self {
+        Self::WhitespaceOf(chars)
+    }
+
+    /// Matches if all characters are in `range`, such as `'a'..='f'`.
+    pub const fn char_range(range: RangeInclusive<char>) -> Self {
+        Self::CharRange(range)
+    }
+
+    /// Matches `literal`, for starting a chain of [`RuleExt`](crate::RuleExt)
+    /// combinators.
+    pub const fn literal(literal: &'a str) -> Self {
+        Self::Literal(literal)
+    }
+
     pub fn matches(&self, value: &'a str) -> MatchResult<T> {
+        self.matches_in(Context::from(value))
+    }
+
+    /// Matches the candidate slice of `cx`, letting context-dependent rules
+    /// inspect the surrounding text.
+    ///
+    /// The empty slice never matches: rules that would match it report a
+    /// partial match instead, as a token always spans at least one
+    /// character.
+    pub fn matches_in(&self, cx: Context<'a>) -> MatchResult<T> {
+        match self.eval(cx) {
+            MatchResult::Match(_) if cx.range().is_empty() => MatchResult::PartialMatch,
+            result => result,
+        }
+    }
+
+    /// Matches the longest prefix of `value` that fully matches, the way the
+    /// lexer picks a token out of the rest of the buffer, so that `a` can be
+    /// matched out of `a+b` without caring what follows it. The slice is
+    /// extended one character at a time for as long as the rule reports a
+    /// match or partial match, and the longest match is evaluated again on
+    /// its own to make the token.
+    pub fn match_prefix(&self, value: &'a str) -> PrefixResult<T> {
+        let mut last = None;
+        let mut open = true;
+
+        for (offset, c) in value.char_indices() {
+            let end = offset + c.len_utf8();
+
+            match self.matches_in(Context::new(value, 0..end).probe()) {
+                MatchResult::None => {
+                    open = false;
+                    break;
+                }
+                MatchResult::Cut => return PrefixResult::Cut,
+                MatchResult::Match(_) => last = Some(end),
+                MatchResult::PartialMatch => {}
+            }
+        }
+
+        match last {
+            Some(len) => PrefixResult::Match {
+                len,
+                token: match self.matches_in(Context::new(value, 0..len)) {
+                    MatchResult::Match(token) => token,
+                    _ => None,
+                },
+            },
+            None if open => PrefixResult::PartialMatch,
+            None => PrefixResult::None,
+        }
+    }
+
+    /// Matches like [`Rule::matches_in`], except that the empty slice can
+    /// match, as the parts of a [`Rule::Seq`] can be empty.
+    pub(crate) fn eval(&self, cx: Context<'a>) -> MatchResult<T> {
+        let value = cx.value();
+
         match self {
-            Self::Literal(literal) => value
-                .eq(*literal)
-                .then_some(MatchResult::Match(None))
-                .unwrap_or_else(|| {
-                    literal
-                        .starts_with(value)
-                        .then_some(MatchResult::PartialMatch)
-                        .unwrap_or(MatchResult::None)
-                }),
-            Self::Numeric => value
-                .chars()
-                .all(|c| c.is_numeric())
-                .then_some(MatchResult::Match(None))
-                .unwrap_or(MatchResult::None),
-            Self::Alphabetic => value
-                .chars()
-                .all(|c| c.is_alphabetic())
-                .then_some(MatchResult::Match(None))
-                .unwrap_or(MatchResult::None),
-            Self::Whitespace => value
-                .chars()
-                .all(|c| c.is_whitespace())
-                .then_some(MatchResult::Match(None))
-                .unwrap_or(MatchResult::None),
-            Self::Value(rule, out) => rule
-                .matches(value)
-                .is_match()
-                .then_some(MatchResult::Match(Some(out(value))))
-                .unwrap_or_else(|| {
-                    rule.matches(value)
-                        .is_partial_match()
-                        .then_some(MatchResult::PartialMatch)
-                        .unwrap_or(MatchResult::None)
-                }),
-            Self::Ignore(rule) => rule.matches(value),
-            Self::EndsWith(literal) => value
-                .ends_with(literal)
-                .then_some(MatchResult::Match(None))
-                .unwrap_or(MatchResult::None),
-            Self::Not(rule) => rule
-                .matches(value)
-                .is_none()
-                .then_some(MatchResult::Match(None))
-                .unwrap_or(MatchResult::None),
-            Self::Only(rule) => rule.matches(value),
-            Self::Both(a, b) => a
-                .matches(value)
-                .is_match()
-                .then_some(
-                    b.matches(value)
-                        .is_match()
-                        .then_some(MatchResult::Match(None))
-                        .unwrap_or(MatchResult::None),
-                )
-                .unwrap_or(MatchResult::None),
-            Self::Either(a, b) => a
-                .matches(value)
-                .is_match()
-                .then_some(MatchResult::Match(None))
-                .unwrap_or_else(|| {
-                    a.matches(value)
-                        .is_partial_match()
-                        .then_some(MatchResult::PartialMatch)
-                        .unwrap_or_else(|| {
-                            b.matches(value)
-                                .is_match()
-                                .then_some(MatchResult::Match(None))
-                                .unwrap_or_else(|| {
-                                    b.matches(value)
-                                        .is_partial_match()
-                                        .then_some(MatchResult::PartialMatch)
-                                        .unwrap_or(MatchResult::None)
-                                })
-                        })
+            Self::Literal(literal) => self::literal(literal, value),
+            Self::LiteralIgnoreCase(literal) => self::literal_ignore_case(literal, value),
+            Self::Literals(literals) => self::literals(literals.iter().copied(), value),
+            Self::Keywords(keywords) => self::keywords(keywords, value),
+            Self::Numeric => {
+                if all_chars(value, |byte| byte.is_ascii_digit(), char::is_numeric) {
+                    MatchResult::Match(None)
+                } else {
+                    MatchResult::None
+                }
+            }
+            Self::Integer => crate::number::integer(value),
+            Self::Float => crate::number::float(value),
+            Self::HexInteger => crate::number::hex_integer(value),
+            Self::Number => crate::number::number(value),
+            Self::Signed(rule) => match value.starts_with(['+', '-']) {
+                true => rule.eval(Context {
+                    start: cx.start + 1,
+                    ..cx
                 }),
+                false => rule.eval(cx),
+            },
+            Self::Alphabetic => {
+                if all_chars(
+                    value,
+                    |byte| byte.is_ascii_alphabetic(),
+                    char::is_alphabetic,
+                ) {
+                    MatchResult::Match(None)
+                } else {
+                    MatchResult::None
+                }
+            }
+            Self::Whitespace => {
+                if all_chars(value, is_ascii_whitespace, char::is_whitespace) {
+                    MatchResult::Match(None)
+                } else {
+                    MatchResult::None
+                }
+            }
+            Self::WhitespaceOf(whitespace) => {
+                if value.chars().all(|c| whitespace.contains(c)) {
+                    MatchResult::Match(None)
+                } else {
+                    MatchResult::None
+                }
+            }
+            Self::Chars(predicate) => {
+                if value.chars().all(predicate) {
+                    MatchResult::Match(None)
+                } else {
+                    MatchResult::None
+                }
+            }
+            Self::CharRange(range) => {
+                if value.chars().all(|c| range.contains(&c)) {
+                    MatchResult::Match(None)
+                } else {
+                    MatchResult::None
+                }
+            }
+            #[cfg(feature = "unicode-segmentation")]
+            Self::Grapheme => {
+                use unicode_segmentation::UnicodeSegmentation;
+
+                let mut graphemes = value.graphemes(true);
+
+                if graphemes.next().is_some() && graphemes.next().is_none() {
+                    MatchResult::Match(None)
+                } else {
+                    MatchResult::None
+                }
+            }
+            Self::Value(rule, out) => self::value(rule.eval(cx), out, cx),
+            Self::ValueFilter(rule, out) => match rule.eval(cx) {
+                MatchResult::Match(_) => out(value)
+                    .map(|token| MatchResult::Match(Some(token)))
+                    .unwrap_or(MatchResult::None),
+                result => result,
+            },
+            Self::ValueMany(rule, out) => match rule.eval(cx) {
+                MatchResult::Match(_) => {
+                    let mut first = None;
+                    out(value, &mut |_, token| {
+                        first.get_or_insert(token);
+                    });
+
+                    MatchResult::Match(first)
+                }
+                result => result,
+            },
+            #[cfg(feature = "alloc")]
+            Self::ValueCow(rule, out) => match rule.eval(cx) {
+                MatchResult::Match(_) => MatchResult::Match(Some(out(rule.lexeme(value)))),
+                MatchResult::None => MatchResult::None,
+                MatchResult::PartialMatch => MatchResult::PartialMatch,
+                MatchResult::Cut => MatchResult::Cut,
+            },
+            Self::Ignore(rule) => self::ignore(rule.eval(cx.probe())),
+            Self::Quoted { quote, escape } => self::quoted(*quote, *escape, value),
+            Self::LineComment(open) => self::line_comment(open, value),
+            Self::BlockComment {
+                open,
+                close,
+                nested,
+            } => self::block_comment(open, close, *nested, value),
+            Self::Delimited {
+                open,
+                close,
+                escape,
+            } => self::delimited(open, close, *escape, value),
+            Self::LineContinuation { marker } => self::line_continuation(marker, value),
+            Self::EndsWith(literal) => self::ends_with(literal, value),
+            Self::StartsWith(literal) => {
+                if value.starts_with(literal) {
+                    MatchResult::Match(None)
+                } else if literal.starts_with(value) {
+                    MatchResult::PartialMatch
+                } else {
+                    MatchResult::None
+                }
+            }
+            Self::Not(rule) => self::not(rule.eval(cx.probe())),
+            Self::Except(a, b) => match a.eval(cx) {
+                MatchResult::Match(_) if b.eval(cx.probe()).is_match() => {
+                    match a.longest_match(cx.buffer, cx.start) > Some(cx.end) {
+                        true => MatchResult::PartialMatch,
+                        false => MatchResult::None,
+                    }
+                }
+                result => result,
+            },
+            Self::Only(rule) => rule.eval(cx),
+            Self::Both(a, b) => self::both(a.eval(cx.probe()), || b.eval(cx.probe())),
+            Self::Either(a, b) => self::either(a.eval(cx.probe()), || b.eval(cx.probe())),
             Self::All(rules, out) => {
-                for rule in *rules {
-                    match rule.matches(value) {
-                        MatchResult::None => return MatchResult::None,
-                        MatchResult::PartialMatch => return MatchResult::PartialMatch,
-                        _ => {}
+                self::all(rules.iter().map(|rule| rule.eval(cx.probe())), out, cx)
+            }
+            Self::Any(rules) => self::ranked(
+                rules
+                    .iter()
+                    .map(|rule| (rule.priority(), move || rule.eval(cx))),
+            ),
+            Self::Priority(_, rule) => rule.eval(cx),
+            Self::Seq(rules) => self::seq(rules, cx),
+            Self::Repeat { rule, min, max } => self::repeat(*rule, 0, *min, *max, cx.probe()),
+            Self::TrailingContext { main, trail } => match main.eval(cx) {
+                MatchResult::Match(token) => {
+                    if trail.longest_match(cx.buffer, cx.end).is_some() {
+                        MatchResult::Match(token)
+                    } else {
+                        MatchResult::PartialMatch
                     }
                 }
-
-                MatchResult::Match(Some(out(value)))
+                result => result,
+            },
+            Self::IdentifierOrKeyword {
+                ident,
+                keywords,
+                fallback,
+            } => match ident.eval(cx.probe()) {
+                MatchResult::Match(_) if self::continues(*ident, cx) => MatchResult::PartialMatch,
+                MatchResult::Match(_) if cx.probe => MatchResult::Match(None),
+                MatchResult::Match(_) => MatchResult::Match(Some(
+                    keywords
+                        .binary_search_by(|(keyword, _)| (*keyword).cmp(value))
+                        .map_or_else(|_| fallback(value), |index| keywords[index].1()),
+                )),
+                result => result,
+            },
+            Self::AtLineStart(rule) => {
+                if cx.buffer[..cx.start]
+                    .chars()
+                    .next_back()
+                    .is_none_or(|c| c == '\n' || c == '\r')
+                {
+                    rule.eval(cx)
+                } else {
+                    MatchResult::None
+                }
+            }
+            Self::Gated(rule) => {
+                if cx.gate {
+                    rule.eval(cx)
+                } else {
+                    MatchResult::None
+                }
             }
-            Self::Any(rules) => {
-                let mut matches = 0;
+            Self::PushMode(rule, _) | Self::PopMode(rule) => rule.eval(cx),
+            Self::Embed { open, lex } => match open.longest_match(cx.buffer, cx.start) {
+                Some(end) if end <= cx.end => {
+                    let (token, consumed) = lex(cx.buffer, cx.start..end);
 
-                for rule in *rules {
-                    match rule.matches(value) {
-                        MatchResult::None => {}
-                        MatchResult::Match(token) => {
-                            if matches == 0 {
-                                return MatchResult::Match(token);
-                            }
-                        }
-                        MatchResult::PartialMatch => matches += 1,
+                    match (end + consumed).cmp(&cx.end) {
+                        core::cmp::Ordering::Equal => MatchResult::Match(Some(token)),
+                        core::cmp::Ordering::Greater => MatchResult::PartialMatch,
+                        core::cmp::Ordering::Less => MatchResult::None,
                     }
                 }
+                _ => match open.eval(cx) {
+                    MatchResult::None => MatchResult::None,
+                    _ => MatchResult::PartialMatch,
+                },
+            },
+            Self::Commit { prefix, rule, .. } => match rule.eval(cx) {
+                MatchResult::None
+                    if prefix.longest_match(value, 0).is_some()
+                        && rule.longest_match(value, 0).is_none() =>
+                {
+                    MatchResult::Cut
+                }
+                result => result,
+            },
+            #[cfg(feature = "alloc")]
+            Self::Owned(rule) => rule.eval(cx),
+        }
+    }
+
+    /// Returns the length of the opening delimiter of a delimited rule, such
+    /// as [`Rule::Quoted`] or [`Rule::BlockComment`], that is still waiting
+    /// for its closing delimiter at the end of `cx`.
+    pub(crate) fn unterminated(&self, cx: Context<'a>) -> Option<usize> {
+        match self {
+            Self::Quoted { quote, .. } => self
+                .matches_in(cx)
+                .is_partial_match()
+                .then_some(quote.len_utf8()),
+            Self::BlockComment { open, .. } | Self::Delimited { open, .. } => {
+                (cx.value().len() >= open.len() && self.matches_in(cx).is_partial_match())
+                    .then_some(open.len())
+            }
+            Self::Value(rule, _)
+            | Self::ValueFilter(rule, _)
+            | Self::ValueMany(rule, _)
+            | Self::Ignore(rule)
+            | Self::Only(rule)
+            | Self::Priority(_, rule)
+            | Self::AtLineStart(rule)
+            | Self::Gated(rule)
+            | Self::PushMode(rule, _)
+            | Self::PopMode(rule)
+            | Self::Commit { rule, .. } => rule.unterminated(cx),
+            #[cfg(feature = "alloc")]
+            Self::ValueCow(rule, _) => rule.unterminated(cx),
+            Self::Either(a, b) => a.unterminated(cx).or_else(|| b.unterminated(cx)),
+            Self::Any(rules) => rules.iter().find_map(|rule| rule.unterminated(cx)),
+            _ => None,
+        }
+    }
+
+    /// Returns the callback of the [`Rule::ValueMany`] that decides the token
+    /// for `cx`, which has to fully match.
+    pub(crate) fn split(&self, cx: Context<'a>) -> Option<Split<'a, T>> {
+        match self {
+            Self::ValueMany(_, out) => Some(*out),
+            Self::Only(rule)
+            | Self::Priority(_, rule)
+            | Self::AtLineStart(rule)
+            | Self::Gated(rule)
+            | Self::PushMode(rule, _)
+            | Self::PopMode(rule)
+            | Self::Commit { rule, .. }
+            | Self::TrailingContext { main: rule, .. } => rule.split(cx),
+            Self::Any(rules) => rules[self::decider(rules, cx)?].split(cx),
+            _ => None,
+        }
+    }
+
+    /// Returns the change of mode carried by the rule that decides the token
+    /// for `cx`, which has to fully match.
+    pub(crate) fn mode_action(&self, cx: Context<'a>) -> Option<ModeAction<'a>> {
+        match self {
+            Self::PushMode(_, mode) => Some(ModeAction::Push(mode)),
+            Self::PopMode(_) => Some(ModeAction::Pop),
+            Self::Value(rule, _)
+            | Self::ValueFilter(rule, _)
+            | Self::ValueMany(rule, _)
+            | Self::Ignore(rule)
+            | Self::Only(rule)
+            | Self::Priority(_, rule)
+            | Self::AtLineStart(rule)
+            | Self::Gated(rule)
+            | Self::Commit { rule, .. }
+            | Self::TrailingContext { main: rule, .. } => rule.mode_action(cx),
+            #[cfg(feature = "alloc")]
+            Self::ValueCow(rule, _) => rule.mode_action(cx),
+            Self::Any(rules) => rules[self::decider(rules, cx)?].mode_action(cx),
+            _ => None,
+        }
+    }
+
+    /// Returns the tag of the [`Rule::Commit`] that cuts `cx`, if it is
+    /// found through wrappers and alternatives rather than in a part of a
+    /// sequence.
+    pub(crate) fn cut(&self, cx: Context<'a>) -> Option<&'a str> {
+        match self {
+            Self::Commit { tag, rule, .. } => rule
+                .cut(cx)
+                .or_else(|| self.eval(cx.probe()).is_cut().then_some(*tag)),
+            Self::Value(rule, _)
+            | Self::ValueFilter(rule, _)
+            | Self::ValueMany(rule, _)
+            | Self::Ignore(rule)
+            | Self::Only(rule)
+            | Self::Priority(_, rule)
+            | Self::AtLineStart(rule)
+            | Self::Gated(rule)
+            | Self::PushMode(rule, _)
+            | Self::PopMode(rule)
+            | Self::TrailingContext { main: rule, .. } => rule.cut(cx),
+            #[cfg(feature = "alloc")]
+            Self::ValueCow(rule, _) => rule.cut(cx),
+            Self::Any(rules) => rules.iter().find_map(|rule| rule.cut(cx)),
+            _ => None,
+        }
+    }
+
+    /// Returns the end of the region of the [`Rule::Embed`] whose opening
+    /// delimiter is exactly the slice of `cx`, if it is found through
+    /// wrappers and alternatives like for [`Rule::cut`].
+    pub(crate) fn embed_end(&self, cx: Context<'a>) -> Option<usize> {
+        match self {
+            Self::Embed { open, lex } => {
+                if !open.eval(cx.probe()).is_match()
+                    || open.longest_match(cx.buffer, cx.start) != Some(cx.end)
+                {
+                    return None;
+                }
+
+                let (_, consumed) = lex(cx.buffer, cx.start..cx.end);
+                let end = cx.end + consumed;
+
+                cx.buffer.is_char_boundary(end).then_some(end)
+            }
+            Self::Value(rule, _)
+            | Self::ValueFilter(rule, _)
+            | Self::ValueMany(rule, _)
+            | Self::Ignore(rule)
+            | Self::Priority(_, rule)
+            | Self::AtLineStart(rule)
+            | Self::Gated(rule)
+            | Self::PushMode(rule, _)
+            | Self::PopMode(rule)
+            | Self::Commit { rule, .. } => rule.embed_end(cx),
+            #[cfg(feature = "alloc")]
+            Self::ValueCow(rule, _) => rule.embed_end(cx),
+            Self::Any(rules) => rules.iter().find_map(|rule| rule.embed_end(cx)),
+            _ => None,
+        }
+    }
+
+    /// Returns the priority this rule has among the alternatives of a
+    /// [`Rule::Any`], as set by [`Rule::Priority`].
+    pub(crate) fn priority(&self) -> u8 {
+        match self {
+            Self::Priority(priority, _) => *priority,
+            Self::Value(rule, _)
+            | Self::ValueFilter(rule, _)
+            | Self::ValueMany(rule, _)
+            | Self::Ignore(rule)
+            | Self::Only(rule) => rule.priority(),
+            #[cfg(feature = "alloc")]
+            Self::ValueCow(rule, _) => rule.priority(),
+            #[cfg(feature = "alloc")]
+            Self::Owned(rule) => rule.priority(),
+            _ => 0,
+        }
+    }
+
+    /// Returns whether a [`Rule::LineContinuation`] in this rule matches `cx`.
+    pub(crate) fn continues_line(&self, cx: Context<'a>) -> bool {
+        match self {
+            Self::LineContinuation { .. } => self.matches_in(cx).is_match(),
+            Self::Ignore(rule)
+            | Self::Only(rule)
+            | Self::Priority(_, rule)
+            | Self::AtLineStart(rule) => rule.continues_line(cx),
+            Self::Either(a, b) => a.continues_line(cx) || b.continues_line(cx),
+            Self::Any(rules) => rules.iter().any(|rule| rule.continues_line(cx)),
+            _ => false,
+        }
+    }
+
+    /// Returns the text a [`Rule::ValueCow`] callback receives for `value`.
+    #[cfg(feature = "alloc")]
+    fn lexeme(&self, value: &'a str) -> Lexeme<'a> {
+        match self {
+            Self::Quoted { quote, escape } => Lexeme::unescape(
+                &value[quote.len_utf8()..value.len() - quote.len_utf8()],
+                *escape,
+            ),
+            Self::Delimited {
+                open,
+                close,
+                escape: Some(escape),
+            } => Lexeme::unescape(&value[open.len()..value.len() - close.len()], *escape),
+            _ => Lexeme::from(value),
+        }
+    }
+
+    /// Returns the end of the longest slice of `buffer` starting at `start`
+    /// that fully matches, extending the slice one character at a time for as
+    /// long as the rule reports a match or partial match.
+    pub(crate) fn longest_match(&self, buffer: &'a str, start: usize) -> Option<usize> {
+        let mut last = None;
+
+        for (offset, c) in buffer[start..].char_indices() {
+            let end = start + offset + c.len_utf8();
+
+            match self.matches_in(Context::new(buffer, start..end).probe()) {
+                MatchResult::None | MatchResult::Cut => break,
+                MatchResult::Match(_) => last = Some(end),
+                MatchResult::PartialMatch => {}
+            }
+        }
+
+        last
+    }
+}
+
+/// Clones without requiring `T: Clone`, as a rule only holds references to
+/// its sub-rules and callbacks. Rules aren't `Copy` because of the range held
+/// by [`Rule::CharRange`].
+impl<T> Clone for Rule<'_, T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Literal(literal) => Self::Literal(literal),
+            Self::LiteralIgnoreCase(literal) => Self::LiteralIgnoreCase(literal),
+            Self::Literals(literals) => Self::Literals(literals),
+            Self::Keywords(keywords) => Self::Keywords(keywords),
+            Self::Numeric => Self::Numeric,
+            Self::Integer => Self::Integer,
+            Self::Float => Self::Float,
+            Self::HexInteger => Self::HexInteger,
+            Self::Number => Self::Number,
+            Self::Signed(rule) => Self::Signed(rule),
+            Self::Alphabetic => Self::Alphabetic,
+            Self::Whitespace => Self::Whitespace,
+            Self::WhitespaceOf(chars) => Self::WhitespaceOf(chars),
+            Self::Chars(f) => Self::Chars(*f),
+            Self::CharRange(range) => Self::CharRange(range.clone()),
+            #[cfg(feature = "unicode-segmentation")]
+            Self::Grapheme => Self::Grapheme,
+            Self::Value(rule, f) => Self::Value(rule, *f),
+            Self::ValueFilter(rule, f) => Self::ValueFilter(rule, *f),
+            Self::ValueMany(rule, f) => Self::ValueMany(rule, *f),
+            #[cfg(feature = "alloc")]
+            Self::ValueCow(rule, f) => Self::ValueCow(rule, *f),
+            Self::Ignore(rule) => Self::Ignore(rule),
+            Self::Quoted { quote, escape } => Self::Quoted {
+                quote: *quote,
+                escape: *escape,
+            },
+            Self::LineComment(open) => Self::LineComment(open),
+            Self::BlockComment {
+                open,
+                close,
+                nested,
+            } => Self::BlockComment {
+                open,
+                close,
+                nested: *nested,
+            },
+            Self::Delimited {
+                open,
+                close,
+                escape,
+            } => Self::Delimited {
+                open,
+                close,
+                escape: *escape,
+            },
+            Self::LineContinuation { marker } => Self::LineContinuation { marker },
+            Self::EndsWith(value) => Self::EndsWith(value),
+            Self::StartsWith(value) => Self::StartsWith(value),
+            Self::Not(rule) => Self::Not(rule),
+            Self::Except(rule, except) => Self::Except(rule, except),
+            Self::Only(rule) => Self::Only(rule),
+            Self::Both(a, b) => Self::Both(a, b),
+            Self::Either(a, b) => Self::Either(a, b),
+            Self::All(rules, f) => Self::All(rules, *f),
+            Self::Seq(rules) => Self::Seq(rules),
+            Self::Repeat { rule, min, max } => Self::Repeat {
+                rule,
+                min: *min,
+                max: *max,
+            },
+            Self::Any(rules) => Self::Any(rules),
+            Self::Priority(priority, rule) => Self::Priority(*priority, rule),
+            Self::TrailingContext { main, trail } => Self::TrailingContext { main, trail },
+            Self::IdentifierOrKeyword {
+                ident,
+                keywords,
+                fallback,
+            } => Self::IdentifierOrKeyword {
+                ident,
+                keywords,
+                fallback: *fallback,
+            },
+            Self::AtLineStart(rule) => Self::AtLineStart(rule),
+            Self::Gated(rule) => Self::Gated(rule),
+            Self::PushMode(rule, mode) => Self::PushMode(rule, mode),
+            Self::PopMode(rule) => Self::PopMode(rule),
+            Self::Embed { open, lex } => Self::Embed { open, lex: *lex },
+            Self::Commit { tag, prefix, rule } => Self::Commit { tag, prefix, rule },
+            #[cfg(feature = "alloc")]
+            Self::Owned(rule) => Self::Owned(rule),
+        }
+    }
+}
+
+impl<T> PartialEq for Rule<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        use core::ptr::fn_addr_eq;
+
+        match (self, other) {
+            (Self::Literal(a), Self::Literal(b)) => a == b,
+            (Self::LiteralIgnoreCase(a), Self::LiteralIgnoreCase(b)) => a == b,
+            (Self::Literals(a), Self::Literals(b)) => a == b,
+            (Self::Keywords(a), Self::Keywords(b)) => keywords_eq(a, b),
+            (Self::Numeric, Self::Numeric) => true,
+            (Self::Integer, Self::Integer) => true,
+            (Self::Float, Self::Float) => true,
+            (Self::HexInteger, Self::HexInteger) => true,
+            (Self::Number, Self::Number) => true,
+            (Self::Signed(a), Self::Signed(b)) => a == b,
+            (Self::Alphabetic, Self::Alphabetic) => true,
+            (Self::Whitespace, Self::Whitespace) => true,
+            (Self::WhitespaceOf(a), Self::WhitespaceOf(b)) => a == b,
+            (Self::Chars(f), Self::Chars(g)) => fn_addr_eq(*f, *g),
+            (Self::CharRange(a), Self::CharRange(b)) => a == b,
+            #[cfg(feature = "unicode-segmentation")]
+            (Self::Grapheme, Self::Grapheme) => true,
+            (Self::Value(a, f), Self::Value(b, g)) => a == b && extract_eq(*f, *g),
+            (Self::ValueFilter(a, f), Self::ValueFilter(b, g)) => a == b && fn_addr_eq(*f, *g),
+            (Self::ValueMany(a, f), Self::ValueMany(b, g)) => a == b && fn_addr_eq(*f, *g),
+            #[cfg(feature = "alloc")]
+            (Self::ValueCow(a, f), Self::ValueCow(b, g)) => a == b && fn_addr_eq(*f, *g),
+            (Self::Ignore(a), Self::Ignore(b)) => a == b,
+            (
+                Self::Quoted {
+                    quote: a,
+                    escape: b,
+                },
+                Self::Quoted {
+                    quote: c,
+                    escape: d,
+                },
+            ) => a == c && b == d,
+            (
+                Self::BlockComment {
+                    open: a,
+                    close: b,
+                    nested: c,
+                },
+                Self::BlockComment {
+                    open: d,
+                    close: e,
+                    nested: f,
+                },
+            ) => a == d && b == e && c == f,
+            (
+                Self::Delimited {
+                    open: a,
+                    close: b,
+                    escape: c,
+                },
+                Self::Delimited {
+                    open: d,
+                    close: e,
+                    escape: f,
+                },
+            ) => a == d && b == e && c == f,
+            (Self::LineContinuation { marker: a }, Self::LineContinuation { marker: b }) => a == b,
+            (Self::LineComment(a), Self::LineComment(b)) => a == b,
+            (Self::EndsWith(a), Self::EndsWith(b)) => a == b,
+            (Self::StartsWith(a), Self::StartsWith(b)) => a == b,
+            (Self::Not(a), Self::Not(b)) => a == b,
+            (Self::Only(a), Self::Only(b)) => a == b,
+            (Self::Except(a, b), Self::Except(c, d)) => a == c && b == d,
+            (Self::Both(a, b), Self::Both(c, d)) => a == c && b == d,
+            (Self::Either(a, b), Self::Either(c, d)) => a == c && b == d,
+            (Self::All(a, f), Self::All(b, g)) => a == b && extract_eq(*f, *g),
+            (Self::Any(a), Self::Any(b)) => a == b,
+            (Self::Priority(p, a), Self::Priority(q, b)) => p == q && a == b,
+            (Self::Seq(a), Self::Seq(b)) => a == b,
+            (
+                Self::Repeat { rule, min, max },
+                Self::Repeat {
+                    rule: other,
+                    min: other_min,
+                    max: other_max,
+                },
+            ) => rule == other && min == other_min && max == other_max,
+            (
+                Self::TrailingContext { main: a, trail: b },
+                Self::TrailingContext { main: c, trail: d },
+            ) => a == c && b == d,
+            (Self::AtLineStart(a), Self::AtLineStart(b)) => a == b,
+            (Self::Gated(a), Self::Gated(b)) => a == b,
+            (Self::PushMode(a, m), Self::PushMode(b, n)) => a == b && m == n,
+            (Self::PopMode(a), Self::PopMode(b)) => a == b,
+            (Self::Embed { open: a, lex: f }, Self::Embed { open: b, lex: g }) => {
+                a == b && fn_addr_eq(*f, *g)
+            }
+            (
+                Self::Commit {
+                    tag: s,
+                    prefix: a,
+                    rule: b,
+                },
+                Self::Commit {
+                    tag: t,
+                    prefix: c,
+                    rule: d,
+                },
+            ) => s == t && a == c && b == d,
+            (
+                Self::IdentifierOrKeyword {
+                    ident: a,
+                    keywords: k,
+                    fallback: f,
+                },
+                Self::IdentifierOrKeyword {
+                    ident: b,
+                    keywords: l,
+                    fallback: g,
+                },
+            ) => a == b && keywords_eq(k, l) && fn_addr_eq(*f, *g),
+            #[cfg(feature = "alloc")]
+            (Self::Owned(a), Self::Owned(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+pub(crate) fn literal<T>(literal: &str, value: &str) -> MatchResult<T> {
+    if value == literal {
+        MatchResult::Match(None)
+    } else if literal.starts_with(value) {
+        MatchResult::PartialMatch
+    } else {
+        MatchResult::None
+    }
+}
+
+fn literal_ignore_case<T>(literal: &str, value: &str) -> MatchResult<T> {
+    let mut literal = literal.chars().flat_map(char::to_lowercase);
+
+    for c in value.chars().flat_map(char::to_lowercase) {
+        if literal.next() != Some(c) {
+            return MatchResult::None;
+        }
+    }
+
+    match literal.next() {
+        Some(_) => MatchResult::PartialMatch,
+        None => MatchResult::Match(None),
+    }
+}
+
+pub(crate) fn literals<'l, T>(
+    literals: impl Iterator<Item = &'l str>,
+    value: &str,
+) -> MatchResult<T> {
+    self::any(literals.map(|literal| self::literal(literal, value)))
+}
+
+/// Looks `value` up in the sorted `keywords`, partially matching if it
+/// starts a longer keyword.
+fn keywords<T>(keywords: &[Keyword<'_, T>], value: &str) -> MatchResult<T> {
+    let index = keywords.partition_point(|(keyword, _)| *keyword < value);
+
+    match keywords.get(index) {
+        Some((keyword, token)) if *keyword == value => MatchResult::Match(Some(token())),
+        Some((keyword, _)) if keyword.starts_with(value) => MatchResult::PartialMatch,
+        _ => MatchResult::None,
+    }
+}
+
+pub(crate) fn extract_eq<'a, T>(a: &dyn Extract<'a, T>, b: &dyn Extract<'a, T>) -> bool {
+    core::ptr::eq(a, b)
+}
+
+fn keywords_eq<T>(a: &[Keyword<'_, T>], b: &[Keyword<'_, T>]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|((a, f), (b, g))| a == b && core::ptr::fn_addr_eq(*f, *g))
+}
+
+/// A rule tree node that can be evaluated, so that [`Rule::Seq`] and
+/// [`Rule::Repeat`] can be shared with their owned counterparts.
+pub(crate) trait Eval<'a, T> {
+    fn eval(&self, cx: Context<'a>) -> MatchResult<T>;
+}
+
+impl<'a, T> Eval<'a, T> for Rule<'a, T> {
+    fn eval(&self, cx: Context<'a>) -> MatchResult<T> {
+        Rule::eval(self, cx)
+    }
+}
+
+/// Matches the candidate slice as consecutive parts, the first rule taking
+/// the longest prefix it fully matches and the rest matching what is left,
+/// partially matching if the slice can still grow into a match.
+pub(crate) fn seq<'a, T, R: Eval<'a, T>>(rules: &[R], cx: Context<'a>) -> MatchResult<T> {
+    let Some((first, rest)) = rules.split_first() else {
+        return if cx.start == cx.end {
+            MatchResult::Match(None)
+        } else {
+            MatchResult::None
+        };
+    };
+
+    let cx = cx.probe();
+    let Some((split, partial)) = munch(first, cx, true) else {
+        return MatchResult::Cut;
+    };
+    let result = match split {
+        Some(split) => seq(rest, Context { start: split, ..cx }),
+        None => MatchResult::None,
+    };
+
+    match result {
+        MatchResult::Match(_) => MatchResult::Match(None),
+        MatchResult::None if partial => MatchResult::PartialMatch,
+        result => result,
+    }
+}
+
+/// Matches the slice of `cx` as `count` or more further repetitions of
+/// `rule`, after `count` parts have already matched, each repetition taking
+/// the longest non-empty prefix that `rule` fully matches.
+pub(crate) fn repeat<'a, T, R: Eval<'a, T>>(
+    rule: &R,
+    mut count: usize,
+    min: usize,
+    max: Option<usize>,
+    mut cx: Context<'a>,
+) -> MatchResult<T> {
+    let mut partial = false;
+
+    loop {
+        if cx.start == cx.end {
+            return match count >= min {
+                true => MatchResult::Match(None),
+                false => MatchResult::PartialMatch,
+            };
+        }
+
+        let split = match max.is_some_and(|max| count >= max) {
+            true => None,
+            false => match munch(rule, cx, false) {
+                Some((split, partially)) => {
+                    partial |= partially;
+                    split
+                }
+                None => return MatchResult::Cut,
+            },
+        };
+
+        match split {
+            Some(split) => {
+                cx.start = split;
+                count += 1;
+            }
+            None if partial => return MatchResult::PartialMatch,
+            None => return MatchResult::None,
+        }
+    }
+}
+
+/// Returns the end of the longest prefix of the slice of `cx` that `rule`
+/// fully matches, if any, and whether `rule` partially matches the whole
+/// slice, or `None` on a cut. The empty prefix only counts if `empty` is set.
+///
+/// Like the lexer, it stops extending a prefix once `rule` no longer matches
+/// it even partially, so `rule` is evaluated at most once per character.
+fn munch<'a, T, R: Eval<'a, T>>(
+    rule: &R,
+    cx: Context<'a>,
+    empty: bool,
+) -> Option<(Option<usize>, bool)> {
+    let partial = match rule.eval(cx) {
+        MatchResult::Match(_) if empty || cx.start < cx.end => return Some((Some(cx.end), false)),
+        MatchResult::Cut => return None,
+        result => matches!(result, MatchResult::PartialMatch),
+    };
+    let mut last = None;
+
+    if empty && cx.start < cx.end {
+        match rule.eval(Context {
+            end: cx.start,
+            ..cx
+        }) {
+            MatchResult::Match(_) => last = Some(cx.start),
+            MatchResult::Cut => return None,
+            _ => {}
+        }
+    }
+
+    for (offset, c) in cx.value().char_indices() {
+        let end = cx.start + offset + c.len_utf8();
+
+        if end == cx.end {
+            break;
+        }
+
+        match rule.eval(Context { end, ..cx }) {
+            MatchResult::Match(_) => last = Some(end),
+            MatchResult::PartialMatch => {}
+            MatchResult::None => break,
+            MatchResult::Cut => return None,
+        }
+    }
+
+    Some((last, partial))
+}
+
+/// Returns whether `rule` still fully matches once the slice of `cx` grows by
+/// the next character of the buffer.
+pub(crate) fn continues<'a, T, R: Eval<'a, T>>(rule: &R, cx: Context<'a>) -> bool {
+    cx.remainder().chars().next().is_some_and(|c| {
+        rule.eval(Context {
+            end: cx.end + c.len_utf8(),
+            ..cx.probe()
+        })
+        .is_match()
+    })
+}
+
+/// Returns whether every character of `value` satisfies `unicode`, testing
+/// the leading ASCII bytes with the equivalent `ascii` rather than decoding
+/// them.
+fn all_chars(value: &str, ascii: impl Fn(u8) -> bool, unicode: impl Fn(char) -> bool) -> bool {
+    let bytes = value.as_bytes();
 
+    if value.is_ascii() {
+        // Without an early exit, the test vectorizes.
+        return bytes.iter().fold(true, |all, &byte| all & ascii(byte));
+    }
+
+    let len = bytes
+        .iter()
+        .position(|byte| !byte.is_ascii())
+        .unwrap_or(bytes.len());
+
+    bytes[..len].iter().all(|&byte| ascii(byte)) && value[len..].chars().all(unicode)
+}
+
+/// Matches the ASCII characters of [`char::is_whitespace`], which unlike
+/// [`u8::is_ascii_whitespace`] include the vertical tab.
+const fn is_ascii_whitespace(byte: u8) -> bool {
+    matches!(byte, b'\t'..=b'\r' | b' ')
+}
+
+fn quoted<T>(quote: char, escape: char, value: &str) -> MatchResult<T> {
+    let mut chars = value.chars();
+
+    if chars.next() != Some(quote) {
+        return MatchResult::None;
+    }
+
+    while let Some(c) = chars.next() {
+        if c == escape {
+            chars.next();
+        } else if c == quote {
+            return if chars.next().is_none() {
+                MatchResult::Match(None)
+            } else {
+                MatchResult::None
+            };
+        }
+    }
+
+    MatchResult::PartialMatch
+}
+
+fn ends_with<T>(literal: &str, value: &str) -> MatchResult<T> {
+    if literal.is_empty() {
+        return MatchResult::Match(None);
+    }
+
+    match value.find(literal) {
+        Some(index) if index + literal.len() == value.len() => MatchResult::Match(None),
+        Some(_) => MatchResult::None,
+        None => MatchResult::PartialMatch,
+    }
+}
+
+fn line_continuation<T>(marker: &str, value: &str) -> MatchResult<T> {
+    let Some(rest) = value.strip_prefix(marker) else {
+        return if marker.starts_with(value) {
+            MatchResult::PartialMatch
+        } else {
+            MatchResult::None
+        };
+    };
+
+    match rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))
+    {
+        Some(indent) => {
+            if indent.chars().all(|c| c == ' ' || c == '\t') {
+                MatchResult::Match(None)
+            } else {
                 MatchResult::None
             }
         }
+        None if rest.is_empty() || rest == "\r" => MatchResult::PartialMatch,
+        None => MatchResult::None,
+    }
+}
+
+fn delimited<T>(open: &str, close: &str, escape: Option<char>, value: &str) -> MatchResult<T> {
+    let Some(rest) = value.strip_prefix(open) else {
+        return if open.starts_with(value) {
+            MatchResult::PartialMatch
+        } else {
+            MatchResult::None
+        };
+    };
+
+    let mut chars = rest.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        if Some(c) == escape {
+            chars.next();
+        } else if rest[index..].starts_with(close) {
+            return if index + close.len() == rest.len() {
+                MatchResult::Match(None)
+            } else {
+                MatchResult::None
+            };
+        }
+    }
+
+    MatchResult::PartialMatch
+}
+
+fn line_comment<T>(open: &str, value: &str) -> MatchResult<T> {
+    match value.strip_prefix(open) {
+        Some(comment) if comment.contains('\n') => MatchResult::None,
+        Some(_) => MatchResult::Match(None),
+        None => {
+            if open.starts_with(value) {
+                MatchResult::PartialMatch
+            } else {
+                MatchResult::None
+            }
+        }
+    }
+}
+
+fn block_comment<T>(open: &str, close: &str, nested: bool, value: &str) -> MatchResult<T> {
+    if !value.starts_with(open) {
+        return if open.starts_with(value) {
+            MatchResult::PartialMatch
+        } else {
+            MatchResult::None
+        };
+    }
+
+    let mut depth = 1;
+    let mut index = open.len();
+
+    while index < value.len() {
+        let rest = &value[index..];
+
+        if rest.starts_with(close) {
+            depth -= 1;
+            index += close.len();
+
+            if depth == 0 {
+                return if index == value.len() {
+                    MatchResult::Match(None)
+                } else {
+                    MatchResult::None
+                };
+            }
+        } else if nested && rest.starts_with(open) {
+            depth += 1;
+            index += open.len();
+        } else {
+            index += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    MatchResult::PartialMatch
+}
+
+pub(crate) fn value<'a, T>(
+    result: MatchResult<T>,
+    out: &dyn Extract<'a, T>,
+    cx: Context<'a>,
+) -> MatchResult<T> {
+    match result {
+        MatchResult::None => MatchResult::None,
+        MatchResult::Match(_) => MatchResult::Match(extract(out, cx)),
+        MatchResult::PartialMatch => MatchResult::PartialMatch,
+        MatchResult::Cut => MatchResult::Cut,
+    }
+}
+
+pub(crate) fn ignore<T>(result: MatchResult<T>) -> MatchResult<T> {
+    match result {
+        MatchResult::Match(_) => MatchResult::Match(None),
+        result => result,
+    }
+}
+
+pub(crate) fn not<T>(result: MatchResult<T>) -> MatchResult<T> {
+    match result {
+        MatchResult::None | MatchResult::PartialMatch => MatchResult::Match(None),
+        MatchResult::Match(_) => MatchResult::PartialMatch,
+        MatchResult::Cut => MatchResult::Cut,
+    }
+}
+
+pub(crate) fn both<T>(a: MatchResult<T>, b: impl FnOnce() -> MatchResult<T>) -> MatchResult<T> {
+    let partial = match a {
+        MatchResult::Match(_) => false,
+        MatchResult::PartialMatch => true,
+        result => return result,
+    };
+
+    match b() {
+        MatchResult::Match(_) if !partial => MatchResult::Match(None),
+        MatchResult::Match(_) | MatchResult::PartialMatch => MatchResult::PartialMatch,
+        result => result,
+    }
+}
+
+pub(crate) fn either<T>(a: MatchResult<T>, b: impl FnOnce() -> MatchResult<T>) -> MatchResult<T> {
+    let partial = a.is_partial_match();
+
+    let result = match a {
+        MatchResult::None | MatchResult::PartialMatch => b(),
+        result => result,
+    };
+
+    match result {
+        MatchResult::Match(_) => MatchResult::Match(None),
+        MatchResult::Cut => MatchResult::Cut,
+        _ if partial => MatchResult::PartialMatch,
+        MatchResult::PartialMatch => MatchResult::PartialMatch,
+        MatchResult::None => MatchResult::None,
+    }
+}
+
+pub(crate) fn all<'a, T>(
+    results: impl Iterator<Item = MatchResult<T>>,
+    out: &dyn Extract<'a, T>,
+    cx: Context<'a>,
+) -> MatchResult<T> {
+    for result in results {
+        match result {
+            MatchResult::None => return MatchResult::None,
+            MatchResult::PartialMatch => return MatchResult::PartialMatch,
+            MatchResult::Cut => return MatchResult::Cut,
+            _ => {}
+        }
+    }
+
+    MatchResult::Match(extract(out, cx))
+}
+
+/// Makes the token for the slice of `cx`, unless it is only probed.
+fn extract<'a, T>(out: &dyn Extract<'a, T>, cx: Context<'a>) -> Option<T> {
+    (!cx.probe).then(|| out(cx.value()))
+}
+
+/// Returns the result of the alternative with the highest priority among the
+/// ones that fully match, the first listed winning ties, or a partial match if
+/// none does but any partially matches. Alternatives are evaluated in order,
+/// skipping the ones that can no longer win, and the first cut among them is
+/// returned right away. A partial match never hides a full match, so adding
+/// an alternative that merely starts like the input can't change the result.
+pub(crate) fn ranked<T, F: FnOnce() -> MatchResult<T>>(
+    alternatives: impl Iterator<Item = (u8, F)>,
+) -> MatchResult<T> {
+    let mut best = None;
+    let mut partial = false;
+
+    for (priority, eval) in alternatives {
+        if best.as_ref().is_some_and(|(best, _)| priority <= *best) {
+            continue;
+        }
+
+        match eval() {
+            MatchResult::None => {}
+            MatchResult::PartialMatch => partial = true,
+            MatchResult::Cut => return MatchResult::Cut,
+            result => best = Some((priority, result)),
+        }
+    }
+
+    match best {
+        Some((_, result)) => result,
+        None if partial => MatchResult::PartialMatch,
+        None => MatchResult::None,
+    }
+}
+
+/// Returns the index of the alternative that decides the token for `cx`, as
+/// [`Rule::Any`] ranks them.
+pub(crate) fn decider<'a, T>(rules: &[Rule<'a, T>], cx: Context<'a>) -> Option<usize> {
+    rules
+        .iter()
+        .enumerate()
+        .filter(|(_, rule)| rule.eval(cx.probe()).is_match())
+        .min_by_key(|(_, rule)| core::cmp::Reverse(rule.priority()))
+        .map(|(index, _)| index)
+}
+
+pub(crate) fn any<T>(results: impl Iterator<Item = MatchResult<T>>) -> MatchResult<T> {
+    let mut partial = false;
+
+    for result in results {
+        match result {
+            MatchResult::None => {}
+            MatchResult::PartialMatch => partial = true,
+            result => return result,
+        }
+    }
+
+    if partial {
+        MatchResult::PartialMatch
+    } else {
+        MatchResult::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONE: &str = "None";
+    const MATCH: &str = "Match";
+    const PARTIAL: &str = "PartialMatch";
+
+    fn result(name: &str) -> MatchResult<()> {
+        match name {
+            NONE => MatchResult::None,
+            MATCH => MatchResult::Match(None),
+            _ => MatchResult::PartialMatch,
+        }
+    }
+
+    fn name(result: MatchResult<()>) -> &'static str {
+        match result {
+            MatchResult::None => NONE,
+            MatchResult::Match(_) => MATCH,
+            MatchResult::PartialMatch => PARTIAL,
+            MatchResult::Cut => "Cut",
+        }
+    }
+
+    #[test]
+    fn not() {
+        for (a, expected) in [(NONE, MATCH), (MATCH, PARTIAL), (PARTIAL, MATCH)] {
+            assert_eq!(name(super::not(result(a))), expected, "not {a}");
+        }
+    }
+
+    #[test]
+    fn both() {
+        let table = [
+            (NONE, NONE, NONE),
+            (NONE, MATCH, NONE),
+            (NONE, PARTIAL, NONE),
+            (MATCH, NONE, NONE),
+            (MATCH, MATCH, MATCH),
+            (MATCH, PARTIAL, PARTIAL),
+            (PARTIAL, NONE, NONE),
+            (PARTIAL, MATCH, PARTIAL),
+            (PARTIAL, PARTIAL, PARTIAL),
+        ];
+
+        for (a, b, expected) in table {
+            assert_eq!(
+                name(super::both(result(a), || result(b))),
+                expected,
+                "{a} and {b}"
+            );
+        }
+    }
+
+    #[test]
+    fn either() {
+        let table = [
+            (NONE, NONE, NONE),
+            (NONE, MATCH, MATCH),
+            (NONE, PARTIAL, PARTIAL),
+            (MATCH, NONE, MATCH),
+            (MATCH, MATCH, MATCH),
+            (MATCH, PARTIAL, MATCH),
+            (PARTIAL, NONE, PARTIAL),
+            (PARTIAL, MATCH, MATCH),
+            (PARTIAL, PARTIAL, PARTIAL),
+        ];
+
+        for (a, b, expected) in table {
+            assert_eq!(
+                name(super::either(result(a), || result(b))),
+                expected,
+                "{a} or {b}"
+            );
+        }
+    }
+
+    #[test]
+    fn match_prefix() {
+        const RULE: Rule<'static, &str> = Rule::Any(&[
+            Rule::Value(&Rule::Alphabetic, &|value| value),
+            Rule::Value(&Rule::Literals(&["+", "+="]), &|value| value),
+        ]);
+
+        let table = [
+            ("a+b", Some((1, "a"))),
+            ("+=b", Some((2, "+="))),
+            ("+b", Some((1, "+"))),
+            ("abc", Some((3, "abc"))),
+        ];
+
+        for (value, expected) in table {
+            let PrefixResult::Match { len, token } = RULE.match_prefix(value) else {
+                panic!("{value:?} doesn't match");
+            };
+
+            assert_eq!(Some((len, token.unwrap())), expected, "{value:?}");
+        }
+
+        assert!(RULE.match_prefix("1a").is_none());
+        assert!(RULE.match_prefix("").is_partial_match());
+        assert!(Rule::<()>::Literal("and")
+            .match_prefix("an")
+            .is_partial_match());
+        assert!(Rule::<()>::Literal("and").match_prefix("an+").is_none());
+        assert_eq!(
+            Rule::<()>::Literal("and")
+                .match_prefix("andy")
+                .matched_len(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn ascii_classes() {
+        let corpus = (0..=0x7f)
+            .filter_map(char::from_u32)
+            .chain("é٣中ⅷᛮ\u{a0}\u{85}\u{2003}\u{feff}".chars())
+            .collect::<Vec<_>>();
+
+        // Every character on its own and next to each other one, ASCII or
+        // not, along with longer mixed runs.
+        let values = corpus
+            .iter()
+            .flat_map(|a| corpus.iter().map(move |b| [*a, *b]))
+            .map(String::from_iter)
+            .chain(corpus.iter().map(char::to_string))
+            .chain(
+                [
+                    "12٣4",
+                    "abc中é",
+                    "x9",
+                    " \t\n\u{b}\u{c}\r\u{85}",
+                    "\u{2003} a",
+                ]
+                .map(String::from),
+            )
+            .collect::<Vec<_>>();
+        let classes = [
+            (Rule::<()>::Numeric, char::is_numeric as fn(char) -> bool),
+            (Rule::Alphabetic, char::is_alphabetic),
+            (Rule::Whitespace, char::is_whitespace),
+        ];
+
+        for (rule, class) in classes {
+            let unicode = Rule::<()>::Chars(class);
+
+            for value in &values {
+                assert_eq!(
+                    rule.matches(value).is_match(),
+                    unicode.matches(value).is_match(),
+                    "{rule:?} {value:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn no_backtracking() {
+        let word: Rule<()> = Rule::Repeat {
+            rule: &Rule::Alphabetic,
+            min: 1,
+            max: None,
+        };
+        let input = "a".repeat(1000) + "1!";
+
+        assert!(word.matches(&input[..1001]).is_none());
+        assert!(word.matches(&input[..1000]).is_match());
+
+        let parts: Rule<()> = Rule::Seq(&[
+            Rule::Alphabetic,
+            Rule::Alphabetic,
+            Rule::Alphabetic,
+            Rule::Alphabetic,
+            Rule::Numeric,
+        ]);
+
+        assert!(parts.matches(&input).is_none());
+        assert!(parts.matches(&input[..1001]).is_match());
+        assert!(Rule::<()>::Seq(&[Rule::Alphabetic, Rule::Literal("x")])
+            .matches("abx")
+            .is_partial_match());
+    }
+
+    #[test]
+    fn identifier_except_keyword() {
+        const RULE: Rule<'static, ()> =
+            Rule::Both(&Rule::Alphabetic, &Rule::Not(&Rule::Literal("end")));
+
+        assert!(RULE.matches("end").is_partial_match());
+        assert!(RULE.matches("endless").is_match());
+        assert!(RULE.matches("en1").is_none());
+
+        // Short prefixes of the keyword are ordinary identifiers.
+        for input in ["e \n", "en \n", "ends \n"] {
+            let rule = Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(
+                    &Rule::Both(&Rule::Alphabetic, &Rule::Not(&Rule::Literal("end"))),
+                    &|text| text,
+                ),
+            ]);
+            let tokens = crate::Lexer::with_buffer(rule, input).collect::<Vec<_>>();
+
+            assert_eq!(tokens, [Ok(input.trim_end())], "{input:?}");
+        }
     }
 }
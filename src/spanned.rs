@@ -0,0 +1,160 @@
+use core::{fmt, ops::Deref, ops::Range};
+
+/// A value along with the byte range of the input it was lexed from.
+///
+/// Comparing with `==` only compares the values, so that tokens can be
+/// checked against expected ones without spelling out their positions; use
+/// [`Spanned::eq_with_span`] to compare spans too.
+#[derive(Clone)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Range<usize>,
+}
+
+impl<T> Spanned<T> {
+    pub const fn new(value: T, span: Range<usize>) -> Self {
+        Self { value, span }
+    }
+
+    /// Maps the value, keeping the span.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<U> {
+        Spanned {
+            value: f(self.value),
+            span: self.span,
+        }
+    }
+
+    /// Borrows the value, keeping the span.
+    pub fn as_ref(&self) -> Spanned<&T> {
+        Spanned {
+            value: &self.value,
+            span: self.span.clone(),
+        }
+    }
+
+    /// Returns `true` if both the values and the spans are equal.
+    pub fn eq_with_span<U>(&self, other: &Spanned<U>) -> bool
+    where
+        T: PartialEq<U>,
+    {
+        self.value == other.value && self.span == other.span
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: PartialEq<U>, U> PartialEq<Spanned<U>> for Spanned<T> {
+    fn eq(&self, other: &Spanned<U>) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq> Eq for Spanned<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for Spanned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} @ {:?}", self.value, self.span)
+    }
+}
+
+/// Serializes as a `(value, span)` pair.
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for Spanned<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.value, &self.span).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Spanned<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (value, span) = <(T, Range<usize>)>::deserialize(deserializer)?;
+        Ok(Self { value, span })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lexer, Rule};
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        Open,
+        Close,
+        Word(&'a str),
+    }
+
+    #[test]
+    fn map() {
+        let word = Spanned::new("word", 4..8).map(str::len);
+
+        assert_eq!(word.value, 4);
+        assert_eq!(word.span, 4..8);
+        assert_eq!(*word.as_ref().value, 4);
+        assert_eq!(word.checked_sub(1), Some(3));
+    }
+
+    #[test]
+    fn equality() {
+        let a = Spanned::new('a', 0..1);
+        let b = Spanned::new('a', 5..6);
+
+        assert_eq!(a, b);
+        assert!(!a.eq_with_span(&b));
+        assert!(a.eq_with_span(&Spanned::new('a', 0..1)));
+        assert_ne!(a, Spanned::new('b', 0..1));
+    }
+
+    #[test]
+    fn parse() {
+        const RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Literal("("), &|_| Token::Open),
+            Rule::Value(&Rule::Literal(")"), &|_| Token::Close),
+            Rule::Value(&Rule::Alphabetic, &Token::Word),
+        ]);
+
+        /// Parses a parenthesized list of words, returning the span of the
+        /// whole list along with the words.
+        fn list<'a>(
+            tokens: &mut impl Iterator<Item = Spanned<Token<'a>>>,
+        ) -> Option<Spanned<Vec<&'a str>>> {
+            let Spanned {
+                value: Token::Open,
+                span: open,
+            } = tokens.next()?
+            else {
+                return None;
+            };
+            let mut words = Vec::new();
+
+            for token in tokens {
+                match token.value {
+                    Token::Word(word) => words.push(word),
+                    Token::Close => return Some(Spanned::new(words, open.start..token.span.end)),
+                    Token::Open => return None,
+                }
+            }
+
+            None
+        }
+
+        let mut lexer = Lexer::with_buffer(RULE, " (ab cd) \n");
+        let words = list(&mut lexer.spanned()).unwrap();
+
+        assert_eq!(words, Spanned::new(Vec::from(["ab", "cd"]), 0..0));
+        assert_eq!(words.span, 1..8);
+        assert_eq!(words.len(), 2);
+    }
+}
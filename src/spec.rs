@@ -0,0 +1,263 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{OwnedRule, Rule, TokenFactory};
+
+/// A grammar read from a file, such as JSON or TOML, rather than written in
+/// Rust. Compiles with [`Rule::from_spec`] into an [`OwnedRule::Any`] of its
+/// rules.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct GrammarSpec {
+    pub rules: Vec<RuleSpec>,
+}
+
+/// A rule of a [`GrammarSpec`], such as
+/// `{ "kind": "literal", "value": "and", "token": "And" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleSpec {
+    #[serde(flatten)]
+    pub kind: RuleKind,
+    /// The name of the token produced by matches of the rule, handed to the
+    /// token factory along with the matched slice.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// What a [`RuleSpec`] matches, named after the [`Rule`] variant it compiles
+/// into, in snake case. Rules that take callbacks, other than producing a
+/// named token, aren't available.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RuleKind {
+    Literal {
+        value: String,
+    },
+    LiteralIgnoreCase {
+        value: String,
+    },
+    Literals {
+        values: Vec<String>,
+    },
+    Numeric,
+    Alphabetic,
+    Whitespace,
+    WhitespaceOf {
+        chars: String,
+    },
+    CharRange {
+        start: char,
+        end: char,
+    },
+    Integer,
+    Float,
+    HexInteger,
+    Number,
+    Quoted {
+        quote: char,
+        escape: char,
+    },
+    LineComment {
+        open: String,
+    },
+    BlockComment {
+        open: String,
+        close: String,
+        #[serde(default)]
+        nested: bool,
+    },
+    Delimited {
+        open: String,
+        close: String,
+        #[serde(default)]
+        escape: Option<char>,
+    },
+    EndsWith {
+        value: String,
+    },
+    StartsWith {
+        value: String,
+    },
+    Ignore {
+        rule: Box<RuleSpec>,
+    },
+    Not {
+        rule: Box<RuleSpec>,
+    },
+    Only {
+        rule: Box<RuleSpec>,
+    },
+    Both {
+        first: Box<RuleSpec>,
+        second: Box<RuleSpec>,
+    },
+    Either {
+        first: Box<RuleSpec>,
+        second: Box<RuleSpec>,
+    },
+    Any {
+        rules: Vec<RuleSpec>,
+    },
+}
+
+impl RuleSpec {
+    pub fn new(kind: RuleKind) -> Self {
+        Self { kind, token: None }
+    }
+
+    /// Names the token produced by matches of the rule.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    fn compile<'a, T>(&'a self, factory: TokenFactory<'a, T>) -> OwnedRule<'a, T> {
+        let boxed = |rule: &'a RuleSpec| Box::new(rule.compile(factory));
+
+        let rule = match &self.kind {
+            RuleKind::Literal { value } => OwnedRule::Inline(Rule::Literal(value)),
+            RuleKind::LiteralIgnoreCase { value } => {
+                OwnedRule::Inline(Rule::LiteralIgnoreCase(value))
+            }
+            RuleKind::Literals { values } => {
+                OwnedRule::Literals(values.iter().map(String::as_str).collect())
+            }
+            RuleKind::Numeric => OwnedRule::Rule(&Rule::Numeric),
+            RuleKind::Alphabetic => OwnedRule::Rule(&Rule::Alphabetic),
+            RuleKind::Whitespace => OwnedRule::Rule(&Rule::Whitespace),
+            RuleKind::WhitespaceOf { chars } => OwnedRule::Inline(Rule::WhitespaceOf(chars)),
+            RuleKind::CharRange { start, end } => OwnedRule::Inline(Rule::CharRange(*start..=*end)),
+            RuleKind::Integer => OwnedRule::Rule(&Rule::Integer),
+            RuleKind::Float => OwnedRule::Rule(&Rule::Float),
+            RuleKind::HexInteger => OwnedRule::Rule(&Rule::HexInteger),
+            RuleKind::Number => OwnedRule::Rule(&Rule::Number),
+            RuleKind::Quoted { quote, escape } => OwnedRule::Inline(Rule::Quoted {
+                quote: *quote,
+                escape: *escape,
+            }),
+            RuleKind::LineComment { open } => OwnedRule::Inline(Rule::LineComment(open)),
+            RuleKind::BlockComment {
+                open,
+                close,
+                nested,
+            } => OwnedRule::Inline(Rule::BlockComment {
+                open,
+                close,
+                nested: *nested,
+            }),
+            RuleKind::Delimited {
+                open,
+                close,
+                escape,
+            } => OwnedRule::Inline(Rule::Delimited {
+                open,
+                close,
+                escape: *escape,
+            }),
+            RuleKind::EndsWith { value } => OwnedRule::Inline(Rule::EndsWith(value)),
+            RuleKind::StartsWith { value } => OwnedRule::Inline(Rule::StartsWith(value)),
+            RuleKind::Ignore { rule } => OwnedRule::Ignore(boxed(rule)),
+            RuleKind::Not { rule } => OwnedRule::Not(boxed(rule)),
+            RuleKind::Only { rule } => OwnedRule::Only(boxed(rule)),
+            RuleKind::Both { first, second } => OwnedRule::Both(boxed(first), boxed(second)),
+            RuleKind::Either { first, second } => OwnedRule::Either(boxed(first), boxed(second)),
+            RuleKind::Any { rules } => {
+                OwnedRule::Any(rules.iter().map(|rule| rule.compile(factory)).collect())
+            }
+        };
+
+        match &self.token {
+            Some(token) => OwnedRule::Named(Box::new(rule), token, factory),
+            None => rule,
+        }
+    }
+}
+
+impl<'a, T> Rule<'a, T> {
+    /// Compiles a grammar loaded at runtime into an owned rule, making the
+    /// tokens of rules with a `token` name through `factory`, which receives
+    /// the name and the matched slice.
+    pub fn from_spec(spec: &'a GrammarSpec, factory: TokenFactory<'a, T>) -> OwnedRule<'a, T> {
+        OwnedRule::Any(
+            spec.rules
+                .iter()
+                .map(|rule| rule.compile(factory))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lexer;
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        Let,
+        Identifier(&'a str),
+        Number(&'a str),
+        String(&'a str),
+    }
+
+    fn token<'a>(name: &str, value: &'a str) -> Token<'a> {
+        match name {
+            "Let" => Token::Let,
+            "Identifier" => Token::Identifier(value),
+            "Number" => Token::Number(value),
+            "String" => Token::String(value),
+            _ => unreachable!("{name}"),
+        }
+    }
+
+    const GRAMMAR: &str = r#"{
+        "rules": [
+            { "kind": "ignore", "rule": { "kind": "whitespace" } },
+            {
+                "kind": "ignore",
+                "rule": { "kind": "block_comment", "open": "/*", "close": "*/" }
+            },
+            { "kind": "literal_ignore_case", "value": "let", "token": "Let" },
+            { "kind": "alphabetic", "token": "Identifier" },
+            { "kind": "number", "token": "Number" },
+            { "kind": "quoted", "quote": "'", "escape": "\\", "token": "String" }
+        ]
+    }"#;
+
+    #[test]
+    fn from_json() {
+        let spec: GrammarSpec = serde_json::from_str(GRAMMAR).unwrap();
+
+        assert_eq!(
+            spec.rules[2],
+            RuleSpec::new(RuleKind::LiteralIgnoreCase {
+                value: String::from("let")
+            })
+            .token("Let")
+        );
+
+        let rule = Rule::from_spec(&spec, token);
+        let tokens = Lexer::with_buffer(rule.as_rule(), "LET x /* one */ 0x1F 'it\\'s'\n")
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Let,
+                Token::Identifier("x"),
+                Token::Number("0x1F"),
+                Token::String("'it\\'s'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let spec: GrammarSpec = serde_json::from_str(GRAMMAR).unwrap();
+        let json = serde_json::to_string(&spec).unwrap();
+
+        assert!(json.contains(r#"{"kind":"alphabetic","token":"Identifier"}"#));
+        assert_eq!(serde_json::from_str::<GrammarSpec>(&json).unwrap(), spec);
+    }
+}
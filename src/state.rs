@@ -0,0 +1,368 @@
+use core::{fmt, ops::Range};
+
+use crate::{Lexer, ModeStack, PushedBack, Rule, MODE_DEPTH_LIMIT, PUSH_BACK_LIMIT};
+
+/// A snapshot of the progress of a [`Lexer`], as returned by
+/// [`Lexer::save_state`], for resuming it later, possibly in another process.
+///
+/// The state covers everything except the buffer, the rule and the lexer's
+/// configuration, which have to be provided again when resuming. The state
+/// records the length and a hash of the buffer to catch resuming against
+/// different input. With the `serde` feature, it serializes as a sequence.
+///
+/// The warnings collected so far aren't part of the state, and neither are
+/// the checkpoints of peeked tokens: a token peeked before saving is still
+/// returned after resuming, but [`Lexer::rewind`] can't go back past it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexerState<T> {
+    version: u32,
+    buffer_len: usize,
+    buffer_hash: u64,
+    index: (usize, usize),
+    matched: Option<usize>,
+    terminate: bool,
+    gate_open: bool,
+    line_base: Option<(usize, usize, Option<Range<usize>>)>,
+    mode: ModeStack,
+    pushed: [PushedBack<T>; PUSH_BACK_LIMIT],
+    span: Range<usize>,
+    exhausted: bool,
+}
+
+impl<T> LexerState<T> {
+    /// The version of the state format, bumped whenever its meaning changes.
+    pub const VERSION: u32 = 3;
+
+    /// Returns whether every offset of the state lies on a character
+    /// boundary of `buffer`, and the mode stack within its limit.
+    fn fits(&self, buffer: &str) -> bool {
+        let fits = |range: &Range<usize>| {
+            range.start <= range.end
+                && buffer.is_char_boundary(range.start)
+                && buffer.is_char_boundary(range.end)
+        };
+        let (start, end) = self.index;
+
+        fits(&(start..end))
+            && self.matched.is_none_or(|matched| fits(&(start..matched)))
+            && fits(&self.span)
+            && usize::from(self.mode.len) <= MODE_DEPTH_LIMIT
+            && self.line_base.as_ref().is_none_or(|(base, _, source)| {
+                buffer.is_char_boundary(*base) && source.as_ref().is_none_or(fits)
+            })
+            && self.pushed.iter().flatten().all(|token| fits(&token.span))
+    }
+}
+
+/// The reason [`Lexer::resume`] rejected a state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResumeError {
+    /// The state was saved by an incompatible version of the crate.
+    Version { found: u32, expected: u32 },
+    /// The buffer differs from the one the state was saved for.
+    Buffer,
+    /// The state points outside of the buffer or between the bytes of a
+    /// character, as a corrupted or edited state can.
+    Invalid,
+}
+
+impl fmt::Display for ResumeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Version { found, expected } => {
+                write!(f, "lexer state version {found} is not version {expected}")
+            }
+            Self::Buffer => f.write_str("lexer state was saved for a different buffer"),
+            Self::Invalid => f.write_str("lexer state doesn't fit its buffer"),
+        }
+    }
+}
+
+impl core::error::Error for ResumeError {}
+
+/// Hashes `buffer` with 64-bit FNV-1a, which is stable across processes and
+/// platforms.
+fn hash(buffer: &str) -> u64 {
+    buffer.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl<'a, T: Clone> Lexer<'a, T> {
+    /// Takes a snapshot of the lexer's progress.
+    ///
+    /// A source name set by a position directive is only kept if it was
+    /// taken from the buffer.
+    pub fn save_state(&self) -> LexerState<T> {
+        let start = self.buffer.as_ptr() as usize;
+        let line_base = self.line_base.map(|(base, line, source)| {
+            let source = source.and_then(|source| {
+                let offset = (source.as_ptr() as usize).checked_sub(start)?;
+                let range = offset..offset + source.len();
+
+                (self.buffer.get(range.clone()) == Some(source)).then_some(range)
+            });
+
+            (base, line, source)
+        });
+
+        LexerState {
+            version: LexerState::<T>::VERSION,
+            buffer_len: self.buffer.len(),
+            buffer_hash: hash(self.buffer),
+            index: self.index,
+            matched: self.matched,
+            terminate: self.terminate,
+            gate_open: self.gate_open,
+            line_base,
+            mode: self.mode,
+            pushed: self.pushed.clone(),
+            span: self.span.clone(),
+            exhausted: self.exhausted,
+        }
+    }
+}
+
+impl<'a, T> Lexer<'a, T> {
+    /// Creates a lexer over `buffer` that continues where the lexer `state`
+    /// was saved from left off. Configuration, such as strict mode, has to
+    /// be applied again.
+    pub fn resume(
+        rule: Rule<'a, T>,
+        buffer: &'a str,
+        state: LexerState<T>,
+    ) -> Result<Self, ResumeError> {
+        if state.version != LexerState::<T>::VERSION {
+            return Err(ResumeError::Version {
+                found: state.version,
+                expected: LexerState::<T>::VERSION,
+            });
+        }
+
+        if state.buffer_len != buffer.len() || state.buffer_hash != hash(buffer) {
+            return Err(ResumeError::Buffer);
+        }
+
+        if !state.fits(buffer) {
+            return Err(ResumeError::Invalid);
+        }
+
+        let mut lexer = Self::with_buffer(rule, buffer);
+
+        lexer.index = state.index;
+        lexer.data = &buffer[state.index.0..state.index.1];
+        lexer.matched = state.matched;
+        lexer.terminate = state.terminate;
+        lexer.gate_open = state.gate_open;
+        lexer.line_base = state
+            .line_base
+            .map(|(base, line, source)| (base, line, source.map(|range| &buffer[range])));
+        lexer.mode = state.mode;
+        lexer.pushed = state.pushed;
+        lexer.span = state.span;
+        lexer.exhausted = state.exhausted;
+
+        Ok(lexer)
+    }
+}
+
+#[cfg(feature = "serde")]
+type Fields<T> = (
+    u32,
+    usize,
+    u64,
+    (usize, usize),
+    Option<usize>,
+    bool,
+    bool,
+    Option<(usize, usize, Option<Range<usize>>)>,
+    ([u8; crate::MODE_DEPTH_LIMIT], u8),
+    [Option<(T, Range<usize>)>; PUSH_BACK_LIMIT],
+    Range<usize>,
+    bool,
+);
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for LexerState<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let pushed = self
+            .pushed
+            .each_ref()
+            .map(|token| token.as_ref().map(|token| (&token.value, &token.span)));
+
+        (
+            self.version,
+            self.buffer_len,
+            self.buffer_hash,
+            self.index,
+            self.matched,
+            self.terminate,
+            self.gate_open,
+            &self.line_base,
+            (self.mode.modes, self.mode.len),
+            pushed,
+            &self.span,
+            self.exhausted,
+        )
+            .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for LexerState<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (
+            version,
+            buffer_len,
+            buffer_hash,
+            index,
+            matched,
+            terminate,
+            gate_open,
+            line_base,
+            (modes, len),
+            pushed,
+            span,
+            exhausted,
+        ) = Fields::<T>::deserialize(deserializer)?;
+
+        Ok(Self {
+            version,
+            buffer_len,
+            buffer_hash,
+            index,
+            matched,
+            terminate,
+            gate_open,
+            line_base,
+            mode: ModeStack { modes, len },
+            pushed: pushed.map(|token| token.map(|(value, span)| crate::Spanned::new(value, span))),
+            span,
+            exhausted,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Token<'a> {
+        Word(&'a str),
+        Number(&'a str),
+    }
+
+    const RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+        Rule::Ignore(&Rule::Whitespace),
+        Rule::Value(&Rule::Alphabetic, &Token::Word),
+        Rule::Value(&Rule::Numeric, &Token::Number),
+    ]);
+
+    const INPUT: &str = "one 2 three 4 five\n";
+
+    #[test]
+    fn resume() {
+        let uninterrupted = Lexer::with_buffer(RULE, INPUT)
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let mut lexer = Lexer::with_buffer(RULE, INPUT);
+        let mut tokens = lexer.by_ref().flatten().take(2).collect::<Vec<_>>();
+
+        // Pushed back tokens are part of the state.
+        lexer.push_back(Token::Number("2"), 4..5);
+        tokens.pop();
+
+        let state = lexer.save_state();
+
+        let lexer = Lexer::resume(RULE, INPUT, state).unwrap();
+        tokens.extend(lexer.flatten());
+
+        assert_eq!(tokens, uninterrupted);
+    }
+
+    #[test]
+    fn mismatch() {
+        let mut lexer = Lexer::with_buffer(RULE, INPUT);
+        lexer.by_ref().flatten().next();
+
+        let state = lexer.save_state();
+
+        assert_eq!(
+            Lexer::resume(RULE, "one 2 three 4 fivx\n", state.clone()).err(),
+            Some(ResumeError::Buffer)
+        );
+        assert_eq!(
+            Lexer::resume(RULE, "one\n", state.clone()).err(),
+            Some(ResumeError::Buffer)
+        );
+
+        let mut future = state;
+        future.version += 1;
+
+        assert_eq!(
+            Lexer::resume(RULE, INPUT, future)
+                .err()
+                .map(|error| error.to_string()),
+            Some(String::from("lexer state version 4 is not version 3"))
+        );
+    }
+
+    #[test]
+    fn exhausted() {
+        let mut lexer = Lexer::with_buffer(RULE, INPUT);
+        assert_eq!(lexer.by_ref().count(), 5);
+
+        let mut lexer = Lexer::resume(RULE, INPUT, lexer.save_state()).unwrap();
+        assert!(lexer.next().is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json() {
+        #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+        enum Token {
+            Word(String),
+            Number(String),
+        }
+
+        const RULE: Rule<'static, Token> = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Alphabetic, &|value| Token::Word(value.into())),
+            Rule::Value(&Rule::Numeric, &|value| Token::Number(value.into())),
+        ]);
+
+        let uninterrupted = Lexer::with_buffer(RULE, INPUT)
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let mut lexer = Lexer::with_buffer(RULE, INPUT);
+        let mut tokens = lexer.by_ref().flatten().take(2).collect::<Vec<_>>();
+        lexer.push_back(tokens.pop().unwrap(), 4..5);
+
+        let json = serde_json::to_string(&lexer.save_state()).unwrap();
+        let state = serde_json::from_str::<LexerState<Token>>(&json).unwrap();
+        assert_eq!(state, lexer.save_state());
+
+        tokens.extend(Lexer::resume(RULE, INPUT, state).unwrap().flatten());
+        assert_eq!(tokens, uninterrupted);
+
+        // An edited state is rejected rather than panicking.
+        let mut fields = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        fields[3] = serde_json::json!([2, 99]);
+        let state = serde_json::from_value::<LexerState<Token>>(fields.clone()).unwrap();
+        assert_eq!(
+            Lexer::resume(RULE, INPUT, state).err(),
+            Some(ResumeError::Invalid)
+        );
+
+        fields[3] = serde_json::json!([1, 1]);
+        fields[8][1] = serde_json::json!(MODE_DEPTH_LIMIT + 1);
+        let state = serde_json::from_value::<LexerState<Token>>(fields).unwrap();
+        assert_eq!(
+            Lexer::resume(RULE, INPUT, state).err(),
+            Some(ResumeError::Invalid)
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{OwnedRule, Rule};
+
+/// Size and composition of a rule tree, as returned by [`Rule::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuleStats {
+    /// Number of distinct rule nodes.
+    pub nodes: usize,
+    /// Length of the longest path from the root to a leaf, counting both.
+    pub depth: usize,
+    /// Number of literal strings, counting each entry of a literal set.
+    pub literals: usize,
+    /// Largest number of alternatives in a single `Any`, `Either` or literal
+    /// set.
+    pub alternatives_max_width: usize,
+    /// Number of value callbacks.
+    pub callbacks: usize,
+}
+
+impl<'a, T> Rule<'a, T> {
+    /// Walks the rule tree and reports its size and composition.
+    ///
+    /// Nodes reachable through several references are only counted once, and
+    /// a reference cycle between statics is cut where it closes.
+    pub fn stats(&self) -> RuleStats {
+        let mut walker = Walker::default();
+        walker.stats.depth = walker.rule(self);
+        walker.stats
+    }
+}
+
+/// Identifies a node by its address and whether it is an owned node.
+type Node = (usize, bool);
+
+#[derive(Default)]
+struct Walker {
+    stats: RuleStats,
+    depths: BTreeMap<Node, usize>,
+    stack: Vec<Node>,
+}
+
+impl Walker {
+    /// Returns the depth of `node`, walking it with `walk` on first visit.
+    fn visit(&mut self, node: Node, walk: impl FnOnce(&mut Self) -> usize) -> usize {
+        if self.stack.contains(&node) {
+            return 0;
+        }
+
+        if let Some(depth) = self.depths.get(&node) {
+            return *depth;
+        }
+
+        self.stats.nodes += 1;
+        self.stack.push(node);
+        let depth = 1 + walk(self);
+        self.stack.pop();
+        self.depths.insert(node, depth);
+
+        depth
+    }
+
+    fn alternatives(&mut self, width: usize) {
+        self.stats.alternatives_max_width = self.stats.alternatives_max_width.max(width);
+    }
+
+    fn rule<T>(&mut self, rule: &Rule<'_, T>) -> usize {
+        let node = (rule as *const Rule<'_, T> as usize, false);
+
+        self.visit(node, |walker| match rule {
+            Rule::Literal(_) | Rule::LiteralIgnoreCase(_) => {
+                walker.stats.literals += 1;
+                0
+            }
+            Rule::Literals(literals) => {
+                walker.stats.literals += literals.len();
+                walker.alternatives(literals.len());
+                0
+            }
+            Rule::Keywords(keywords) => {
+                walker.stats.literals += keywords.len();
+                walker.stats.callbacks += keywords.len();
+                walker.alternatives(keywords.len());
+                0
+            }
+            Rule::Value(rule, _) => {
+                walker.stats.callbacks += 1;
+                walker.rule(rule)
+            }
+            Rule::ValueFilter(rule, _) => {
+                walker.stats.callbacks += 1;
+                walker.rule(rule)
+            }
+            Rule::ValueMany(rule, _) => {
+                walker.stats.callbacks += 1;
+                walker.rule(rule)
+            }
+            #[cfg(feature = "alloc")]
+            Rule::ValueCow(rule, _) => {
+                walker.stats.callbacks += 1;
+                walker.rule(rule)
+            }
+            Rule::Ignore(rule)
+            | Rule::Not(rule)
+            | Rule::Only(rule)
+            | Rule::Priority(_, rule)
+            | Rule::AtLineStart(rule)
+            | Rule::Gated(rule)
+            | Rule::PushMode(rule, _)
+            | Rule::PopMode(rule)
+            | Rule::Signed(rule) => walker.rule(rule),
+            Rule::Both(a, b) | Rule::Except(a, b) => walker.rule(a).max(walker.rule(b)),
+            Rule::Either(a, b) => {
+                walker.alternatives(2);
+                walker.rule(a).max(walker.rule(b))
+            }
+            Rule::All(rules, _) => {
+                walker.stats.callbacks += 1;
+                rules
+                    .iter()
+                    .map(|rule| walker.rule(rule))
+                    .max()
+                    .unwrap_or(0)
+            }
+            Rule::Any(rules) => {
+                walker.alternatives(rules.len());
+                rules
+                    .iter()
+                    .map(|rule| walker.rule(rule))
+                    .max()
+                    .unwrap_or(0)
+            }
+            Rule::Seq(rules) => rules
+                .iter()
+                .map(|rule| walker.rule(rule))
+                .max()
+                .unwrap_or(0),
+            Rule::Repeat { rule, .. } => walker.rule(rule),
+            Rule::TrailingContext { main, trail } => walker.rule(main).max(walker.rule(trail)),
+            Rule::Embed { open, .. } => {
+                walker.stats.callbacks += 1;
+                walker.rule(open)
+            }
+            Rule::Commit { prefix, rule, .. } => walker.rule(prefix).max(walker.rule(rule)),
+            Rule::IdentifierOrKeyword {
+                ident, keywords, ..
+            } => {
+                walker.stats.literals += keywords.len();
+                walker.stats.callbacks += keywords.len() + 1;
+                walker.rule(ident)
+            }
+            Rule::Owned(rule) => walker.owned(rule),
+            _ => 0,
+        })
+    }
+
+    fn owned<T>(&mut self, rule: &OwnedRule<'_, T>) -> usize {
+        let node = (rule as *const OwnedRule<'_, T> as usize, true);
+
+        self.visit(node, |walker| match rule {
+            OwnedRule::Rule(rule) => walker.rule(rule),
+            OwnedRule::Inline(rule) => walker.rule(rule),
+            OwnedRule::Literals(literals) => {
+                walker.stats.literals += literals.len();
+                walker.alternatives(literals.len());
+                0
+            }
+            OwnedRule::Value(rule, _) | OwnedRule::Named(rule, ..) => {
+                walker.stats.callbacks += 1;
+                walker.owned(rule)
+            }
+            OwnedRule::Ignore(rule) | OwnedRule::Not(rule) | OwnedRule::Only(rule) => {
+                walker.owned(rule)
+            }
+            OwnedRule::Both(a, b) => walker.owned(a).max(walker.owned(b)),
+            OwnedRule::Either(a, b) => {
+                walker.alternatives(2);
+                walker.owned(a).max(walker.owned(b))
+            }
+            OwnedRule::All(rules, _) => {
+                walker.stats.callbacks += 1;
+                rules
+                    .iter()
+                    .map(|rule| walker.owned(rule))
+                    .max()
+                    .unwrap_or(0)
+            }
+            OwnedRule::Seq(rules) => rules
+                .iter()
+                .map(|rule| walker.owned(rule))
+                .max()
+                .unwrap_or(0),
+            OwnedRule::Repeat { rule, .. } => walker.owned(rule),
+            OwnedRule::Any(rules) => {
+                walker.alternatives(rules.len());
+                rules
+                    .iter()
+                    .map(|rule| walker.owned(rule))
+                    .max()
+                    .unwrap_or(0)
+            }
+            OwnedRule::IdentifierOrKeyword {
+                ident,
+                keywords,
+                fallback,
+                ..
+            } => {
+                walker.stats.literals += keywords.len();
+                walker.stats.callbacks += keywords.len() + usize::from(fallback.is_some());
+                walker.rule(ident)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Token;
+
+    static WORD: Rule<'static, Token> = Rule::Value(&Rule::Alphabetic, &|_| Token);
+
+    static LOOP: Rule<'static, Token> = Rule::Not(&Rule::Ignore(&LOOP));
+
+    static TREE: Rule<'static, Token> = Rule::Any(&[
+        Rule::Ignore(&Rule::Whitespace),
+        Rule::Either(&Rule::Literal("function"), &Rule::Literal("func")),
+        Rule::Value(&Rule::Literals(&["and", "or", "not"]), &|_| Token),
+        Rule::Both(&WORD, &WORD),
+        Rule::Only(&WORD),
+    ]);
+
+    #[test]
+    fn known_tree() {
+        assert_eq!(
+            TREE.stats(),
+            RuleStats {
+                nodes: 12,
+                depth: 4,
+                literals: 5,
+                alternatives_max_width: 5,
+                callbacks: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn owned_tree() {
+        let pair = Rule::<Token>::Either(&Rule::Literal("a"), &Rule::Literal("b"));
+        let owned = pair.optimize();
+
+        assert_eq!(
+            owned.as_rule().stats(),
+            RuleStats {
+                nodes: 2,
+                depth: 2,
+                literals: 2,
+                alternatives_max_width: 2,
+                callbacks: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn cycle() {
+        assert_eq!(
+            LOOP.stats(),
+            RuleStats {
+                nodes: 2,
+                depth: 2,
+                ..RuleStats::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,534 @@
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+
+use crate::{Error, LexErrorOwned, Lexer, ModeStack, PushedBack, Rule, PUSH_BACK_LIMIT};
+
+/// A rule set that can be instantiated for any buffer lifetime.
+///
+/// Lexers that own their input, such as the async adapter, can't hand out
+/// slices of it to a [`Rule`] built for a fixed lifetime, so they ask the
+/// grammar for a fresh rule over their internal buffer instead. Tokens must
+/// therefore not borrow from the input.
+pub trait Grammar {
+    type Token;
+
+    fn rule<'x>(&self) -> Rule<'x, Self::Token>;
+
+    /// Configures the lexer running the rule, such as with
+    /// [`Lexer::with_modes`], [`Lexer::strict`] or
+    /// [`Lexer::with_recovery`]. The lexer is built anew for each token,
+    /// carrying its progress over, so the configuration should be the same
+    /// every time.
+    fn configure<'x>(&self, lexer: Lexer<'x, Self::Token>) -> Lexer<'x, Self::Token> {
+        lexer
+    }
+}
+
+pub(crate) enum Next<T> {
+    Token(T),
+    NeedInput,
+    Error(LexErrorOwned),
+    Done,
+}
+
+/// The progress of the lexer between tokens, which outlives the lexer built
+/// over the buffer for each token. Offsets are relative to the buffer.
+struct State<T> {
+    index: (usize, usize),
+    matched: Option<usize>,
+    span: Range<usize>,
+    terminate: bool,
+    gate_open: bool,
+    line_base: Option<(usize, usize, Option<String>)>,
+    lines: (usize, usize),
+    columns: usize,
+    mode: ModeStack,
+    pushed: [PushedBack<T>; PUSH_BACK_LIMIT],
+}
+
+impl<T> State<T> {
+    const fn new() -> Self {
+        Self {
+            index: (0, 0),
+            matched: None,
+            span: 0..0,
+            terminate: false,
+            gate_open: true,
+            line_base: None,
+            lines: (0, 0),
+            columns: 0,
+            mode: ModeStack::new(),
+            pushed: [const { None }; PUSH_BACK_LIMIT],
+        }
+    }
+}
+
+/// Accumulates input that arrives in chunks and lexes it exactly like a
+/// [`Lexer`] over the concatenated input would.
+///
+/// Rules that look past the candidate slice, such as
+/// [`Rule::TrailingContext`], only see the input received so far. Committed
+/// input is dropped up to the character before the next token, which
+/// [`Rule::AtLineStart`] looks at, however long the line.
+pub(crate) struct Chunked<G: Grammar> {
+    grammar: G,
+    buffer: String,
+    pending: Vec<u8>,
+    /// The number of bytes dropped from the front of the buffer.
+    dropped: usize,
+    state: State<G::Token>,
+    finished: bool,
+}
+
+impl<G: Grammar> Chunked<G> {
+    pub(crate) const fn new(grammar: G) -> Self {
+        Self {
+            grammar,
+            buffer: String::new(),
+            pending: Vec::new(),
+            dropped: 0,
+            state: State::new(),
+            finished: false,
+        }
+    }
+
+    /// Appends raw bytes, holding back a trailing incomplete UTF-8 sequence
+    /// until the rest of it arrives.
+    #[cfg(any(test, feature = "tokio"))]
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> Result<(), core::str::Utf8Error> {
+        self.pending.extend_from_slice(bytes);
+
+        let valid = match core::str::from_utf8(&self.pending) {
+            Ok(valid) => valid.len(),
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            Err(error) => return Err(error),
+        };
+
+        self.compact();
+        self.buffer
+            .push_str(core::str::from_utf8(&self.pending[..valid]).unwrap_or_default());
+        self.pending.drain(..valid);
+
+        Ok(())
+    }
+
+    /// Appends text, which never ends inside a UTF-8 sequence.
+    pub(crate) fn push_str(&mut self, chunk: &str) {
+        debug_assert!(self.pending.is_empty(), "pushing text after partial bytes");
+
+        self.compact();
+        self.buffer.push_str(chunk);
+    }
+
+    /// Returns the number of bytes of the whole input committed to tokens or
+    /// skipped so far.
+    pub(crate) const fn consumed_bytes(&self) -> usize {
+        self.dropped + self.state.index.0
+    }
+
+    /// Returns the number of bytes of input received so far, including an
+    /// incomplete UTF-8 sequence held back.
+    pub(crate) fn fed_bytes(&self) -> usize {
+        self.dropped + self.buffer.len() + self.pending.len()
+    }
+
+    /// Marks the end of input, failing if it ends inside a UTF-8 sequence.
+    #[cfg(any(test, feature = "tokio"))]
+    pub(crate) fn finish(&mut self) -> Result<(), core::str::Utf8Error> {
+        self.end();
+
+        match core::str::from_utf8(&self.pending) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Marks the end of input received as text.
+    pub(crate) fn end(&mut self) {
+        self.finished = true;
+    }
+
+    /// Steps a lexer over the buffer until it yields a token or needs more
+    /// input, building it once for all the steps.
+    pub(crate) fn next_token(&mut self) -> Next<G::Token> {
+        let state = &mut self.state;
+        let mut lexer = self
+            .grammar
+            .configure(Lexer::with_buffer(self.grammar.rule(), &self.buffer));
+
+        lexer.index = state.index;
+        lexer.data = &self.buffer[state.index.0..state.index.1];
+        lexer.matched = state.matched;
+        lexer.span = state.span.clone();
+        lexer.terminate = state.terminate;
+        lexer.gate_open = state.gate_open;
+        lexer.line_base = state
+            .line_base
+            .as_ref()
+            .map(|(base, line, source)| (*base, *line, source.as_deref()));
+        lexer.lines = state.lines;
+        lexer.columns = state.columns;
+        lexer.mode = state.mode;
+        lexer.pushed = core::mem::replace(&mut state.pushed, [const { None }; PUSH_BACK_LIMIT]);
+
+        let next = loop {
+            let queued = lexer.pushed.iter().any(Option::is_some);
+
+            if !queued && !self.finished && lexer.index.1 + 1 >= self.buffer.len() {
+                break Next::NeedInput;
+            }
+
+            match lexer.step() {
+                Ok(Some(token)) => break Next::Token(token),
+                Ok(None) => {}
+                Err(Error::Eof) => break Next::Done,
+                Err(error) => break Next::Error(lexer.map_err_span(shift(error, self.dropped))),
+            }
+        };
+
+        let line_base = lexer.line_base.map(|(base, line, source)| {
+            let source = match &state.line_base {
+                Some((_, _, Some(known))) if Some(known.as_str()) == source => Some(known.clone()),
+                _ => source.map(String::from),
+            };
+
+            (base, line, source)
+        });
+
+        state.index = lexer.index;
+        state.matched = lexer.matched;
+        state.span = lexer.span.clone();
+        state.terminate = lexer.terminate;
+        state.gate_open = lexer.gate_open;
+        state.lines = lexer.lines;
+        state.mode = lexer.mode;
+        state.pushed = core::mem::replace(&mut lexer.pushed, [const { None }; PUSH_BACK_LIMIT]);
+        state.line_base = line_base;
+
+        next
+    }
+
+    /// Drops the committed input once it makes up most of the buffer, up to
+    /// the character before the committed cursor and the first token pushed
+    /// back, such as a piece of a lexeme split by a `Rule::ValueMany`.
+    fn compact(&mut self) {
+        let committed = self.state.index.0;
+
+        if committed == 0 || committed * 2 < self.buffer.len() {
+            return;
+        }
+
+        let before = self.buffer[..committed]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(index, _)| index);
+        let cut = self
+            .state
+            .pushed
+            .iter()
+            .flatten()
+            .map(|token| token.span.start)
+            .fold(before, usize::min);
+
+        if cut == 0 {
+            return;
+        }
+
+        let breaks = |from: usize| self.buffer[from..cut].matches('\n').count();
+        let state = &mut self.state;
+
+        state.columns = match self.buffer[..cut].rfind('\n') {
+            Some(index) => self.buffer[index + 1..cut].chars().count(),
+            None => state.columns + self.buffer[..cut].chars().count(),
+        };
+
+        state.lines = match state.lines {
+            (start, breaks_before) if start < cut => (0, breaks_before + breaks(start)),
+            (start, breaks_before) => (start - cut, breaks_before),
+        };
+
+        if let Some((base, line, _)) = &mut state.line_base {
+            match *base < cut {
+                true => {
+                    *line += breaks(*base);
+                    *base = 0;
+                }
+                false => *base -= cut,
+            }
+        }
+
+        state.index = (state.index.0 - cut, state.index.1 - cut);
+        state.matched = state.matched.map(|end| end - cut);
+        state.span = state.span.start.saturating_sub(cut)..state.span.end.saturating_sub(cut);
+
+        for token in state.pushed.iter_mut().flatten() {
+            token.span = token.span.start - cut..token.span.end - cut;
+        }
+
+        self.buffer.drain(..cut);
+        self.dropped += cut;
+    }
+}
+
+/// Moves the offsets in `error`, raised by a lexer over the buffer, by the
+/// bytes dropped before it.
+fn shift(error: Error<'_>, by: usize) -> Error<'_> {
+    match error {
+        Error::UnknownToken {
+            text,
+            at,
+            line,
+            column,
+        } => Error::UnknownToken {
+            text,
+            at: at + by,
+            line,
+            column,
+        },
+        Error::Ambiguous { at, rules } => Error::Ambiguous { at: at + by, rules },
+        Error::Committed { at, rule, tag } => Error::Committed {
+            at: at + by,
+            rule,
+            tag,
+        },
+        Error::Unterminated { open, eof } => Error::Unterminated {
+            open: open.start + by..open.end + by,
+            eof: eof + by,
+        },
+        Error::Unexpected {
+            expected,
+            found,
+            span,
+            line,
+            column,
+        } => Error::Unexpected {
+            expected,
+            found,
+            span: span.start + by..span.end + by,
+            line,
+            column,
+        },
+        Error::ModeOverflow { at } => Error::ModeOverflow { at: at + by },
+        Error::TooManyTokens { at } => Error::TooManyTokens { at: at + by },
+        Error::Overflow { produced, offset } => Error::Overflow {
+            produced,
+            offset: offset + by,
+        },
+        Error::Eof => Error::Eof,
+    }
+}
+
+/// Lexes input pushed to it in chunks, such as from a socket, giving exactly
+/// the tokens a [`Lexer`] over the concatenated input would.
+///
+/// Iterating yields the tokens that are complete so far, and returns `None`
+/// once the lexer needs more input to decide the next one: feed it and
+/// iterate again, then call [`ChunkedLexer::finish`] to flush the last
+/// token. The first error is yielded with offsets into the whole input,
+/// after which iteration ends; a [`RecoveryStrategy`](crate::RecoveryStrategy)
+/// set by [`Grammar::configure`] carries on past unknown text instead.
+pub struct ChunkedLexer<G: Grammar> {
+    chunked: Chunked<G>,
+    done: bool,
+}
+
+impl<G: Grammar> ChunkedLexer<G> {
+    pub const fn new(grammar: G) -> Self {
+        Self {
+            chunked: Chunked::new(grammar),
+            done: false,
+        }
+    }
+
+    /// Appends `chunk` to the input.
+    pub fn feed(&mut self, chunk: &str) {
+        self.chunked.push_str(chunk);
+    }
+
+    /// Marks the end of input, so that the remaining tokens can be lexed.
+    pub fn finish(&mut self) {
+        self.chunked.end();
+    }
+
+    /// Returns the number of bytes committed to tokens or skipped so far,
+    /// counted from the start of the first chunk, like
+    /// [`Lexer::consumed_bytes`].
+    pub const fn consumed_bytes(&self) -> usize {
+        self.chunked.consumed_bytes()
+    }
+
+    /// Returns the number of bytes fed so far. The difference with
+    /// [`ChunkedLexer::consumed_bytes`] is the input held until more
+    /// arrives or the input ends.
+    pub fn fed_bytes(&self) -> usize {
+        self.chunked.fed_bytes()
+    }
+}
+
+impl<G: Grammar> Iterator for ChunkedLexer<G> {
+    type Item = Result<G::Token, LexErrorOwned>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.chunked.next_token() {
+            Next::Token(token) => Some(Ok(token)),
+            Next::NeedInput => None,
+            Next::Error(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+            Next::Done => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Words;
+
+    impl Grammar for Words {
+        type Token = ();
+
+        fn rule<'x>(&self) -> Rule<'x, ()> {
+            Rule::Value(&Rule::Literal("ab"), &|_| ())
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Token {
+        Word,
+        Gt,
+    }
+
+    struct Generics;
+
+    impl Grammar for Generics {
+        type Token = Token;
+
+        fn rule<'x>(&self) -> Rule<'x, Token> {
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Alphabetic, &|_| Token::Word),
+                Rule::ValueMany(&Rule::Literal(">>"), |_, emit| {
+                    emit(0..1, Token::Gt);
+                    emit(1..2, Token::Gt);
+                }),
+            ])
+        }
+    }
+
+    /// Lexes the chunks, collecting tokens and the first error.
+    fn lex(chunks: &[&str]) -> (Vec<Token>, Option<LexErrorOwned>) {
+        let mut chunked = Chunked::new(Generics);
+        let mut tokens = Vec::new();
+
+        for (index, chunk) in chunks.iter().enumerate() {
+            chunked.push(chunk.as_bytes()).unwrap();
+
+            if index + 1 == chunks.len() {
+                chunked.finish().unwrap();
+            }
+
+            loop {
+                match chunked.next_token() {
+                    Next::Token(token) => tokens.push(token),
+                    Next::NeedInput => break,
+                    Next::Error(error) => return (tokens, Some(error)),
+                    Next::Done => return (tokens, None),
+                }
+            }
+        }
+
+        (tokens, None)
+    }
+
+    #[test]
+    fn keeps_state_between_steps() {
+        for chunks in [&["a >> b ? c"][..], &["a >", "> b ", "? c"]] {
+            let (tokens, error) = lex(chunks);
+
+            assert_eq!(tokens, [Token::Word, Token::Gt, Token::Gt, Token::Word]);
+
+            let error = error.unwrap();
+            assert_eq!(error.kind, crate::ErrorKind::UnknownToken);
+            assert_eq!(error.span, 7..8);
+        }
+    }
+
+    #[test]
+    fn reports_whole_input_positions() {
+        let lines = ["a b\n"; 8].concat();
+        let chunks = lines
+            .split_inclusive('\n')
+            .chain(["  a ?"])
+            .collect::<Vec<_>>();
+        let (tokens, error) = lex(&chunks);
+
+        assert_eq!(tokens.len(), 17);
+
+        let error = error.unwrap();
+        assert_eq!(error.span, 36..37);
+        assert_eq!(error.message, "unknown token `?` at 9:5");
+    }
+
+    #[test]
+    fn drops_committed_input_mid_line() {
+        let line = ["ab".repeat(30), ">> ".into()].concat().repeat(40_000);
+        let mut chunked = Chunked::new(Generics);
+        let mut tokens = 0;
+        let mut longest = 0;
+
+        for chunk in line.as_bytes().chunks(100).chain([&b"  ?"[..]]) {
+            chunked.push(chunk).unwrap();
+            longest = longest.max(chunked.buffer.len());
+
+            while let Next::Token(_) = chunked.next_token() {
+                tokens += 1;
+            }
+        }
+
+        chunked.finish().unwrap();
+
+        let Next::Error(error) = chunked.next_token() else {
+            panic!("expected an error");
+        };
+
+        assert_eq!(tokens, 120_000);
+        assert!(longest < 1000, "{longest}");
+        assert_eq!(error.span, line.len() + 2..line.len() + 3);
+        assert_eq!(
+            error.message,
+            alloc::format!("unknown token `?` at 1:{}", line.len() + 3)
+        );
+    }
+
+    #[test]
+    fn holds_back_split_characters() {
+        let mut chunked = Chunked::new(Words);
+        let bytes = "aπb".as_bytes();
+
+        chunked.push(&bytes[..2]).unwrap();
+        assert_eq!(chunked.buffer, "a");
+
+        chunked.push(&bytes[2..]).unwrap();
+        assert_eq!(chunked.buffer, "aπb");
+        assert!(chunked.finish().is_ok());
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        let mut chunked = Chunked::new(Words);
+
+        chunked.push(&"π".as_bytes()[..1]).unwrap();
+        assert!(chunked.finish().is_err());
+        assert!(chunked.push(&[0xff]).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use alloc::vec::Vec;
+
+use crate::Lexer;
+
+/// Delimiters of template strings, such as JavaScript's
+/// `` `hello ${name}!` ``.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemplateConfig<'c> {
+    /// Opens a template string, e.g. `` ` ``.
+    pub open: &'c str,
+    /// Closes a template string, e.g. `` ` ``.
+    pub close: &'c str,
+    /// Opens an interpolation inside a template string, e.g. `${`.
+    pub interp_open: &'c str,
+    /// Closes an interpolation, e.g. `}`.
+    pub interp_close: &'c str,
+    /// Opens a nested block inside an interpolation that is closed by
+    /// `interp_close` without ending the interpolation, e.g. `{`.
+    pub block_open: &'c str,
+}
+
+impl TemplateConfig<'static> {
+    /// Template strings as in JavaScript.
+    pub const JAVASCRIPT: Self = Self {
+        open: "`",
+        close: "`",
+        interp_open: "${",
+        interp_close: "}",
+        block_open: "{",
+    };
+}
+
+/// A token produced by [`Templates`].
+#[derive(Debug, PartialEq)]
+pub enum TemplateToken<'a, T> {
+    TemplateStart,
+    /// Raw text of a template string, escapes included.
+    Chunk(&'a str),
+    InterpolationStart,
+    /// A token lexed by the lexer's rule, outside template strings or inside
+    /// an interpolation.
+    Token(T),
+    InterpolationEnd,
+    TemplateEnd,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    Template,
+    /// Inside an interpolation, with the number of open nested blocks.
+    Interpolation(usize),
+}
+
+/// Iterator over tokens of input containing template strings, which may nest
+/// inside interpolations.
+///
+/// Text inside a template string is split into chunks at interpolations; a
+/// backslash escapes the character after it. Everything else is lexed with
+/// the lexer's rule, which must produce the tokens for `block_open` and
+/// `interp_close` by itself, as well as any strings that may contain the
+/// template delimiters.
+///
+/// Created by [`Lexer::with_templates`]. Iteration ends on the first lexer
+/// error, such as the end of the input.
+pub struct Templates<'l, 'a, 'c, T> {
+    lexer: &'l mut Lexer<'a, T>,
+    config: TemplateConfig<'c>,
+    modes: Vec<Mode>,
+}
+
+impl<'l, 'a, 'c, T> Templates<'l, 'a, 'c, T> {
+    pub(crate) const fn new(lexer: &'l mut Lexer<'a, T>, config: TemplateConfig<'c>) -> Self {
+        Self {
+            lexer,
+            config,
+            modes: Vec::new(),
+        }
+    }
+
+    fn advance(&mut self, len: usize) {
+        let index = self.lexer.index.0 + len;
+        self.lexer.index = (index, index);
+        self.lexer.matched = None;
+        self.lexer.debug_assert_cursors();
+    }
+
+    /// Returns the length of the template text at the start of `rest`.
+    fn chunk_len(&self, rest: &str) -> usize {
+        let mut chars = rest.char_indices();
+
+        while let Some((index, c)) = chars.next() {
+            let rest = &rest[index..];
+
+            if rest.starts_with(self.config.close) || rest.starts_with(self.config.interp_open) {
+                return index;
+            }
+
+            if c == '\\' {
+                chars.next();
+            }
+        }
+
+        rest.len()
+    }
+
+    fn nest(&mut self, token: &str) {
+        if let Some(Mode::Interpolation(depth)) = self.modes.last_mut() {
+            if token == self.config.block_open {
+                *depth += 1;
+            } else if token == self.config.interp_close {
+                *depth = depth.saturating_sub(1);
+            }
+        }
+    }
+}
+
+impl<'a, T> Iterator for Templates<'_, 'a, '_, T> {
+    type Item = TemplateToken<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let config = self.config;
+
+        loop {
+            let at = self.lexer.index.0;
+            let rest = &self.lexer.buffer[at..];
+
+            if let Some(Mode::Template) = self.modes.last() {
+                if rest.starts_with(config.close) {
+                    self.modes.pop();
+                    self.advance(config.close.len());
+                    return Some(TemplateToken::TemplateEnd);
+                }
+
+                if rest.starts_with(config.interp_open) {
+                    self.modes.push(Mode::Interpolation(0));
+                    self.advance(config.interp_open.len());
+                    return Some(TemplateToken::InterpolationStart);
+                }
+
+                let len = self.chunk_len(rest);
+
+                if len == 0 {
+                    return None;
+                }
+
+                self.advance(len);
+                return Some(TemplateToken::Chunk(&rest[..len]));
+            }
+
+            if self.lexer.index.1 == at {
+                if rest.starts_with(config.open) {
+                    self.modes.push(Mode::Template);
+                    self.advance(config.open.len());
+                    return Some(TemplateToken::TemplateStart);
+                }
+
+                if let Some(Mode::Interpolation(0)) = self.modes.last() {
+                    if rest.starts_with(config.interp_close) {
+                        self.modes.pop();
+                        self.advance(config.interp_close.len());
+                        return Some(TemplateToken::InterpolationEnd);
+                    }
+                }
+            }
+
+            let token = self.lexer.step().ok()?;
+            self.nest(&self.lexer.buffer[at..self.lexer.index.0]);
+
+            if let Some(token) = token {
+                return Some(TemplateToken::Token(token));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rule;
+
+    #[derive(Debug, PartialEq)]
+    enum Js<'a> {
+        Brace,
+        String(&'a str),
+        Identifier(&'a str),
+    }
+
+    fn lex(input: &str) -> Vec<TemplateToken<'_, Js<'_>>> {
+        Lexer::with_buffer(
+            Rule::Any(&[
+                Rule::Ignore(&Rule::Whitespace),
+                Rule::Value(&Rule::Literals(&["{", "}"]), &|_| Js::Brace),
+                Rule::Value(
+                    &Rule::Quoted {
+                        quote: '"',
+                        escape: '\\',
+                    },
+                    &Js::String,
+                ),
+                Rule::IdentifierOrKeyword {
+                    ident: &Rule::Alphabetic,
+                    keywords: &[],
+                    fallback: Js::Identifier,
+                },
+            ]),
+            input,
+        )
+        .with_templates(TemplateConfig::JAVASCRIPT)
+        .collect()
+    }
+
+    #[test]
+    fn interpolation() {
+        use TemplateToken::*;
+
+        assert_eq!(
+            lex("x `hello ${name}!` y\n"),
+            [
+                Token(Js::Identifier("x")),
+                TemplateStart,
+                Chunk("hello "),
+                InterpolationStart,
+                Token(Js::Identifier("name")),
+                InterpolationEnd,
+                Chunk("!"),
+                TemplateEnd,
+                Token(Js::Identifier("y")),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_braces() {
+        use TemplateToken::*;
+
+        assert_eq!(
+            lex("`a${ { x } }b\\`` \n"),
+            [
+                TemplateStart,
+                Chunk("a"),
+                InterpolationStart,
+                Token(Js::Brace),
+                Token(Js::Identifier("x")),
+                Token(Js::Brace),
+                InterpolationEnd,
+                Chunk("b\\`"),
+                TemplateEnd,
+            ]
+        );
+    }
+
+    #[test]
+    fn delimiters_inside_interpolation() {
+        use TemplateToken::*;
+
+        assert_eq!(
+            lex("`${ \"`\" `in${b}` }` \n"),
+            [
+                TemplateStart,
+                InterpolationStart,
+                Token(Js::String("\"`\"")),
+                TemplateStart,
+                Chunk("in"),
+                InterpolationStart,
+                Token(Js::Identifier("b")),
+                InterpolationEnd,
+                TemplateEnd,
+                InterpolationEnd,
+                TemplateEnd,
+            ]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use alloc::vec::Vec;
+
+use crate::{Error, Lexer, Rule, Spanned};
+
+/// Everything lexed out of a buffer by [`tokenize`]: the tokens, the text
+/// skipped between them and the regions that raised errors, each in buffer
+/// order. Together their spans cover the buffer exactly once.
+#[derive(Debug)]
+pub struct TokenStream<'a, T> {
+    pub tokens: Vec<Spanned<T>>,
+    /// Text that didn't produce a token, such as whitespace and comments
+    /// under [`Rule::Ignore`].
+    pub trivia: Vec<Spanned<&'a str>>,
+    /// The errors raised, spanning the text skipped to recover from them.
+    /// Consecutive unknown characters are reported as a single
+    /// [`Error::UnknownToken`] covering all of them.
+    pub errors: Vec<Spanned<Error<'a>>>,
+}
+
+/// Lexes the whole of `input` with `rule`, recovering from errors by
+/// skipping a character with [`Lexer::skip_char`] and carrying on, for batch
+/// tools such as formatters and highlighters that want every token at once.
+pub fn tokenize<'a, T>(rule: Rule<'a, T>, input: &'a str) -> TokenStream<'a, T> {
+    let mut lexer = Lexer::with_buffer(rule, input);
+    let mut stream = TokenStream {
+        tokens: Vec::new(),
+        trivia: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut end = 0;
+
+    let gap = |stream: &mut TokenStream<'a, T>, end: usize, start: usize| {
+        if start > end {
+            stream
+                .trivia
+                .push(Spanned::new(&input[end..start], end..start));
+        }
+    };
+
+    while let Some(token) = lexer.next() {
+        match token {
+            Ok(token) => {
+                let span = lexer.span();
+
+                gap(&mut stream, end, span.start);
+                end = end.max(span.end);
+                stream.tokens.push(Spanned::new(token, span));
+            }
+            Err(error) => {
+                let start = lexer.offset();
+                let skipped = lexer.skip_char();
+
+                if skipped.is_empty() {
+                    break;
+                }
+
+                let span = start..start + skipped.len();
+
+                gap(&mut stream, end, start);
+                end = span.end;
+
+                match (stream.errors.last_mut(), error) {
+                    (
+                        Some(Spanned {
+                            value: Error::UnknownToken { text, .. },
+                            span: last,
+                        }),
+                        Error::UnknownToken { .. },
+                    ) if last.end == start => {
+                        last.end = span.end;
+                        *text = &input[last.clone()];
+                    }
+                    (_, error) => stream.errors.push(Spanned::new(error, span)),
+                }
+            }
+        }
+    }
+
+    gap(&mut stream, end, input.len());
+
+    stream
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        Word(&'a str),
+        Number(&'a str),
+    }
+
+    const RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+        Rule::Ignore(&Rule::Whitespace),
+        Rule::Ignore(&Rule::BlockComment {
+            open: "/*",
+            close: "*/",
+            nested: false,
+        }),
+        Rule::Value(&Rule::Alphabetic, &Token::Word),
+        Rule::Value(&Rule::Integer, &Token::Number),
+    ]);
+
+    #[test]
+    fn recovers_from_errors() {
+        let stream = tokenize(RULE, "one @ two 2 #three");
+
+        assert_eq!(
+            stream
+                .errors
+                .iter()
+                .map(|error| error.span.clone())
+                .collect::<Vec<_>>(),
+            [4..5, 12..13]
+        );
+        assert!(matches!(
+            stream.errors[1].value,
+            Error::UnknownToken {
+                text: "#",
+                at: 12,
+                ..
+            }
+        ));
+        assert_eq!(stream.trivia[0].value, " ");
+        assert_eq!(stream.trivia[0].span, 3..4);
+        assert_eq!(
+            stream
+                .tokens
+                .into_iter()
+                .map(Spanned::into_inner)
+                .collect::<Vec<_>>(),
+            [
+                Token::Word("one"),
+                Token::Word("two"),
+                Token::Number("2"),
+                Token::Word("three"),
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_unknown_regions() {
+        let stream = tokenize(RULE, "a @#$ b");
+
+        assert_eq!(stream.errors.len(), 1);
+        assert_eq!(stream.errors[0].span, 2..5);
+        assert!(matches!(
+            stream.errors[0].value,
+            Error::UnknownToken { text: "@#$", .. }
+        ));
+    }
+
+    #[test]
+    fn lossless() {
+        for input in [
+            "one @ two 2 #three",
+            "  lead /* note */ 42 trail  \n",
+            "@@ x /* open",
+            "",
+        ] {
+            let stream = tokenize(RULE, input);
+            let mut spans = stream
+                .tokens
+                .iter()
+                .map(|token| token.span.clone())
+                .chain(stream.trivia.iter().map(|trivia| trivia.span.clone()))
+                .chain(stream.errors.iter().map(|error| error.span.clone()))
+                .collect::<Vec<_>>();
+
+            spans.sort_by_key(|span| span.start);
+
+            let text = spans
+                .iter()
+                .map(|span| &input[span.clone()])
+                .collect::<String>();
+
+            assert_eq!(text, input);
+            assert!(spans.windows(2).all(|pair| pair[0].end == pair[1].start));
+        }
+    }
+}
@@ -0,0 +1,674 @@
+use alloc::{collections::BTreeSet, vec::Vec};
+use core::fmt;
+
+use crate::{Lexer, OwnedRule, Rule};
+
+/// What is wrong with a rule, as found by [`Rule::validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// An `Any` without alternatives, which never matches.
+    EmptyAny,
+    /// An `All` without rules, which matches any slice.
+    EmptyAll,
+    /// An alternative of an `Any` that an earlier alternative always matches
+    /// first, such as a second `Literal("in")`, so it never decides a token.
+    Shadowed,
+    /// A keyword table that isn't sorted by keyword, which binary search
+    /// would miss keywords in.
+    UnsortedKeywords,
+    /// A top-level rule, or an alternative of a top-level `Any`, that matches
+    /// the empty string, so the lexer could never make progress with it.
+    MatchesEmpty,
+    /// A `Literal("")`, or `Literals` with an empty literal, anywhere in the
+    /// tree, which matches nothing but the empty slice.
+    EmptyLiteral,
+    /// An `Ignore` directly around a rule that makes a token, such as a
+    /// `Value`, whose token is always dropped.
+    IgnoredValue,
+}
+
+/// A problem with a rule tree, along with where it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleProblem {
+    pub kind: ProblemKind,
+    /// Indices of the children to follow from the root to the offending
+    /// rule, empty for the root itself.
+    pub path: Vec<usize>,
+}
+
+impl fmt::Display for RuleProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ProblemKind::EmptyAny => f.write_str("`Any` without alternatives")?,
+            ProblemKind::EmptyAll => f.write_str("`All` without rules")?,
+            ProblemKind::Shadowed => f.write_str("alternative shadowed by an earlier one")?,
+            ProblemKind::MatchesEmpty => f.write_str("rule matches the empty string")?,
+            ProblemKind::UnsortedKeywords => f.write_str("keywords are not sorted")?,
+            ProblemKind::EmptyLiteral => f.write_str("empty literal")?,
+            ProblemKind::IgnoredValue => f.write_str("`Ignore` dropping the token of a `Value`")?,
+        }
+
+        match self.path.split_first() {
+            Some((first, rest)) => {
+                write!(f, " at {first}")?;
+
+                for index in rest {
+                    write!(f, ".{index}")?;
+                }
+
+                Ok(())
+            }
+            None => f.write_str(" at the root"),
+        }
+    }
+}
+
+/// Every problem found in a rule tree, as returned by [`Rule::validate`] and
+/// [`Lexer::try_new`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleError {
+    pub problems: Vec<RuleProblem>,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid rule: ")?;
+
+        for (index, problem) in self.problems.iter().enumerate() {
+            if index > 0 {
+                f.write_str("; ")?;
+            }
+
+            problem.fmt(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl core::error::Error for RuleError {}
+
+impl<'a, T> Rule<'a, T> {
+    /// Checks the rule tree for shapes that can never lex correctly, listing
+    /// every problem found.
+    ///
+    /// Nodes reachable through several references are only checked once, and
+    /// a reference cycle between statics is cut where it closes.
+    pub fn validate(&self) -> Result<(), RuleError> {
+        let mut walker = Walker::default();
+
+        match self {
+            Rule::Any(rules) => {
+                for (index, rule) in rules.iter().enumerate() {
+                    if Node::Rule(rule).nullable(&mut Vec::new()) {
+                        walker.report(ProblemKind::MatchesEmpty, Vec::from([index]));
+                    }
+                }
+            }
+            _ if Node::Rule(self).nullable(&mut Vec::new()) => {
+                walker.report(ProblemKind::MatchesEmpty, Vec::new());
+            }
+            _ => {}
+        }
+
+        walker.walk(Node::Rule(self));
+
+        match walker.problems.is_empty() {
+            true => Ok(()),
+            false => Err(RuleError {
+                problems: walker.problems,
+            }),
+        }
+    }
+}
+
+impl<'a, T> Lexer<'a, T> {
+    /// Creates a lexer like [`Lexer::new`], after checking the rule with
+    /// [`Rule::validate`].
+    pub fn try_new(rule: Rule<'a, T>) -> Result<Self, RuleError> {
+        rule.validate()?;
+        Ok(Self::new(rule))
+    }
+
+    /// Checks the rule and the rules of every mode with [`Rule::validate`],
+    /// stopping at the first that has problems.
+    ///
+    /// Debug builds call this on the first step, so that a lexer made with
+    /// [`Lexer::new`] is checked too.
+    pub fn debug_validate(&self) -> Result<(), RuleError> {
+        let modes = self.modes.iter().map(|(_, rule)| rule);
+
+        core::iter::once(&self.rule)
+            .chain(modes)
+            .try_for_each(Rule::validate)
+    }
+}
+
+/// A node of a mixed tree of borrowed and owned rules.
+enum Node<'r, 'a, T> {
+    Rule(&'r Rule<'a, T>),
+    Owned(&'r OwnedRule<'a, T>),
+}
+
+impl<'r, 'a, T> Node<'r, 'a, T> {
+    /// Identifies the node by its address and whether it is an owned node.
+    fn key(&self) -> (usize, bool) {
+        match self {
+            Node::Rule(rule) => (*rule as *const Rule<'a, T> as usize, false),
+            Node::Owned(rule) => (*rule as *const OwnedRule<'a, T> as usize, true),
+        }
+    }
+
+    fn children(&self) -> Vec<Node<'r, 'a, T>> {
+        match self {
+            Node::Rule(rule) => match rule {
+                Rule::Value(rule, _)
+                | Rule::ValueFilter(rule, _)
+                | Rule::ValueMany(rule, _)
+                | Rule::ValueCow(rule, _)
+                | Rule::Ignore(rule)
+                | Rule::Not(rule)
+                | Rule::Only(rule)
+                | Rule::Priority(_, rule)
+                | Rule::AtLineStart(rule)
+                | Rule::Gated(rule)
+                | Rule::PushMode(rule, _)
+                | Rule::PopMode(rule)
+                | Rule::Signed(rule)
+                | Rule::IdentifierOrKeyword { ident: rule, .. }
+                | Rule::Repeat { rule, .. }
+                | Rule::Embed { open: rule, .. } => Vec::from([Node::Rule(*rule)]),
+                Rule::Both(a, b)
+                | Rule::Either(a, b)
+                | Rule::Except(a, b)
+                | Rule::TrailingContext { main: a, trail: b }
+                | Rule::Commit {
+                    prefix: a, rule: b, ..
+                } => Vec::from([Node::Rule(*a), Node::Rule(*b)]),
+                Rule::All(rules, _) | Rule::Seq(rules) | Rule::Any(rules) => {
+                    rules.iter().map(Node::Rule).collect()
+                }
+                Rule::Owned(rule) => Vec::from([Node::Owned(*rule)]),
+                _ => Vec::new(),
+            },
+            Node::Owned(rule) => match rule {
+                OwnedRule::Rule(rule) => Vec::from([Node::Rule(*rule)]),
+                OwnedRule::Inline(rule) => Vec::from([Node::Rule(rule)]),
+                OwnedRule::IdentifierOrKeyword { ident: rule, .. } => {
+                    Vec::from([Node::Rule(*rule)])
+                }
+                OwnedRule::Value(rule, _)
+                | OwnedRule::Named(rule, ..)
+                | OwnedRule::Ignore(rule)
+                | OwnedRule::Not(rule)
+                | OwnedRule::Only(rule)
+                | OwnedRule::Repeat { rule, .. } => Vec::from([Node::Owned(rule)]),
+                OwnedRule::Both(a, b) | OwnedRule::Either(a, b) => {
+                    Vec::from([Node::Owned(a), Node::Owned(b)])
+                }
+                OwnedRule::All(rules, _) | OwnedRule::Seq(rules) | OwnedRule::Any(rules) => {
+                    rules.iter().map(Node::Owned).collect()
+                }
+                OwnedRule::Literals(_) => Vec::new(),
+            },
+        }
+    }
+
+    /// Returns whether the node can match an empty slice. Character classes
+    /// such as [`Rule::Whitespace`] count as needing a character, since the
+    /// lexer never asks them about an empty slice.
+    fn nullable(&self, stack: &mut Vec<(usize, bool)>) -> bool {
+        let key = self.key();
+
+        if stack.contains(&key) {
+            return false;
+        }
+
+        stack.push(key);
+
+        let children = self.children();
+        let any = |stack: &mut Vec<_>| children.iter().any(|child| child.nullable(stack));
+
+        let nullable = match self {
+            Node::Rule(rule) => match rule {
+                Rule::Literal(literal)
+                | Rule::LiteralIgnoreCase(literal)
+                | Rule::EndsWith(literal)
+                | Rule::StartsWith(literal)
+                | Rule::LineComment(literal) => literal.is_empty(),
+                Rule::Literals(literals) => literals.contains(&""),
+                Rule::Keywords(keywords) => keywords
+                    .first()
+                    .is_some_and(|(keyword, _)| keyword.is_empty()),
+                Rule::Not(_) => !any(stack),
+                Rule::Repeat { min: 0, .. } => true,
+                Rule::Both(..) | Rule::All(..) | Rule::Seq(_) => {
+                    children.iter().all(|child| child.nullable(stack))
+                }
+                Rule::TrailingContext { main, .. }
+                | Rule::Commit { rule: main, .. }
+                | Rule::Except(main, _) => Node::Rule(*main).nullable(stack),
+                _ => any(stack),
+            },
+            Node::Owned(rule) => match rule {
+                OwnedRule::Literals(literals) => literals.contains(&""),
+                OwnedRule::Not(_) => !any(stack),
+                OwnedRule::Repeat { min: 0, .. } => true,
+                OwnedRule::Both(..) | OwnedRule::All(..) | OwnedRule::Seq(_) => {
+                    children.iter().all(|child| child.nullable(stack))
+                }
+                _ => any(stack),
+            },
+        };
+
+        stack.pop();
+        nullable
+    }
+}
+
+/// Returns whether `earlier`, an alternative listed before `later` in the
+/// same `Any`, fully matches every slice that `later` does, ignoring the
+/// tokens they produce, without `later` ranking above it.
+fn shadows<'a, T>(earlier: &Rule<'a, T>, later: &Rule<'a, T>) -> bool {
+    fn matcher<'r, 'a, T>(rule: &'r Rule<'a, T>) -> &'r Rule<'a, T> {
+        match rule {
+            Rule::Value(rule, _) | Rule::Ignore(rule) | Rule::Priority(_, rule) => matcher(rule),
+            _ => rule,
+        }
+    }
+
+    if later.priority() > earlier.priority() {
+        return false;
+    }
+
+    match (matcher(earlier), matcher(later)) {
+        // Never matching anything, it is reported as `EmptyAny` instead.
+        (Rule::Any([]), _) => false,
+        (Rule::Literals(earlier), Rule::Literal(literal)) => earlier.contains(literal),
+        (Rule::Literals(earlier), Rule::Literals(literals)) => {
+            literals.iter().all(|literal| earlier.contains(literal))
+        }
+        (earlier, later) => earlier == later,
+    }
+}
+
+#[derive(Default)]
+struct Walker {
+    problems: Vec<RuleProblem>,
+    visited: BTreeSet<(usize, bool)>,
+    path: Vec<usize>,
+}
+
+impl Walker {
+    fn report(&mut self, kind: ProblemKind, path: Vec<usize>) {
+        self.problems.push(RuleProblem { kind, path });
+    }
+
+    /// Checks `node` and its descendants.
+    fn walk<T>(&mut self, node: Node<'_, '_, T>) {
+        if !self.visited.insert(node.key()) {
+            return;
+        }
+
+        let empty_any = match node {
+            Node::Rule(Rule::Any(rules)) => rules.is_empty(),
+            Node::Owned(OwnedRule::Any(rules)) => rules.is_empty(),
+            _ => false,
+        };
+
+        if empty_any {
+            self.report(ProblemKind::EmptyAny, self.path.clone());
+        }
+
+        let empty_all = match node {
+            Node::Rule(Rule::All(rules, _)) => rules.is_empty(),
+            Node::Owned(OwnedRule::All(rules, _)) => rules.is_empty(),
+            _ => false,
+        };
+
+        if empty_all {
+            self.report(ProblemKind::EmptyAll, self.path.clone());
+        }
+
+        if let Node::Rule(Rule::Any(rules)) = node {
+            for (index, rule) in rules.iter().enumerate() {
+                if rules[..index].iter().any(|earlier| shadows(earlier, rule)) {
+                    let mut path = self.path.clone();
+                    path.push(index);
+                    self.report(ProblemKind::Shadowed, path);
+                }
+            }
+        }
+
+        let sorted = match node {
+            Node::Rule(Rule::Keywords(keywords))
+            | Node::Rule(Rule::IdentifierOrKeyword { keywords, .. }) => {
+                keywords.is_sorted_by(|(a, _), (b, _)| a < b)
+            }
+            Node::Owned(OwnedRule::IdentifierOrKeyword { keywords, .. }) => {
+                keywords.is_sorted_by(|(a, _), (b, _)| a < b)
+            }
+            _ => true,
+        };
+
+        if !sorted {
+            self.report(ProblemKind::UnsortedKeywords, self.path.clone());
+        }
+
+        let empty_literal = match node {
+            Node::Rule(Rule::Literal(literal)) => literal.is_empty(),
+            Node::Rule(Rule::Literals(literals)) => literals.contains(&""),
+            Node::Owned(OwnedRule::Literals(literals)) => literals.contains(&""),
+            _ => false,
+        };
+
+        if empty_literal {
+            self.report(ProblemKind::EmptyLiteral, self.path.clone());
+        }
+
+        let ignored = match node {
+            Node::Rule(Rule::Ignore(rule)) => matches!(
+                rule,
+                Rule::Value(..)
+                    | Rule::ValueFilter(..)
+                    | Rule::ValueMany(..)
+                    | Rule::ValueCow(..)
+                    | Rule::All(..)
+            ),
+            Node::Owned(OwnedRule::Ignore(rule)) => matches!(
+                **rule,
+                OwnedRule::Value(..) | OwnedRule::Named(..) | OwnedRule::All(..)
+            ),
+            _ => false,
+        };
+
+        if ignored {
+            self.report(ProblemKind::IgnoredValue, self.path.clone());
+        }
+
+        for (index, child) in node.children().into_iter().enumerate() {
+            self.path.push(index);
+            self.walk(child);
+            self.path.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, string::ToString};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Token;
+
+    static LOOP: Rule<'static, Token> = Rule::Not(&Rule::Ignore(&LOOP));
+
+    fn kinds(rule: &Rule<'_, Token>) -> Vec<ProblemKind> {
+        rule.validate()
+            .unwrap_err()
+            .problems
+            .into_iter()
+            .map(|problem| problem.kind)
+            .collect()
+    }
+
+    #[test]
+    fn valid() {
+        let rule = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Alphabetic, &|_| Token),
+            Rule::Seq(&[
+                Rule::Literal("-"),
+                Rule::Repeat {
+                    rule: &Rule::Numeric,
+                    min: 0,
+                    max: Some(1),
+                },
+            ]),
+        ]);
+
+        assert!(rule.validate().is_ok());
+        assert!(Lexer::try_new(rule).is_ok());
+    }
+
+    #[test]
+    fn empty_any() {
+        let rule = Rule::Any(&[Rule::Value(&Rule::Any(&[]), &|_| Token)]);
+        let error = rule.validate().unwrap_err();
+
+        assert_eq!(
+            error.problems,
+            Vec::from([RuleProblem {
+                kind: ProblemKind::EmptyAny,
+                path: Vec::from([0, 0]),
+            }])
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid rule: `Any` without alternatives at 0.0"
+        );
+
+        let owned = OwnedRule::<Token>::Any(Vec::new());
+
+        assert_eq!(kinds(&owned.as_rule()), [ProblemKind::EmptyAny]);
+    }
+
+    #[test]
+    fn matches_empty() {
+        let optional = Rule::Repeat {
+            rule: &Rule::Numeric,
+            min: 0,
+            max: Some(1),
+        };
+
+        assert_eq!(kinds(&optional), [ProblemKind::MatchesEmpty]);
+        assert_eq!(
+            Rule::<Token>::Any(&[Rule::Alphabetic, Rule::Not(&Rule::Alphabetic)])
+                .validate()
+                .unwrap_err()
+                .problems,
+            [RuleProblem {
+                kind: ProblemKind::MatchesEmpty,
+                path: Vec::from([1]),
+            }]
+        );
+
+        let owned =
+            OwnedRule::<Token>::Any(Vec::from([OwnedRule::Rule(&Rule::Not(&Rule::Alphabetic))]));
+
+        assert_eq!(kinds(&owned.as_rule()), [ProblemKind::MatchesEmpty]);
+    }
+
+    #[test]
+    fn empty_literal() {
+        let rule = Rule::Value(
+            &Rule::Seq(&[
+                Rule::Literal("-"),
+                Rule::Either(&Rule::Literal(""), &Rule::Numeric),
+                Rule::Literals(&["a", ""]),
+            ]),
+            &|_| Token,
+        );
+        let error = rule.validate().unwrap_err();
+
+        assert_eq!(
+            error.problems,
+            [
+                RuleProblem {
+                    kind: ProblemKind::EmptyLiteral,
+                    path: Vec::from([0, 1, 0]),
+                },
+                RuleProblem {
+                    kind: ProblemKind::EmptyLiteral,
+                    path: Vec::from([0, 2]),
+                },
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid rule: empty literal at 0.1.0; empty literal at 0.2"
+        );
+
+        let owned = OwnedRule::<Token>::Seq(Vec::from([
+            OwnedRule::Rule(&Rule::Literal("-")),
+            OwnedRule::Literals(Vec::from(["a", ""])),
+        ]));
+
+        assert_eq!(kinds(&owned.as_rule()), [ProblemKind::EmptyLiteral]);
+    }
+
+    #[test]
+    fn ignored_value() {
+        let rule = Rule::Any(&[
+            Rule::Ignore(&Rule::Value(&Rule::Alphabetic, &|_| Token)),
+            Rule::Literal("x"),
+        ]);
+        let error = rule.validate().unwrap_err();
+
+        assert_eq!(
+            error.problems,
+            [RuleProblem {
+                kind: ProblemKind::IgnoredValue,
+                path: Vec::from([0]),
+            }]
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid rule: `Ignore` dropping the token of a `Value` at 0"
+        );
+        assert!(
+            Rule::<Token>::Ignore(&Rule::Value(&Rule::Literal("x"), &|_| Token))
+                .validate()
+                .is_err()
+        );
+
+        let owned = OwnedRule::Ignore(Box::new(OwnedRule::Value(
+            Box::new(OwnedRule::Rule(&Rule::Literal("x"))),
+            &|_| Token,
+        )));
+
+        assert_eq!(kinds(&owned.as_rule()), [ProblemKind::IgnoredValue]);
+    }
+
+    #[test]
+    fn every_problem() {
+        let rule = Rule::Any(&[
+            Rule::Literal(""),
+            Rule::Any(&[]),
+            Rule::Value(&Rule::Any(&[]), &|_| Token),
+        ]);
+
+        assert_eq!(
+            kinds(&rule),
+            [
+                ProblemKind::MatchesEmpty,
+                ProblemKind::EmptyLiteral,
+                ProblemKind::EmptyAny,
+                ProblemKind::EmptyAny,
+            ]
+        );
+        assert!(Lexer::try_new(rule).is_err());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "invalid rule: `Any` without alternatives at the root")]
+    fn checked_on_first_step() {
+        let _ = Lexer::<Token>::with_buffer(Rule::Any(&[]), "a").next();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn checked_once() {
+        let mut lexer = Lexer::with_buffer(Rule::Value(&Rule::Alphabetic, &|_| Token), "a b");
+        assert!(!lexer.validated);
+
+        lexer.step().unwrap();
+        assert!(lexer.validated);
+
+        // Resetting doesn't check the same rules again.
+        lexer.reset("c");
+        assert!(lexer.validated);
+    }
+
+    #[test]
+    fn debug_validate() {
+        let lexer = Lexer::with_buffer(Rule::Value(&Rule::Alphabetic, &|_| Token), "a")
+            .with_modes(&[("broken", Rule::Any(&[]))]);
+
+        assert_eq!(
+            lexer.debug_validate().unwrap_err().to_string(),
+            "invalid rule: `Any` without alternatives at the root"
+        );
+    }
+
+    #[test]
+    fn empty_all() {
+        let rule = Rule::Any(&[
+            Rule::Alphabetic,
+            Rule::Both(&Rule::Numeric, &Rule::All(&[], &|_| Token)),
+        ]);
+
+        assert_eq!(
+            rule.validate().unwrap_err().problems,
+            [RuleProblem {
+                kind: ProblemKind::EmptyAll,
+                path: Vec::from([1, 1]),
+            }]
+        );
+    }
+
+    #[test]
+    fn shadowed() {
+        let rule = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Literal("in"), &|_| Token),
+            Rule::Literals(&["do", "end"]),
+            Rule::Value(&Rule::Literal("in"), &|_| Token),
+            Rule::Literal("end"),
+            Rule::Literals(&["end", "do"]),
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Literal("if"),
+        ]);
+        let error = rule.validate().unwrap_err();
+
+        assert_eq!(
+            error
+                .problems
+                .iter()
+                .map(|problem| (problem.kind, problem.path.as_slice()))
+                .collect::<Vec<_>>(),
+            [
+                (ProblemKind::Shadowed, &[3][..]),
+                (ProblemKind::Shadowed, &[4]),
+                (ProblemKind::Shadowed, &[5]),
+                (ProblemKind::Shadowed, &[6]),
+            ]
+        );
+        assert!(error
+            .to_string()
+            .starts_with("invalid rule: alternative shadowed by an earlier one at 3; "));
+    }
+
+    #[test]
+    fn unsorted_keywords() {
+        let sorted = Rule::Keywords(&[("else", || Token), ("elseif", || Token)]);
+        let unsorted = Rule::Keywords(&[("elseif", || Token), ("else", || Token)]);
+        let duplicate = Rule::IdentifierOrKeyword {
+            ident: &Rule::Alphabetic,
+            keywords: &[("do", || Token), ("do", || Token)],
+            fallback: |_| Token,
+        };
+
+        assert!(sorted.validate().is_ok());
+        assert_eq!(kinds(&unsorted), [ProblemKind::UnsortedKeywords]);
+        assert_eq!(kinds(&duplicate), [ProblemKind::UnsortedKeywords]);
+    }
+
+    #[test]
+    fn cycle() {
+        assert_eq!(kinds(&LOOP), [ProblemKind::MatchesEmpty]);
+    }
+}
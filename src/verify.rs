@@ -0,0 +1,168 @@
+use alloc::{format, string::String, vec::Vec};
+use core::{fmt, ops::Range};
+
+use crate::{Lexer, Named, Rule, TokenName};
+
+/// Whether a [`CoverageGap`] is uncovered input or input covered twice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapKind {
+    Gap,
+    Overlap,
+}
+
+/// The first place where lexing didn't account for every byte of the input
+/// exactly once, as reported by [`verify_lossless`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageGap {
+    pub kind: GapKind,
+    pub range: Range<usize>,
+    /// Name of the segment before the gap, `"trivia"` for skipped text.
+    pub before: Option<String>,
+    /// Name of the segment after the gap, `"trivia"` for skipped text.
+    pub after: Option<String>,
+}
+
+impl fmt::Display for CoverageGap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            GapKind::Gap => "uncovered input",
+            GapKind::Overlap => "input covered twice",
+        };
+
+        write!(
+            f,
+            "{kind} at {}..{} between {} and {}",
+            self.range.start,
+            self.range.end,
+            self.before.as_deref().unwrap_or("start of input"),
+            self.after.as_deref().unwrap_or("end of input"),
+        )
+    }
+}
+
+/// Lexes `input` while retaining trivia and checks that every byte is covered
+/// by exactly one token or trivia segment.
+pub fn verify_lossless<'a, T: TokenName>(
+    input: &'a str,
+    rule: &'a Rule<'a, T>,
+) -> Result<(), CoverageGap> {
+    let mut lexer = Lexer::with_buffer(Rule::Only(rule), input);
+    let mut segments = Vec::new();
+
+    loop {
+        let start = lexer.index.0;
+
+        match lexer.step() {
+            Ok(Some(token)) => segments.push((start..lexer.index.0, format!("{}", Named(&token)))),
+            Ok(None) if lexer.index.0 > start => {
+                segments.push((start..lexer.index.0, String::from("trivia")));
+            }
+            Ok(None) => {}
+            Err(_) => break,
+        }
+    }
+
+    check_coverage(input.len(), segments)
+}
+
+/// Checks that `segments`, ordered by start, cover `0..len` exactly once.
+fn check_coverage(len: usize, segments: Vec<(Range<usize>, String)>) -> Result<(), CoverageGap> {
+    let mut cursor = 0;
+    let mut before = None;
+
+    for (range, name) in segments {
+        let kind = match range.start {
+            start if start > cursor => Some((GapKind::Gap, cursor..start)),
+            start if start < cursor => Some((GapKind::Overlap, start..cursor)),
+            _ => None,
+        };
+
+        if let Some((kind, range)) = kind {
+            return Err(CoverageGap {
+                kind,
+                range,
+                before,
+                after: Some(name),
+            });
+        }
+
+        cursor = range.end;
+        before = Some(name);
+    }
+
+    if cursor < len {
+        return Err(CoverageGap {
+            kind: GapKind::Gap,
+            range: cursor..len,
+            before,
+            after: None,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    enum Token {
+        End,
+    }
+
+    impl TokenName for Token {
+        fn name(&self) -> &'static str {
+            "'end'"
+        }
+    }
+
+    #[test]
+    fn broken_rule() {
+        let rule = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Literal("end"), &|_| Token::End),
+        ]);
+
+        let gap = verify_lossless("end @ end\n", &rule).unwrap_err();
+
+        assert_eq!(gap.kind, GapKind::Gap);
+        assert_eq!(gap.range, 4..10);
+        assert_eq!(
+            gap.to_string(),
+            "uncovered input at 4..10 between trivia and end of input"
+        );
+    }
+
+    #[test]
+    fn final_character() {
+        let rule = Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Literal("end"), &|_| Token::End),
+        ]);
+
+        assert!(verify_lossless("end end\n", &rule).is_ok());
+        assert!(verify_lossless("end end", &rule).is_ok());
+        assert!(verify_lossless("", &rule).is_ok());
+
+        let gap = verify_lossless("end en", &rule).unwrap_err();
+
+        assert_eq!(gap.range, 4..6);
+        assert_eq!(gap.before.as_deref(), Some("trivia"));
+    }
+
+    #[test]
+    fn overlap() {
+        let gap = check_coverage(
+            4,
+            Vec::from([(0..3, "a".to_string()), (2..4, "b".to_string())]),
+        )
+        .unwrap_err();
+
+        assert_eq!(gap.kind, GapKind::Overlap);
+        assert_eq!(gap.range, 2..3);
+        assert_eq!(gap.before.as_deref(), Some("a"));
+        assert_eq!(gap.after.as_deref(), Some("b"));
+    }
+}
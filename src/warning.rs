@@ -0,0 +1,184 @@
+use alloc::string::String;
+use core::{fmt, ops::Range};
+
+use crate::Lexer;
+
+/// What a [`LexWarning`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningKind {
+    /// Indentation mixing tabs and spaces, found by [`mixed_indentation`].
+    MixedIndentation,
+    /// A decimal number with a redundant leading zero, found by
+    /// [`leading_zero`].
+    LeadingZero,
+    /// Anything else, reported by a custom check or [`Lexer::warn`].
+    Other,
+}
+
+/// A condition worth reporting that doesn't stop lexing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexWarning {
+    pub span: Range<usize>,
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+impl LexWarning {
+    pub fn new(span: Range<usize>, kind: WarningKind, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+/// Shows the warning as a warning, with its span, so that it reads apart
+/// from an [`Error`](crate::Error) wherever both are rendered.
+impl fmt::Display for LexWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "warning: {} at bytes {}..{}",
+            self.message, self.span.start, self.span.end
+        )
+    }
+}
+
+/// Inspects the span of the buffer the lexer just committed, whether it
+/// produced a token or was skipped, for [`Lexer::with_checks`].
+pub type WarningCheck = fn(&str, Range<usize>) -> Option<LexWarning>;
+
+/// Warns about the first line indented with both tabs and spaces in `span`.
+pub fn mixed_indentation(buffer: &str, span: Range<usize>) -> Option<LexWarning> {
+    let mut line_start = buffer[..span.start].ends_with('\n') || span.start == 0;
+
+    for (offset, c) in buffer[span.clone()].char_indices() {
+        let start = span.start + offset;
+
+        if line_start {
+            let indent = buffer[start..span.end]
+                .find(|c| c != ' ' && c != '\t')
+                .map_or(span.end, |len| start + len);
+            let indentation = &buffer[start..indent];
+
+            if indentation.contains(' ') && indentation.contains('\t') {
+                return Some(LexWarning::new(
+                    start..indent,
+                    WarningKind::MixedIndentation,
+                    "indentation mixes tabs and spaces",
+                ));
+            }
+        }
+
+        line_start = c == '\n';
+    }
+
+    None
+}
+
+/// Warns about a decimal number such as `007` whose leading zeros don't
+/// change its value.
+pub fn leading_zero(buffer: &str, span: Range<usize>) -> Option<LexWarning> {
+    let text = &buffer[span.clone()];
+
+    (text.len() > 1 && text.starts_with('0') && text.bytes().all(|byte| byte.is_ascii_digit()))
+        .then(|| {
+            LexWarning::new(
+                span,
+                WarningKind::LeadingZero,
+                "redundant leading zero in number",
+            )
+        })
+}
+
+impl<'a, T> Lexer<'a, T> {
+    /// Runs each of `checks` on every committed span, collecting their
+    /// warnings for [`Lexer::warnings`] without affecting the tokens.
+    pub const fn with_checks(mut self, checks: &'a [WarningCheck]) -> Self {
+        self.checks = checks;
+        self
+    }
+
+    /// Records a warning, for example from a token map or a parser
+    /// inspecting the tokens.
+    pub fn warn(&mut self, warning: LexWarning) {
+        self.warnings.push(warning);
+    }
+
+    /// Returns the warnings collected so far, in the order they were found.
+    pub fn warnings(&self) -> &[LexWarning] {
+        &self.warnings
+    }
+
+    /// Runs the checks on the span just committed.
+    pub(crate) fn check(&mut self, span: Range<usize>) {
+        for check in self.checks {
+            if let Some(warning) = check(self.buffer, span.clone()) {
+                self.warnings.push(warning);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+
+    use super::*;
+    use crate::Rule;
+
+    #[derive(Debug, PartialEq)]
+    enum Token<'a> {
+        Word(&'a str),
+        Number(&'a str),
+    }
+
+    const RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+        Rule::Ignore(&Rule::Whitespace),
+        Rule::Value(&Rule::Alphabetic, &Token::Word),
+        Rule::Value(&Rule::Numeric, &Token::Number),
+    ]);
+
+    const INPUT: &str = "a\n \tb 007 0 10\n\tc\n";
+
+    #[test]
+    fn collects_warnings() {
+        let mut lexer =
+            Lexer::with_buffer(RULE, INPUT).with_checks(&[mixed_indentation, leading_zero]);
+        let tokens = lexer.by_ref().flatten().collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            Lexer::with_buffer(RULE, INPUT)
+                .flatten()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            lexer.warnings(),
+            [
+                LexWarning::new(
+                    2..4,
+                    WarningKind::MixedIndentation,
+                    "indentation mixes tabs and spaces"
+                ),
+                LexWarning::new(
+                    6..9,
+                    WarningKind::LeadingZero,
+                    "redundant leading zero in number"
+                ),
+            ]
+        );
+
+        assert_eq!(
+            lexer.warnings()[1].to_string(),
+            "warning: redundant leading zero in number at bytes 6..9"
+        );
+
+        lexer.warn(LexWarning::new(0..1, WarningKind::Other, "custom"));
+        assert_eq!(lexer.warnings().len(), 3);
+
+        lexer.reset(INPUT);
+        assert!(lexer.warnings().is_empty());
+    }
+}
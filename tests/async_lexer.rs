@@ -0,0 +1,130 @@
+#![cfg(feature = "tokio")]
+
+use std::{future::poll_fn, pin::Pin};
+
+use futures_core::Stream;
+use mile::{AsyncLexError, AsyncLexer, ErrorKind, Grammar, Lexer, Rule};
+use tokio::io::{AsyncWriteExt, BufReader};
+
+const INPUT: &str = r#"
+function add(a, b)
+    return a + b
+end
+
+local function twice(f, x)
+    return f(f(x))
+end
+"#;
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    End,
+    Function,
+    Local,
+    Return,
+    Punctuation(String),
+    Identifier(String),
+}
+
+struct Lua;
+
+impl Grammar for Lua {
+    type Token = Token;
+
+    fn rule<'x>(&self) -> Rule<'x, Token> {
+        Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Literal("end"), &|_| Token::End),
+            Rule::Value(&Rule::Literal("function"), &|_| Token::Function),
+            Rule::Value(&Rule::Literal("local"), &|_| Token::Local),
+            Rule::Value(&Rule::Literal("return"), &|_| Token::Return),
+            Rule::Value(&Rule::Literals(&["(", ")", ",", "+"]), &|value| {
+                Token::Punctuation(value.into())
+            }),
+            Rule::Value(&Rule::Alphabetic, &|value| Token::Identifier(value.into())),
+        ])
+    }
+}
+
+async fn lex_in_chunks(chunk: usize, capacity: usize) -> Vec<Token> {
+    let (mut writer, reader) = tokio::io::duplex(capacity);
+
+    let write = tokio::spawn(async move {
+        for piece in INPUT.as_bytes().chunks(chunk) {
+            writer.write_all(piece).await.unwrap();
+            tokio::task::yield_now().await;
+        }
+    });
+
+    let mut lexer = AsyncLexer::new(Lua, BufReader::with_capacity(capacity, reader));
+    let mut tokens = Vec::new();
+
+    while let Some(token) = poll_fn(|cx| Pin::new(&mut lexer).poll_next(cx)).await {
+        tokens.push(token.unwrap());
+        assert!(lexer.consumed_bytes() <= lexer.fed_bytes());
+    }
+
+    assert_eq!(lexer.consumed_bytes(), INPUT.len());
+    assert_eq!(lexer.fed_bytes(), INPUT.len());
+    write.await.unwrap();
+    tokens
+}
+
+#[tokio::test]
+async fn matches_synchronous_lexing() {
+    let expected = Lexer::with_buffer(Lua.rule(), INPUT)
+        .flatten()
+        .collect::<Vec<_>>();
+
+    assert!(expected.contains(&Token::Local));
+
+    for (chunk, capacity) in [(1, 1), (2, 3), (3, 3), (5, 2), (7, 64), (INPUT.len(), 1024)] {
+        assert_eq!(
+            lex_in_chunks(chunk, capacity).await,
+            expected,
+            "{chunk}/{capacity}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn invalid_utf8_is_an_error() {
+    let reader: &[u8] = b"end \xff end ";
+    let mut lexer = AsyncLexer::new(Lua, reader);
+
+    let first = poll_fn(|cx| Pin::new(&mut lexer).poll_next(cx)).await;
+    let second = poll_fn(|cx| Pin::new(&mut lexer).poll_next(cx)).await;
+
+    assert!(matches!(first, Some(Err(AsyncLexError::Io(_)))));
+    assert!(second.is_none());
+}
+
+#[tokio::test]
+async fn lex_errors_end_the_stream() {
+    let reader: &[u8] = b"end\nlocal ? end";
+    let mut lexer = AsyncLexer::new(Lua, BufReader::with_capacity(2, reader));
+    let mut items = Vec::new();
+
+    while let Some(item) = poll_fn(|cx| Pin::new(&mut lexer).poll_next(cx)).await {
+        items.push(item);
+    }
+
+    match items.as_slice() {
+        [Ok(Token::End), Ok(Token::Local), Err(AsyncLexError::Lex(error))] => {
+            assert_eq!(error.kind, ErrorKind::UnknownToken);
+            assert_eq!(error.span, 10..11);
+            assert_eq!(error.to_string(), "unknown token `?` at 2:7");
+        }
+        items => panic!("unexpected items: {items:?}"),
+    }
+}
+
+#[tokio::test]
+async fn dropping_mid_stream() {
+    let mut lexer = AsyncLexer::new(Lua, INPUT.as_bytes());
+
+    let token = poll_fn(|cx| Pin::new(&mut lexer).poll_next(cx)).await;
+    assert!(matches!(token, Some(Ok(Token::Function))));
+
+    drop(lexer);
+}
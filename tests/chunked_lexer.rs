@@ -0,0 +1,193 @@
+#![cfg(feature = "alloc")]
+
+use mile::{ChunkedLexer, ErrorKind, Grammar, Lexer, Rule};
+
+const INPUT: &str = r#"
+local function fib(n)
+    if n then
+        return fib(n) + fib(n)
+    elseif n then
+        return n
+    end
+end
+"#;
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Else,
+    ElseIf,
+    End,
+    Function,
+    If,
+    Local,
+    Return,
+    Then,
+    Punctuation(String),
+    Identifier(String),
+}
+
+struct Lua;
+
+impl Grammar for Lua {
+    type Token = Token;
+
+    fn rule<'x>(&self) -> Rule<'x, Token> {
+        Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::IdentifierOrKeyword {
+                ident: &Rule::Alphabetic,
+                keywords: &[
+                    ("else", || Token::Else),
+                    ("elseif", || Token::ElseIf),
+                    ("end", || Token::End),
+                    ("function", || Token::Function),
+                    ("if", || Token::If),
+                    ("local", || Token::Local),
+                    ("return", || Token::Return),
+                    ("then", || Token::Then),
+                ],
+                fallback: |value| Token::Identifier(value.into()),
+            },
+            Rule::Value(&Rule::Literals(&["(", ")", "+"]), &|value| {
+                Token::Punctuation(value.into())
+            }),
+        ])
+    }
+}
+
+const GENERICS: &str = "map<list<int>> \"a >> b\" x >> y\n";
+
+#[derive(Debug, PartialEq)]
+enum Generic {
+    Word(String),
+    Lt,
+    Gt,
+    Quote,
+    Text(String),
+}
+
+/// Splits `>>` into two tokens and lexes string contents in a mode of their
+/// own, so both queued pieces and the mode stack span chunk boundaries.
+struct Generics;
+
+impl Grammar for Generics {
+    type Token = Generic;
+
+    fn rule<'x>(&self) -> Rule<'x, Generic> {
+        Rule::Any(&[
+            Rule::Ignore(&Rule::Whitespace),
+            Rule::Value(&Rule::Alphabetic, &|value| Generic::Word(value.into())),
+            Rule::Value(&Rule::Literal("<"), &|_| Generic::Lt),
+            Rule::ValueMany(&Rule::Literal(">>"), |_, emit| {
+                emit(0..1, Generic::Gt);
+                emit(1..2, Generic::Gt);
+            }),
+            Rule::Value(&Rule::Literal(">"), &|_| Generic::Gt),
+            Rule::PushMode(
+                &Rule::Value(&Rule::Literal("\""), &|_| Generic::Quote),
+                "string",
+            ),
+        ])
+    }
+
+    fn configure<'x>(&self, lexer: Lexer<'x, Generic>) -> Lexer<'x, Generic> {
+        lexer.with_modes(&[(
+            "string",
+            Rule::Any(&[
+                Rule::Value(&Rule::Chars(|c| c != '"'), &|value| {
+                    Generic::Text(value.into())
+                }),
+                Rule::PopMode(&Rule::Value(&Rule::Literal("\""), &|_| Generic::Quote)),
+            ]),
+        )])
+    }
+}
+
+fn lex_in_chunks<G: Grammar>(grammar: G, input: &str, chunk: usize) -> Vec<G::Token>
+where
+    G::Token: core::fmt::Debug,
+{
+    let mut lexer = ChunkedLexer::new(grammar);
+    let mut tokens = Vec::new();
+
+    for piece in input.as_bytes().chunks(chunk) {
+        lexer.feed(core::str::from_utf8(piece).unwrap());
+        tokens.extend(lexer.by_ref().map(Result::unwrap));
+    }
+
+    lexer.finish();
+    tokens.extend(lexer.map(Result::unwrap));
+    tokens
+}
+
+#[test]
+fn matches_single_buffer_lexing() {
+    let expected = Lexer::with_buffer(Lua.rule(), INPUT)
+        .flatten()
+        .collect::<Vec<_>>();
+
+    assert!(expected.contains(&Token::ElseIf));
+    assert!(!expected.contains(&Token::Else));
+
+    for chunk in [1, 2, 3, 5, INPUT.len()] {
+        assert_eq!(lex_in_chunks(Lua, INPUT, chunk), expected, "{chunk}");
+    }
+
+    let expected = Generics
+        .configure(Lexer::with_buffer(Generics.rule(), GENERICS))
+        .map(Result::unwrap)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        expected
+            .iter()
+            .filter(|&token| *token == Generic::Gt)
+            .count(),
+        4
+    );
+    assert!(expected.contains(&Generic::Text("a >> b".into())));
+
+    for chunk in 1..=GENERICS.len() {
+        assert_eq!(
+            lex_in_chunks(Generics, GENERICS, chunk),
+            expected,
+            "{chunk}"
+        );
+    }
+}
+
+#[test]
+fn waits_for_unambiguous_tokens() {
+    let mut lexer = ChunkedLexer::new(Lua);
+
+    lexer.feed("local fi");
+    assert_eq!(lexer.next(), Some(Ok(Token::Local)));
+    assert_eq!(lexer.next(), None);
+    assert_eq!((lexer.consumed_bytes(), lexer.fed_bytes()), (6, 8));
+
+    lexer.feed("b");
+    assert_eq!(lexer.next(), None);
+    assert_eq!((lexer.consumed_bytes(), lexer.fed_bytes()), (6, 9));
+
+    lexer.finish();
+    assert_eq!(lexer.next(), Some(Ok(Token::Identifier("fib".into()))));
+    assert_eq!(lexer.next(), None);
+    assert_eq!((lexer.consumed_bytes(), lexer.fed_bytes()), (9, 9));
+}
+
+#[test]
+fn stops_at_errors() {
+    let mut lexer = ChunkedLexer::new(Lua);
+
+    lexer.feed("end ? end");
+    lexer.finish();
+
+    assert_eq!(lexer.next(), Some(Ok(Token::End)));
+
+    let error = lexer.next().unwrap().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::UnknownToken);
+    assert_eq!(error.span, 4..5);
+    assert!(error.to_string().contains("`?`"), "{error}");
+
+    assert_eq!(lexer.next(), None);
+}
@@ -0,0 +1,162 @@
+//! Lexes the Lua subset of the unit tests with a rule derived from the token
+//! enum instead of a hand-written one.
+
+#![cfg(feature = "derive")]
+
+use mile::{Lexable, Lexer, Rule, TokenName};
+
+const TEST_CODE: &str = r#"
+local function add(a, b)
+    return a
+end
+if true then repeat x until false elseif y do nil end
+android orelse
+"#;
+
+#[derive(Debug, Clone, PartialEq, Lexable)]
+#[skip(whitespace)]
+enum Token<'a> {
+    #[token("and")]
+    And,
+    #[token("break")]
+    Break,
+    #[token("do")]
+    Do,
+    #[token("else")]
+    Else,
+    #[token("elseif")]
+    ElseIf,
+    #[token("end")]
+    End,
+    #[token("false")]
+    False,
+    #[token("for")]
+    For,
+    #[token("func")]
+    #[token("function")]
+    Function,
+    #[token("if")]
+    If,
+    #[token("in")]
+    In,
+    #[token("local")]
+    Local,
+    #[token("nil")]
+    Nil,
+    #[token("not")]
+    Not,
+    #[token("or")]
+    Or,
+    #[token("repeat")]
+    Repeat,
+    #[token("return")]
+    Return,
+    #[token("then")]
+    Then,
+    #[token("true")]
+    True,
+    #[token("until")]
+    Until,
+    #[token("while")]
+    While,
+    #[token("(")]
+    #[token(")")]
+    #[token(",")]
+    Punctuation(&'a str),
+    #[rule(alphabetic)]
+    Identifier(&'a str),
+    #[allow(dead_code)]
+    Assign,
+}
+
+fn lua<'a>() -> Rule<'a, Token<'a>> {
+    Rule::Any(&[
+        Rule::Ignore(&Rule::Whitespace),
+        Rule::IdentifierOrKeyword {
+            ident: &Rule::Alphabetic,
+            keywords: &[
+                ("and", || Token::And),
+                ("break", || Token::Break),
+                ("do", || Token::Do),
+                ("else", || Token::Else),
+                ("elseif", || Token::ElseIf),
+                ("end", || Token::End),
+                ("false", || Token::False),
+                ("for", || Token::For),
+                ("func", || Token::Function),
+                ("function", || Token::Function),
+                ("if", || Token::If),
+                ("in", || Token::In),
+                ("local", || Token::Local),
+                ("nil", || Token::Nil),
+                ("not", || Token::Not),
+                ("or", || Token::Or),
+                ("repeat", || Token::Repeat),
+                ("return", || Token::Return),
+                ("then", || Token::Then),
+                ("true", || Token::True),
+                ("until", || Token::Until),
+                ("while", || Token::While),
+            ],
+            fallback: Token::Identifier,
+        },
+        Rule::Value(&Rule::Literals(&["(", ")", ","]), &Token::Punctuation),
+    ])
+}
+
+#[test]
+fn matches_hand_written_rule() {
+    let derived = Token::lexer(TEST_CODE).collect::<Result<Vec<_>, _>>();
+
+    assert_eq!(
+        derived,
+        Lexer::with_buffer(lua(), TEST_CODE).collect::<Result<Vec<_>, _>>()
+    );
+    assert_eq!(derived.unwrap().len(), 25);
+}
+
+#[test]
+fn declaration_order() {
+    #[derive(Debug, PartialEq, Lexable)]
+    #[skip(whitespace)]
+    enum Word {
+        #[rule(alphabetic)]
+        Word,
+        #[token("in")]
+        In,
+    }
+
+    let words = Word::lexer("in inner\n").collect::<Result<Vec<_>, _>>();
+
+    assert_eq!(words, Ok(Vec::from([Word::Word, Word::Word])));
+}
+
+#[test]
+fn token_names() {
+    #[derive(Debug, Lexable, TokenName)]
+    #[skip(whitespace)]
+    enum Token<'a> {
+        #[token("local")]
+        Local,
+        #[token("=")]
+        #[name("'='")]
+        Assign,
+        #[rule(alphabetic)]
+        #[name("identifier")]
+        Identifier(&'a str),
+    }
+
+    assert_eq!(Token::Local.name(), "Local");
+    assert_eq!(Token::Identifier("x").name(), "identifier");
+
+    let mut lexer = Token::lexer("local = x\n");
+    lexer.expect_token(&Token::Local).unwrap();
+
+    let error = lexer.expect_token(&Token::Identifier("")).unwrap_err();
+    assert_eq!(error.to_string(), "expected identifier, found `=` at 1:7");
+    assert_eq!(lexer.expect_token(&Token::Assign).unwrap().span, 6..7);
+    assert!(matches!(
+        lexer.expect_token(&Token::Identifier("")).unwrap().value,
+        Token::Identifier("x")
+    ));
+}
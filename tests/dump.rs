@@ -0,0 +1,69 @@
+//! Runs `mile-dump` on the grammar and inputs in `tests/fixtures/dump`,
+//! comparing its output with the snapshots next to them.
+
+use assert_cmd::Command;
+
+const DIR: &str = "tests/fixtures/dump";
+
+fn dump(args: &[&str]) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("mile-dump")
+        .unwrap()
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .assert()
+}
+
+fn snapshot(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/{DIR}/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+}
+
+#[test]
+fn tokens() {
+    let (spec, input) = (format!("{DIR}/lua.toml"), format!("{DIR}/double.lua"));
+
+    dump(&[&spec, &input])
+        .success()
+        .stdout(snapshot("double.txt"))
+        .stderr("");
+    dump(&["--json", &spec, &input])
+        .success()
+        .stdout(snapshot("double.json"));
+}
+
+#[test]
+fn diagnostics() {
+    let (spec, input) = (format!("{DIR}/lua.toml"), format!("{DIR}/broken.lua"));
+
+    dump(&[&spec, &input])
+        .code(1)
+        .stdout(snapshot("broken.txt"))
+        .stderr(snapshot("broken.stderr"));
+    dump(&["--json", &spec, &input])
+        .code(1)
+        .stdout(snapshot("broken.json"));
+}
+
+#[test]
+fn warnings() {
+    let (spec, input) = (format!("{DIR}/lua.toml"), format!("{DIR}/warned.lua"));
+
+    dump(&[&spec, &input])
+        .success()
+        .stdout(snapshot("warned.txt"))
+        .stderr(snapshot("warned.stderr"));
+    dump(&["--json", &spec, &input])
+        .success()
+        .stdout(snapshot("warned.json"));
+}
+
+#[test]
+fn usage() {
+    dump(&[&format!("{DIR}/lua.toml")])
+        .code(2)
+        .stderr("usage: mile-dump [--json] <spec.toml> <input>\n");
+
+    let input = format!("{DIR}/double.lua");
+    let assert = dump(&[&input, &input]).code(2);
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert!(stderr.starts_with("error: invalid spec"), "{stderr}");
+}
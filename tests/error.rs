@@ -0,0 +1,139 @@
+//! Uses [`Error`] the way an application would: formatting it, comparing it
+//! and propagating it with `?`.
+
+use mile::{Error, Lexer, Rule};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'a> {
+    Word(&'a str),
+}
+
+const RULE: Rule<'static, Token<'static>> = Rule::Any(&[
+    Rule::Ignore(&Rule::Whitespace),
+    Rule::Value(&Rule::Alphabetic, &Token::Word),
+]);
+
+#[test]
+fn messages() {
+    let table = [
+        (Error::Eof, "end of input"),
+        (
+            Error::UnknownToken {
+                text: "@",
+                at: 4,
+                line: 2,
+                column: 1,
+            },
+            "unknown token `@` at 2:1",
+        ),
+        (
+            Error::Ambiguous {
+                at: 3,
+                rules: [0, 2],
+            },
+            "ambiguous token at byte 3: alternatives 0 and 2 both match",
+        ),
+        (
+            Error::Committed {
+                at: 5,
+                rule: 1,
+                tag: None,
+            },
+            "alternative 1 failed after committing at byte 5",
+        ),
+        (
+            Error::Committed {
+                at: 5,
+                rule: 1,
+                tag: Some("string"),
+            },
+            "invalid string at byte 5: alternative 1 failed after committing",
+        ),
+        (
+            Error::Unterminated { open: 2..4, eof: 9 },
+            "unterminated delimiter at bytes 2..4, input ends at byte 9",
+        ),
+        (
+            Error::Unexpected {
+                expected: "word",
+                found: Some("42"),
+                span: 0..2,
+                line: 1,
+                column: 1,
+            },
+            "expected word, found `42` at 1:1",
+        ),
+        (
+            Error::Unexpected {
+                expected: "word",
+                found: None,
+                span: 7..7,
+                line: 3,
+                column: 2,
+            },
+            "expected word, found end of input at 3:2",
+        ),
+        (
+            Error::ModeOverflow { at: 8 },
+            "more than 16 nested modes at byte 8",
+        ),
+        (
+            Error::TooManyTokens { at: 3 },
+            "token at byte 3 split into more than 4 tokens",
+        ),
+        (
+            Error::Overflow {
+                produced: 4,
+                offset: 10,
+            },
+            "token buffer full after 4 tokens, next token at byte 10",
+        ),
+    ];
+
+    for (error, message) in table {
+        assert_eq!(error.to_string(), message);
+    }
+}
+
+#[test]
+fn unknown_token() {
+    let mut lexer = Lexer::with_buffer(RULE, "ab\n cd @ ef\n");
+    let error = lexer.by_ref().find_map(Result::err);
+
+    assert_eq!(
+        error,
+        Some(Error::UnknownToken {
+            text: "@",
+            at: 7,
+            line: 2,
+            column: 5,
+        })
+    );
+    assert_eq!(error.clone(), error);
+    assert_eq!(lexer.error_span(&error.unwrap()), 7..8);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn into_anyhow() {
+    fn words(input: &'static str) -> anyhow::Result<usize> {
+        let mut count = 0;
+
+        for token in Lexer::with_buffer(RULE, input) {
+            token?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    assert_eq!(words("one two\n").unwrap(), 2);
+
+    let error = words("one ? two\n").unwrap_err();
+
+    assert_eq!(error.to_string(), "unknown token `?` at 1:5");
+    assert!(matches!(
+        error.downcast_ref::<Error>(),
+        Some(Error::UnknownToken { at: 4, .. })
+    ));
+}
@@ -0,0 +1,4 @@
+-- doubles n
+local function double(n)
+    return n + n
+end
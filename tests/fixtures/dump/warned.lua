@@ -0,0 +1,4 @@
+local x = 007
+local function f()
+ 	return x
+end
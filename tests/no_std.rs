@@ -0,0 +1,39 @@
+//! Lexes with only `core` in scope, as a `no_std` application would.
+
+#![no_std]
+
+use mile::{Lexer, Rule};
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    End,
+    Function,
+    Local,
+    Identifier(&'a str),
+}
+
+const LUA: Rule<'static, Token<'static>> = Rule::Any(&[
+    Rule::Ignore(&Rule::Whitespace),
+    Rule::IdentifierOrKeyword {
+        ident: &Rule::Alphabetic,
+        keywords: &[
+            ("end", || Token::End),
+            ("function", || Token::Function),
+            ("local", || Token::Local),
+        ],
+        fallback: Token::Identifier,
+    },
+]);
+
+#[test]
+fn lua() {
+    let mut lexer = Lexer::with_buffer(LUA, "local function f end\n");
+
+    assert!(lexer.by_ref().map(Result::unwrap).eq([
+        Token::Local,
+        Token::Function,
+        Token::Identifier("f"),
+        Token::End,
+    ]));
+    assert!(lexer.next().is_none());
+}